@@ -7,15 +7,20 @@ use core_foundation::array::CFArray;
 use core_foundation::base::{CFType, TCFType};
 use core_foundation::string::CFString;
 use std::process::Command;
+use std::time::Duration;
+
+use crate::backend::{PlaylistRef, TrackRef};
+use crate::error::{self, ERR_PLAYLIST_NOT_FOUND};
+use crate::osascript;
+use crate::script::AppleScript;
 
 const TEMP_PLAYLIST_NAME: &str = "___TempQueue___";
+// 一時プレイリストへの複製は曲数に比例して時間がかかる
+const ROTATE_TIMEOUT: Duration = Duration::from_secs(120);
 
 /// Initialize Music app (launch only) at app startup
 pub fn init_music_window_offscreen() {
-    let _ = Command::new("osascript")
-        .arg("-e")
-        .arg("tell application \"Music\" to launch")
-        .output();
+    let _ = osascript::run("tell application \"Music\" to launch");
 }
 
 fn get_music_pid() -> Option<i32> {
@@ -119,18 +124,16 @@ fn ensure_music_hidden_with_window() -> Result<(), String> {
         end tell
     "#;
 
-    Command::new("osascript")
-        .arg("-e")
-        .arg(script)
-        .output()
-        .map_err(|e| format!("Failed: {}", e))?;
+    // 以前と同様、スクリプト自体の失敗は無視する
+    let _ = osascript::run(script);
     Ok(())
 }
 
 /// Select a playlist in the sidebar (process stays hidden)
 /// Automatically expands the Playlists section if collapsed
-fn select_sidebar_item(item_name: &str) -> Result<(), String> {
-    let script = format!(
+/// Script errors (e.g. no Accessibility permission) are returned as MusicError
+fn select_sidebar_item(item_name: &str) -> anyhow::Result<()> {
+    let script = AppleScript::new(
         r#"tell application "System Events"
             tell process "Music"
                 set visible to false
@@ -155,7 +158,7 @@ fn select_sidebar_item(item_name: &str) -> Result<(), String> {
                 -- Now search for the target playlist
                 repeat with r in rows of sidebarOutline
                     try
-                        if name of UI element 1 of r is "{}" then
+                        if name of UI element 1 of r is $item_name then
                             select r
                             return "found"
                         end if
@@ -164,20 +167,15 @@ fn select_sidebar_item(item_name: &str) -> Result<(), String> {
                 return "not found"
             end tell
         end tell"#,
-        item_name.replace('"', "\\\"")
-    );
-
-    let output = Command::new("osascript")
-        .arg("-e")
-        .arg(&script)
-        .output()
-        .map_err(|e| format!("Failed to run osascript: {}", e))?;
+    )
+    .text("item_name", item_name)
+    .build()?;
 
-    let result = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let result = osascript::run(&script)?;
     if result == "found" {
         Ok(())
     } else {
-        Err(format!("'{}' not found in sidebar", item_name))
+        anyhow::bail!("'{}' not found in sidebar", item_name)
     }
 }
 
@@ -200,26 +198,34 @@ fn click_play_button() -> Result<(), String> {
 
 /// Delete the temporary playlist
 fn delete_temp_playlist() {
-    let script = format!(
+    let script = AppleScript::new(
         r#"tell application "Music"
             try
-                delete (first playlist whose name is "{}")
+                delete (first playlist whose name is $temp_name)
             end try
         end tell"#,
-        TEMP_PLAYLIST_NAME
-    );
-    let _ = Command::new("osascript")
-        .arg("-e")
-        .arg(&script)
-        .output();
+    )
+    .text("temp_name", TEMP_PLAYLIST_NAME)
+    .build();
+    if let Ok(script) = script {
+        let _ = osascript::run(&script);
+    }
 }
 
 /// Create a temporary playlist with rotated tracks
-fn create_rotated_playlist_from_playlist(playlist_name: &str, start_index: usize) -> Result<(), String> {
-    let script = format!(
+fn create_rotated_playlist_from_playlist(playlist: PlaylistRef, start_index: usize) -> anyhow::Result<()> {
+    let script = AppleScript::new(
         r#"tell application "Music"
             -- Get source playlist tracks
-            set sourcePlaylist to first playlist whose name is "{playlist_name}"
+            if $playlist_id is not "" then
+                set matchingPlaylists to (every playlist whose persistent ID is $playlist_id)
+            else
+                set matchingPlaylists to (every playlist whose name is $playlist_name)
+            end if
+            if (count of matchingPlaylists) = 0 then
+                error $playlist_name number $not_found
+            end if
+            set sourcePlaylist to item 1 of matchingPlaylists
             set allTracks to tracks of sourcePlaylist
             set trackCount to count of allTracks
 
@@ -229,49 +235,43 @@ fn create_rotated_playlist_from_playlist(playlist_name: &str, start_index: usize
 
             -- Delete existing temp playlist if exists
             try
-                delete (first playlist whose name is "{temp_name}")
+                delete (first playlist whose name is $temp_name)
             end try
 
             -- Create temp playlist with rotated track order
-            set tempPlaylist to make new playlist with properties {{name:"{temp_name}"}}
+            set tempPlaylist to make new playlist with properties {name:$temp_name}
 
             -- Add tracks from N to end
-            repeat with i from {start} to trackCount
+            repeat with i from $start to trackCount
                 duplicate (item i of allTracks) to tempPlaylist
             end repeat
 
             -- Add tracks from 1 to N-1 (if N > 1)
-            if {start} > 1 then
-                repeat with i from 1 to ({start} - 1)
+            if $start > 1 then
+                repeat with i from 1 to ($start - 1)
                     duplicate (item i of allTracks) to tempPlaylist
                 end repeat
             end if
         end tell"#,
-        playlist_name = playlist_name.replace('"', "\\\""),
-        temp_name = TEMP_PLAYLIST_NAME,
-        start = start_index + 1  // AppleScript is 1-indexed
-    );
-
-    let output = Command::new("osascript")
-        .arg("-e")
-        .arg(&script)
-        .output()
-        .map_err(|e| format!("Failed: {}", e))?;
-
-    if output.status.success() {
-        Ok(())
-    } else {
-        let err = String::from_utf8_lossy(&output.stderr);
-        Err(format!("{}", err.trim()))
-    }
+    )
+    .text("playlist_id", playlist.persistent_id)
+    .text("playlist_name", playlist.name)
+    .int("not_found", ERR_PLAYLIST_NOT_FOUND.into())
+    .text("temp_name", TEMP_PLAYLIST_NAME)
+    .int("start", start_index as i64 + 1) // AppleScript is 1-indexed
+    .build()?;
+
+    osascript::run_with_timeout(&script, ROTATE_TIMEOUT)
+        .map(|_| ())
+        .map_err(|e| error::with_playlist_id(e, playlist.persistent_id))
 }
 
 /// Create a temporary playlist with rotated tracks from an album
-fn create_rotated_playlist_from_album(album_name: &str, start_index: usize) -> Result<(), String> {
-    let script = format!(
+fn create_rotated_playlist_from_album(album_name: &str, album_artist: &str, start_index: usize) -> anyhow::Result<()> {
+    let script = AppleScript::new(
         r#"tell application "Music"
-            -- Get album tracks
-            set allTracks to (every track of library playlist 1 whose album is "{album_name}")
+            -- Get album tracks (album artist, or the track artist when it is empty)
+            set allTracks to (every track of library playlist 1 whose album is $album_name and (album artist is $album_artist or (album artist is "" and artist is $album_artist)))
             set trackCount to count of allTracks
 
             if trackCount = 0 then
@@ -280,55 +280,78 @@ fn create_rotated_playlist_from_album(album_name: &str, start_index: usize) -> R
 
             -- Delete existing temp playlist if exists
             try
-                delete (first playlist whose name is "{temp_name}")
+                delete (first playlist whose name is $temp_name)
             end try
 
             -- Create temp playlist with rotated track order
-            set tempPlaylist to make new playlist with properties {{name:"{temp_name}"}}
+            set tempPlaylist to make new playlist with properties {name:$temp_name}
 
             -- Add tracks from N to end
-            repeat with i from {start} to trackCount
+            repeat with i from $start to trackCount
                 duplicate (item i of allTracks) to tempPlaylist
             end repeat
 
             -- Add tracks from 1 to N-1 (if N > 1)
-            if {start} > 1 then
-                repeat with i from 1 to ({start} - 1)
+            if $start > 1 then
+                repeat with i from 1 to ($start - 1)
                     duplicate (item i of allTracks) to tempPlaylist
                 end repeat
             end if
         end tell"#,
-        album_name = album_name.replace('"', "\\\""),
-        temp_name = TEMP_PLAYLIST_NAME,
-        start = start_index + 1  // AppleScript is 1-indexed
-    );
-
-    let output = Command::new("osascript")
-        .arg("-e")
-        .arg(&script)
-        .output()
-        .map_err(|e| format!("Failed: {}", e))?;
+    )
+    .text("album_name", album_name)
+    .text("album_artist", album_artist)
+    .text("temp_name", TEMP_PLAYLIST_NAME)
+    .int("start", start_index as i64 + 1) // AppleScript is 1-indexed
+    .build()?;
+
+    osascript::run_with_timeout(&script, ROTATE_TIMEOUT).map(|_| ())
+}
 
-    if output.status.success() {
-        Ok(())
-    } else {
-        let err = String::from_utf8_lossy(&output.stderr);
-        Err(format!("{}", err.trim()))
-    }
+/// Create a temporary playlist from tracks given by persistent ID, in the given order
+fn create_playlist_from_tracks(track_ids: &[&str]) -> anyhow::Result<()> {
+    let script = AppleScript::new(
+        r#"tell application "Music"
+            set trackIds to paragraphs of $track_ids
+
+            -- Delete existing temp playlist if exists
+            try
+                delete (first playlist whose name is $temp_name)
+            end try
+
+            set tempPlaylist to make new playlist with properties {name:$temp_name}
+
+            -- Tracks removed since the cache was built are skipped
+            repeat with trackId in trackIds
+                try
+                    duplicate (first track of library playlist 1 whose persistent ID is (contents of trackId)) to tempPlaylist
+                end try
+            end repeat
+
+            if (count of tracks of tempPlaylist) = 0 then
+                error "No tracks found"
+            end if
+        end tell"#,
+    )
+    .text("track_ids", &track_ids.join("\n"))
+    .text("temp_name", TEMP_PLAYLIST_NAME)
+    .build()?;
+
+    osascript::run_with_timeout(&script, ROTATE_TIMEOUT).map(|_| ())
 }
 
 /// Play a playlist starting from track N (0-indexed)
-pub fn play_playlist_with_context(playlist_name: &str, track_index: usize) -> Result<(), String> {
+pub fn play_playlist_with_context(playlist: PlaylistRef, track_index: usize) -> anyhow::Result<()> {
     // Create rotated temp playlist
-    create_rotated_playlist_from_playlist(playlist_name, track_index)?;
+    create_rotated_playlist_from_playlist(playlist, track_index)?;
 
     // Ensure window exists but hidden
-    ensure_music_hidden_with_window()?;
+    ensure_music_hidden_with_window().map_err(anyhow::Error::msg)?;
 
     // Select temp playlist in sidebar and click Play
     select_sidebar_item(TEMP_PLAYLIST_NAME)?;
     std::thread::sleep(std::time::Duration::from_millis(100));
-    click_play_button()?;
+    click_play_button().map_err(anyhow::Error::msg)?;
 
     // Delete temp playlist after playback starts
     std::thread::sleep(std::time::Duration::from_millis(500));
@@ -338,17 +361,46 @@ pub fn play_playlist_with_context(playlist_name: &str, track_index: usize) -> Re
 }
 
 /// Play an album starting from track N (0-indexed)
-pub fn play_album_with_context(album_name: &str, track_index: usize) -> Result<(), String> {
+pub fn play_album_with_context(album_name: &str, album_artist: &str, track_index: usize) -> anyhow::Result<()> {
     // Create rotated temp playlist from album
-    create_rotated_playlist_from_album(album_name, track_index)?;
+    create_rotated_playlist_from_album(album_name, album_artist, track_index)?;
+
+    // Ensure window exists but hidden
+    ensure_music_hidden_with_window().map_err(anyhow::Error::msg)?;
+
+    // Select temp playlist in sidebar and click Play
+    select_sidebar_item(TEMP_PLAYLIST_NAME)?;
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    click_play_button().map_err(anyhow::Error::msg)?;
+
+    // Delete temp playlist after playback starts
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    delete_temp_playlist();
+
+    Ok(())
+}
+
+/// Play a list of tracks starting from track N (0-indexed)
+pub fn play_tracks_with_context(tracks: &[TrackRef], track_index: usize) -> anyhow::Result<()> {
+    // Rotate here so the temp playlist starts at track N
+    let ids: Vec<&str> = tracks[track_index.min(tracks.len())..]
+        .iter()
+        .chain(&tracks[..track_index.min(tracks.len())])
+        .map(|t| t.persistent_id)
+        .filter(|id| !id.is_empty())
+        .collect();
+    if ids.is_empty() {
+        anyhow::bail!("No tracks to play");
+    }
+    create_playlist_from_tracks(&ids)?;
 
     // Ensure window exists but hidden
-    ensure_music_hidden_with_window()?;
+    ensure_music_hidden_with_window().map_err(anyhow::Error::msg)?;
 
     // Select temp playlist in sidebar and click Play
     select_sidebar_item(TEMP_PLAYLIST_NAME)?;
     std::thread::sleep(std::time::Duration::from_millis(100));
-    click_play_button()?;
+    click_play_button().map_err(anyhow::Error::msg)?;
 
     // Delete temp playlist after playback starts
     std::thread::sleep(std::time::Duration::from_millis(500));
@@ -1,11 +1,19 @@
+use std::collections::{BTreeSet, HashMap};
 use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
 use std::thread;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
-use crate::accessibility;
-use crate::cache::{CachedTrack, CachedPlaylist, CachedPlaylistTrack, PlaylistCache, Settings, TrackCache};
-use crate::music::{ListItem, MusicController, TrackInfo};
+use crate::backend::{PlayerBackend, PlaylistRef, TrackRef};
+use crate::cache::{CachedTrack, CachedPlaylist, CachedPlaylistTrack, PlaylistCache, SavedSearch, SearchHistory, Settings, TrackCache};
+use crate::error::MusicError;
+use crate::fuzzy::FieldHighlights;
+use crate::music::{ListItem, PlaylistFingerprint, PlaylistInfo, PlaylistKind, SimpleTrack, TrackInfo, TrackStats};
+use crate::playlist_tree::{self, PlaylistTreeRow};
+use crate::query::Query;
+use crate::search_groups::{self, GroupKey, SearchGroup, SearchGrouping, SearchRow};
+use crate::sort::{self, SortField, SortFields, SortKey, SortView, ViewSorts};
 
 // 再生制御用コマンド（メインワーカースレッド）
 enum Command {
@@ -17,27 +25,46 @@ enum Command {
 enum Response {
     PositionUpdated(f64, bool),
     StateUpdated(TrackInfo, i32, bool, String),
+    Failed(MusicError),  // 利用者の対応が必要なエラー（未起動・権限なし）
 }
 
 // キャッシュ用レスポンス（専用スレッドから）
 enum CacheResponse {
     BatchLoaded {
         tracks: Vec<CachedTrack>,
+        malformed: Vec<String>,  // 解釈できずスキップした行
+        skipped: usize,  // これまでにスキップした行の合計
         loaded: usize,
         total: usize,
     },
     Upsert {
         tracks: Vec<CachedTrack>,
+        malformed: Vec<String>,
         total: usize,
     },
+    // ID のない古い行があったため取り直したライブラリ全体（キャッシュを置き換える）
+    Replaced {
+        tracks: Vec<CachedTrack>,
+        malformed: Vec<String>,
+        total: usize,
+    },
+    // ライブラリから削除された曲
+    Removed {
+        persistent_ids: Vec<String>,
+        total: usize,
+    },
+    // 定期同期で取得した再生回数・お気に入りなど
+    StatsUpdated(Vec<TrackStats>),
     Complete,
 }
 
 // プレイリスト読み込み用レスポンス
 enum PlaylistLoadResponse {
-    PlaylistList(Vec<ListItem>),  // プレイリスト一覧
+    PlaylistList(Vec<PlaylistInfo>),  // プレイリスト・フォルダの一覧
     Progress { current: usize, total: usize, name: String },
     PlaylistLoaded(CachedPlaylist),
+    // 削除・名前変更されたプレイリストのキャッシュキー
+    Removed(Vec<String>),
     Complete,
 }
 
@@ -55,10 +82,19 @@ pub enum DragTarget {
     CardDivider,        // Recently AddedとPlaylistsの境界
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum SearchSortMode {
-    Default,            // 検索結果のデフォルト順
-    PlayCount,          // 再生回数降順
+/// 並べ替えメニュー（o キー）。開いている間はキー入力をすべて受け取る
+#[derive(Debug, Clone, Copy)]
+pub struct SortMenu {
+    pub view: SortView,
+    pub selected: usize,  // SortField::ALL の位置
+}
+
+/// 検索履歴の逆方向インクリメンタル検索（検索ボックスで Ctrl+R）
+#[derive(Debug, Clone, Default)]
+pub struct HistorySearch {
+    pub pattern: String,
+    matched: Option<usize>,  // 表示中の履歴の位置
+    pub failing: bool,       // pattern を含む履歴がもうない
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
@@ -123,14 +159,23 @@ pub struct App {
     pub recently_added_selected: usize,
     pub recently_added_scroll: usize,
     pub content_items: Vec<ListItem>,
+    content_natural_order: Option<Vec<usize>>,  // 並べ替え中だけ各行の元の位置（再生位置の計算に使う）
     pub content_selected: usize,
     pub content_scroll: usize,
     pub content_loading: bool,
     pub content_title: String,  // アルバム/プレイリスト詳細表示時のタイトル
     pub content_source_name: String,  // 再生用のアルバム/プレイリスト名
+    pub content_source_artist: String,  // 再生用のアルバムアーティスト（アルバムのみ）
+    pub content_source_id: String,  // 再生用のプレイリスト persistent ID（アルバムでは空）
     pub is_playlist_detail: bool,  // プレイリスト詳細表示中かどうか
-
-    pub playlists: Vec<ListItem>,
+    pub content_saved_search: Option<String>,  // 表示中の保存した検索の名前（表示はプレイリスト詳細と同じ）
+    pub view_sorts: ViewSorts,  // 表示の種類ごとの並べ替え
+    pub sort_menu: Option<SortMenu>,
+
+    pub playlists: Vec<PlaylistTreeRow>,  // 表示中の行（折りたたまれたフォルダの中身を除く）
+    pub playlist_tree: Vec<PlaylistInfo>,  // フォルダを含む全プレイリスト
+    saved_searches: Vec<SavedSearch>,
+    collapsed_folders: BTreeSet<String>,
     pub playlists_selected: usize,
     pub playlists_scroll: usize,
 
@@ -147,15 +192,30 @@ pub struct App {
     pub search_mode: bool,
     pub search_query: String,
     pub search_cursor: usize,  // カーソル位置（文字数）
-    pub search_results: Vec<ListItem>,
-    pub search_sort_mode: SearchSortMode,
-    search_results_unsorted: Vec<ListItem>,  // ソート切替用にオリジナルを保持
+    pub search_results: Vec<usize>,  // cache.tracks の添字（ListItem は作らず、表示する行だけキャッシュから読む）
+    search_natural_order: Option<Vec<usize>>,  // 並べ替え中だけ各行の元の位置
+    pub search_grouping: SearchGrouping,
+    pub search_groups: Vec<SearchGroup>,
+    pub search_rows: Vec<SearchRow>,  // 検索結果の表示行（検索中の content_selected はこの位置）
+    search_collapsed: BTreeSet<GroupKey>,  // 折りたたんだグループ
+    pub fuzzy_search: bool,  // あいまい検索（スコア順）
+    pub search_highlights: HashMap<usize, FieldHighlights>,  // cache.tracks の添字 → 一致した文字の位置（あいまい検索のみ）
+    pub search_error: Option<String>,  // クエリの構文エラー
+    search_history: SearchHistory,
+    history_pos: Option<usize>,  // Up/Down で呼び出し中の履歴の位置
+    history_draft: String,  // 履歴を呼び出す前に入力していたクエリ
+    pub history_search: Option<HistorySearch>,
 
     // プレイリスト追加モード
     pub add_to_playlist_mode: bool,
     pub track_to_add: Option<ListItem>,
     pub new_playlist_input_mode: bool,
     pub new_playlist_name: String,
+
+    // 検索の保存（名前入力）
+    pub save_search_input_mode: bool,
+    pub save_search_name: String,
+    pub delete_saved_search_pending: Option<String>,  // 削除を確認中の保存した検索の名前
     pub playlist_refreshing: Option<String>,  // 更新中のプレイリスト名
 
     position_pending: bool,
@@ -177,22 +237,36 @@ pub struct App {
     playlist_load_rx: Receiver<PlaylistLoadResponse>,
 
     // プレイリスト更新用
-    playlist_refresh_rx: Option<Receiver<(String, Vec<ListItem>)>>,
+    playlist_refresh_rx: Option<Receiver<(String, String, Vec<ListItem>)>>,  // (名前, persistent ID, 曲)
 
     // ハイライトカラー
     pub highlight_color: HighlightColor,
 
+    // 起動時に読み込んだ設定（変更のたびにこれを書き換えて保存する）
+    settings: Settings,
+
     // ウェルカム画面を閉じたかどうか
     pub welcome_dismissed: bool,
+
+    // Music.app の起動を提案中（L キーで起動）
+    pub launch_offered: bool,
+
+    // プレイヤーバックエンド（ワーカースレッドと共有）
+    backend: Arc<dyn PlayerBackend>,
 }
 
 impl App {
-    pub fn new() -> Self {
-        // Initialize Music window off-screen in background
-        // This ensures the window exists before any playlist playback
-        thread::spawn(|| {
-            accessibility::init_music_window_offscreen();
-        });
+    pub fn new(backend: Box<dyn PlayerBackend>) -> Self {
+        let backend: Arc<dyn PlayerBackend> = Arc::from(backend);
+
+        // Initialize the backend in background
+        // (Music.app: ensures the window exists before any playlist playback)
+        {
+            let backend = Arc::clone(&backend);
+            thread::spawn(move || {
+                backend.launch();
+            });
+        }
 
         let (cmd_tx, cmd_rx) = mpsc::channel::<Command>();
         let (resp_tx, resp_rx) = mpsc::channel::<Response>();
@@ -203,18 +277,20 @@ impl App {
 
         // 設定を読み込み
         let settings = Settings::load();
+        let search_history = SearchHistory::load();
 
         // 再生制御用バックグラウンドスレッド（軽量・高速）
+        let worker_backend = Arc::clone(&backend);
         thread::spawn(move || {
             while let Ok(cmd) = cmd_rx.recv() {
                 match cmd {
                     Command::RefreshPosition => {
-                        let (position, is_playing) = MusicController::get_position()
+                        let (position, is_playing) = worker_backend.get_position()
                             .unwrap_or((0.0, false));
                         let _ = resp_tx.send(Response::PositionUpdated(position, is_playing));
                     }
                     Command::RefreshFull => {
-                        let state = MusicController::get_all_state();
+                        let state = worker_backend.get_all_state();
                         match state {
                             Ok(s) => {
                                 let _ = resp_tx.send(Response::StateUpdated(
@@ -224,7 +300,12 @@ impl App {
                                     s.repeat,
                                 ));
                             }
-                            Err(_) => {
+                            Err(e) => {
+                                if let Some(err @ (MusicError::NotRunning | MusicError::PermissionDenied)) =
+                                    e.downcast_ref::<MusicError>()
+                                {
+                                    let _ = resp_tx.send(Response::Failed(err.clone()));
+                                }
                                 // エラー時もレスポンスを送信してpendingフラグをリセット
                                 let _ = resp_tx.send(Response::StateUpdated(
                                     TrackInfo::default(),
@@ -239,43 +320,78 @@ impl App {
             }
         });
 
+        // キャッシュスレッドの終了通知（送信側が drop されると受信側が起きる）
+        let (cache_done_tx, cache_done_rx) = mpsc::channel::<()>();
+        let stats_resp_tx = cache_resp_tx.clone();
+
         // キャッシュ専用バックグラウンドスレッド（独立して自動実行・差分更新対応）
         {
             let cache_loaded = cache.loaded_tracks;
+            let cache_skipped = cache.skipped_tracks;
             let cache_last_updated = cache.last_updated;
             let cache_is_complete = cache.is_complete();
+            let cache_has_untracked = cache.has_untracked_tracks();
+            // 中断したキャッシュ構築の再開・削除された曲の検出用
+            let cached_ids: std::collections::HashSet<String> =
+                cache.tracks.iter().map(|t| t.persistent_id.clone()).filter(|id| !id.is_empty()).collect();
+            let cache_backend = Arc::clone(&backend);
             thread::spawn(move || {
-                let current_total = MusicController::get_total_track_count().unwrap_or(0);
+                let _done = cache_done_tx;
+                let current_total = cache_backend.get_total_track_count().unwrap_or(0);
 
                 if current_total == 0 {
                     let _ = cache_resp_tx.send(CacheResponse::Complete);
                     return;
                 }
 
+                // ID のない古い行は ID で突き合わせられないので、ライブラリ全体を一度取り直して置き換える
+                if cache_has_untracked {
+                    if let Ok(batch) = cache_backend.get_all_tracks() {
+                        let total = batch.row_count();
+                        let tracks: Vec<CachedTrack> = batch.tracks.into_iter().map(to_cached_track).collect();
+                        let _ = cache_resp_tx.send(CacheResponse::Replaced { tracks, malformed: batch.malformed, total });
+                        let _ = cache_resp_tx.send(CacheResponse::Complete);
+                        return;
+                    }
+                }
+
                 // キャッシュが完了済みの場合は差分更新（upsert方式）
                 if cache_is_complete {
                     if let Some(last_updated) = cache_last_updated {
                         // last_updated の1日前から取得して upsert
                         // これにより、キャッシュ構築中に追加された曲も確実に取得できる
                         let cutoff = last_updated.saturating_sub(86400); // 1日 = 86400秒
-                        match MusicController::get_tracks_added_since(cutoff) {
-                            Ok(tracks) => {
-                                if !tracks.is_empty() {
-                                    let cached_tracks: Vec<CachedTrack> = tracks
-                                        .into_iter()
-                                        .map(|t| CachedTrack::new(
-                                            t.name, t.artist, t.album, t.date_added,
-                                            t.year, t.track_number, t.disc_number,
-                                            t.time, t.played_count, t.favorited,
-                                        ))
-                                        .collect();
-                                    let _ = cache_resp_tx.send(CacheResponse::Upsert {
-                                        tracks: cached_tracks,
-                                        total: current_total,
-                                    });
-                                }
+                        if let Ok(batch) = cache_backend.get_tracks_added_since(cutoff) {
+                            if batch.row_count() > 0 {
+                                let cached_tracks: Vec<CachedTrack> = batch.tracks
+                                    .into_iter()
+                                    .map(to_cached_track)
+                                    .collect();
+                                let _ = cache_resp_tx.send(CacheResponse::Upsert {
+                                    tracks: cached_tracks,
+                                    malformed: batch.malformed,
+                                    total: current_total,
+                                });
+                            }
+                        }
+                    }
+
+                    // ライブラリにない曲（削除された曲）を persistent ID で突き合わせる
+                    // 空の一覧は取得の失敗とみなして何も消さない
+                    if let Ok(library_ids) = cache_backend.get_all_persistent_ids() {
+                        if !library_ids.is_empty() {
+                            let library_ids: std::collections::HashSet<String> = library_ids.into_iter().collect();
+                            let removed: Vec<String> = cached_ids
+                                .iter()
+                                .filter(|id| !library_ids.contains(*id))
+                                .cloned()
+                                .collect();
+                            if !removed.is_empty() {
+                                let _ = cache_resp_tx.send(CacheResponse::Removed {
+                                    persistent_ids: removed,
+                                    total: current_total,
+                                });
                             }
-                            Err(_) => {}
                         }
                     }
 
@@ -284,25 +400,64 @@ impl App {
                 }
 
                 // キャッシュが未完了の場合は続きから読み込む
-                let mut cache_offset = cache_loaded;
+                // まずライブラリ全体を列でまとめて取得し、取得済みの曲を除いて少しずつ送る
+                if let Ok(batch) = cache_backend.get_all_tracks() {
+                    const PROGRESS_CHUNK: usize = 1000;
+                    let total = batch.row_count();
+                    // ライブラリ全体を取り直したので、スキップした行はこの取得の分だけ
+                    let skipped = batch.malformed.len();
+                    let remaining: Vec<CachedTrack> = batch.tracks
+                        .into_iter()
+                        .filter(|t| !cached_ids.contains(&t.persistent_id))
+                        .map(to_cached_track)
+                        .collect();
+
+                    let mut loaded = total.saturating_sub(remaining.len() + batch.malformed.len());
+                    let mut malformed = batch.malformed;
+                    let mut rows = remaining.into_iter().peekable();
+                    loop {
+                        let tracks: Vec<CachedTrack> = rows.by_ref().take(PROGRESS_CHUNK).collect();
+                        let malformed = std::mem::take(&mut malformed);
+                        loaded += tracks.len() + malformed.len();
+                        // スキップした行の数は最後に入れる（途中で止まっても前回の数のまま）
+                        let done = rows.peek().is_none();
+                        let skipped = if done { skipped } else { cache_skipped };
+                        let _ = cache_resp_tx.send(CacheResponse::BatchLoaded { tracks, malformed, skipped, loaded, total });
+                        if done {
+                            break;
+                        }
+                        thread::sleep(std::time::Duration::from_millis(100));
+                    }
+                    let _ = cache_resp_tx.send(CacheResponse::Complete);
+                    return;
+                }
+
+                // 列での取得に失敗した場合は 50 曲ずつ読み込む
+                // 再開位置はライブラリの行で数える（読み込めた曲とスキップした行の合計）
+                let mut cache_offset = cache_loaded + cache_skipped;
+                let mut skipped = cache_skipped;
                 const BATCH_SIZE: usize = 50;
 
                 while cache_offset < current_total {
-                    match MusicController::get_tracks_batch(cache_offset + 1, BATCH_SIZE) {
-                        Ok(tracks) => {
-                            let cached_tracks: Vec<CachedTrack> = tracks
+                    match retry_on_timeout(|| cache_backend.get_tracks_batch(cache_offset + 1, BATCH_SIZE)) {
+                        Ok(batch) => {
+                            // 壊れた行も取得済みとして数える（同じ範囲を取り直さない）
+                            let batch_len = batch.row_count();
+                            if batch_len == 0 {
+                                break;
+                            }
+                            cache_offset += batch_len;
+                            skipped += batch.malformed.len();
+
+                            let cached_tracks: Vec<CachedTrack> = batch.tracks
                                 .into_iter()
-                                .map(|t| CachedTrack::new(
-                                    t.name, t.artist, t.album, t.date_added,
-                                    t.year, t.track_number, t.disc_number,
-                                    t.time, t.played_count, t.favorited,
-                                ))
+                                .map(to_cached_track)
                                 .collect();
-                            let batch_len = cached_tracks.len();
-                            cache_offset += batch_len;
 
                             let _ = cache_resp_tx.send(CacheResponse::BatchLoaded {
                                 tracks: cached_tracks,
+                                malformed: batch.malformed,
+                                skipped,
                                 loaded: cache_offset,
                                 total: current_total,
                             });
@@ -318,6 +473,24 @@ impl App {
             });
         }
 
+        // 再生回数・お気に入りなどの定期同期スレッド（優先度低）
+        // キャッシュの構築・差分更新が終わってから始め、以後は設定の間隔ごとに取り直す
+        if settings.stats_refresh_minutes > 0 {
+            let interval = std::time::Duration::from_secs(settings.stats_refresh_minutes * 60);
+            let stats_backend = Arc::clone(&backend);
+            thread::spawn(move || {
+                let _ = cache_done_rx.recv();
+                loop {
+                    if let Ok(stats) = stats_backend.get_track_stats() {
+                        if !stats.is_empty() && stats_resp_tx.send(CacheResponse::StatsUpdated(stats)).is_err() {
+                            return;
+                        }
+                    }
+                    thread::sleep(interval);
+                }
+            });
+        }
+
         // キャッシュからRecently Addedを初期化
         let recently_added = Self::albums_to_list_items(&cache.get_recent_albums(30));
         let cache_complete = cache.is_complete();
@@ -327,84 +500,101 @@ impl App {
 
         // プレイリストキャッシュを読み込み
         let playlist_cache = PlaylistCache::load();
-        let playlist_cache_clone = playlist_cache.playlists.keys().cloned().collect::<std::collections::HashSet<_>>();
+        // 再検証スレッドが把握しているキャッシュの中身（キー → ID・名前・変更検出用の値）
+        let mut known_playlists: HashMap<String, PlaylistFingerprint> = playlist_cache
+            .playlists
+            .iter()
+            .map(|(key, p)| (key.clone(), PlaylistFingerprint {
+                persistent_id: p.persistent_id.clone(),
+                name: p.name.clone(),
+                fingerprint: p.fingerprint.clone(),
+            }))
+            .collect();
 
-        // プレイリスト読み込み用バックグラウンドスレッド
+        // スマートプレイリストは中身が勝手に変わるので、間に短い間隔で確認する
+        let mut smart_ids: Vec<String> = playlist_cache
+            .tree
+            .iter()
+            .filter(|p| p.kind == PlaylistKind::Smart)
+            .map(|p| p.persistent_id.clone())
+            .collect();
+
+        // プレイリスト読み込み・再検証用バックグラウンドスレッド
+        // 起動時に未取得・変更されたプレイリストを取得し、以後は設定の間隔ごとに確認する
+        let playlist_backend = Arc::clone(&backend);
+        let full_interval = std::time::Duration::from_secs(settings.playlist_refresh_minutes * 60);
+        let smart_interval = std::time::Duration::from_secs(settings.smart_playlist_refresh_minutes * 60);
         thread::spawn(move || {
-            // プレイリスト一覧を取得
-            let playlists = match MusicController::get_playlists() {
-                Ok(p) => p,
-                Err(_) => {
-                    let _ = playlist_load_tx.send(PlaylistLoadResponse::Complete);
+            let mut first_pass = true;
+            let mut next_full = Some(std::time::Instant::now());
+            loop {
+                if next_full.is_some_and(|t| std::time::Instant::now() >= t) {
+                    // 全体: 一覧を送り、削除・名前変更を反映して、変わったものを取り直す
+                    if let Ok(playlists) = playlist_backend.get_playlists() {
+                        smart_ids = playlists
+                            .iter()
+                            .filter(|p| p.kind == PlaylistKind::Smart)
+                            .map(|p| p.persistent_id.clone())
+                            .collect();
+                        if playlist_load_tx.send(PlaylistLoadResponse::PlaylistList(playlists)).is_err() {
+                            return;
+                        }
+                        if let Ok(current) = retry_on_timeout(|| playlist_backend.get_playlist_fingerprints(None)) {
+                            drop_removed_playlists(&playlist_load_tx, &mut known_playlists, &current);
+                            refetch_changed_playlists(&*playlist_backend, &playlist_load_tx, &mut known_playlists, &current, first_pass);
+                        }
+                    }
+                    next_full = (!full_interval.is_zero()).then(|| std::time::Instant::now() + full_interval);
+                    first_pass = false;
+                } else if !smart_ids.is_empty() {
+                    // スマートプレイリストだけ
+                    if let Ok(current) = retry_on_timeout(|| playlist_backend.get_playlist_fingerprints(Some(&smart_ids))) {
+                        refetch_changed_playlists(&*playlist_backend, &playlist_load_tx, &mut known_playlists, &current, false);
+                    }
+                }
+                if playlist_load_tx.send(PlaylistLoadResponse::Complete).is_err() {
                     return;
                 }
-            };
-
-            // プレイリスト一覧を送信
-            let _ = playlist_load_tx.send(PlaylistLoadResponse::PlaylistList(playlists.clone()));
-
-            // キャッシュされていないプレイリストを抽出
-            let uncached: Vec<_> = playlists
-                .iter()
-                .filter(|p| !playlist_cache_clone.contains(&p.name))
-                .collect();
-
-            if uncached.is_empty() {
-                let _ = playlist_load_tx.send(PlaylistLoadResponse::Complete);
-                return;
-            }
 
-            let total = uncached.len();
-            for (i, playlist) in uncached.iter().enumerate() {
-                let _ = playlist_load_tx.send(PlaylistLoadResponse::Progress {
-                    current: i + 1,
-                    total,
-                    name: playlist.name.clone(),
-                });
-
-                // プレイリストのトラックを取得
-                if let Ok(tracks) = MusicController::get_playlist_tracks(&playlist.name) {
-                    let cached_tracks: Vec<CachedPlaylistTrack> = tracks
-                        .iter()
-                        .map(|t| CachedPlaylistTrack {
-                            name: t.name.clone(),
-                            artist: t.artist.clone(),
-                            album: t.album.clone(),
-                            year: t.year,
-                            time: t.time.clone(),
-                            played_count: t.played_count,
-                            favorited: t.favorited,
-                        })
-                        .collect();
-                    let cached_playlist = CachedPlaylist {
-                        name: playlist.name.clone(),
-                        tracks: cached_tracks,
-                    };
-                    let _ = playlist_load_tx.send(PlaylistLoadResponse::PlaylistLoaded(cached_playlist));
-                }
+                // 次の全体確認とスマートプレイリストの確認の早いほうまで待つ
+                let until_full = next_full.map(|t| t.saturating_duration_since(std::time::Instant::now()));
+                let wait = match (until_full, smart_interval.is_zero()) {
+                    (Some(full), false) => full.min(smart_interval),
+                    (Some(full), true) => full,
+                    (None, false) => smart_interval,
+                    (None, true) => return,
+                };
+                thread::sleep(wait);
             }
-            let _ = playlist_load_tx.send(PlaylistLoadResponse::Complete);
         });
 
-        // キャッシュからプレイリスト名を取得（起動時は空、バックグラウンドで読み込まれる）
-        let playlists: Vec<ListItem> = playlist_cache.playlists.keys().map(|name| {
-            ListItem {
-                name: name.clone(),
-                artist: String::new(),
-                album: String::new(),
-                time: String::new(),
-                year: 0,
-                track_number: 0,
-                played_count: 0,
-                favorited: false,
-            }
-        }).collect();
+        // 読み込み時の問題（バックアップからの復元・新しいバージョンのファイルなど）を知らせる
+        let startup_message = [&cache.load_notice, &playlist_cache.load_notice, &settings.load_notice, &search_history.load_notice]
+            .into_iter()
+            .flatten()
+            .next()
+            .cloned();
+
+        // 前回の一覧（フォルダ構成）から表示する。一覧のない古いキャッシュは名前順で並べる
+        let playlist_tree: Vec<PlaylistInfo> = if playlist_cache.tree.is_empty() {
+            let mut cached: Vec<&CachedPlaylist> = playlist_cache.playlists.values().collect();
+            cached.sort_by(|a, b| a.name.cmp(&b.name));
+            cached
+                .into_iter()
+                .map(|p| PlaylistInfo::top_level(p.name.clone(), p.persistent_id.clone()))
+                .collect()
+        } else {
+            playlist_cache.tree.clone()
+        };
+        let collapsed_folders = settings.collapsed_playlist_folders.clone();
+        let mut playlists = playlist_tree::saved_search_rows(&settings.saved_searches, &collapsed_folders);
+        playlists.extend(playlist_tree::visible_rows(&playlist_tree, &collapsed_folders, |_| true));
 
         // 起動時に最初のアルバムを読み込む（content_source_nameを初期化）
-        let (initial_content_items, initial_content_title, initial_content_source_name) =
+        let (initial_content_items, initial_content_title, initial_content_source_name, initial_content_source_artist) =
             if let Some(album_item) = recently_added.first() {
                 let album_name = &album_item.album;
-                let tracks = cache.get_tracks_by_album(album_name);
+                let tracks = cache.get_tracks_by_album(album_name, &album_item.artist);
                 let year = tracks.first().map(|t| t.year).unwrap_or(0);
                 let year_str = if year > 0 { format!(" ({})", year) } else { String::new() };
                 let title = format!("{} - {}{}", album_name, album_item.artist, year_str);
@@ -419,19 +609,20 @@ impl App {
                         track_number: t.track_number,
                         played_count: t.played_count,
                         favorited: t.favorited,
+                        persistent_id: t.persistent_id.clone(),
                     })
                     .collect();
-                (items, title, album_name.clone())
+                (items, title, album_name.clone(), album_item.artist.clone())
             } else {
-                (Vec::new(), String::new(), String::new())
+                (Vec::new(), String::new(), String::new(), String::new())
             };
 
-        Self {
+        let mut app = Self {
             track: TrackInfo::default(),
             volume: 50,
             shuffle: false,
             repeat: "off".to_string(),
-            message: None,
+            message: startup_message,
             should_quit: false,
             show_help: false,
             focus: Focus::RecentlyAdded,
@@ -440,13 +631,22 @@ impl App {
             recently_added_selected: 0,
             recently_added_scroll: 0,
             content_items: initial_content_items,
+            content_natural_order: None,
             content_selected: 0,
             content_scroll: 0,
             content_loading: false,
             content_title: initial_content_title,
             content_source_name: initial_content_source_name,
+            content_source_artist: initial_content_source_artist,
+            content_source_id: String::new(),
             is_playlist_detail: false,
+            content_saved_search: None,
+            view_sorts: settings.sort.clone(),
+            sort_menu: None,
             playlists,
+            saved_searches: settings.saved_searches.clone(),
+            playlist_tree,
+            collapsed_folders,
             playlists_selected: 0,
             playlists_scroll: 0,
             recently_added_visible: 10,  // デフォルト値、UIから更新される
@@ -459,12 +659,25 @@ impl App {
             search_query: String::new(),
             search_cursor: 0,
             search_results: Vec::new(),
-            search_sort_mode: SearchSortMode::Default,
-            search_results_unsorted: Vec::new(),
+            search_natural_order: None,
+            search_grouping: settings.search_grouping,
+            search_groups: Vec::new(),
+            search_rows: Vec::new(),
+            search_collapsed: BTreeSet::new(),
+            fuzzy_search: settings.fuzzy_search,
+            search_highlights: HashMap::new(),
+            search_error: None,
+            search_history,
+            history_pos: None,
+            history_draft: String::new(),
+            history_search: None,
             add_to_playlist_mode: false,
             track_to_add: None,
             new_playlist_input_mode: false,
             new_playlist_name: String::new(),
+            save_search_input_mode: false,
+            delete_saved_search_pending: None,
+            save_search_name: String::new(),
             playlist_refreshing: None,
             position_pending: false,
             full_pending: false,
@@ -481,8 +694,14 @@ impl App {
             playlist_load_rx,
             playlist_refresh_rx: None,
             highlight_color: settings.highlight_color,
+            settings,
             welcome_dismissed: false,
-        }
+            launch_offered: false,
+            backend,
+        };
+        // 最初に表示するアルバムにも保存した並べ替えを適用する
+        app.content_items_changed();
+        app
     }
 
     fn albums_to_list_items(albums: &[(String, String)]) -> Vec<ListItem> {
@@ -497,6 +716,7 @@ impl App {
                 track_number: 0,
                 played_count: 0,
                 favorited: false,
+                persistent_id: String::new(),
             })
             .collect()
     }
@@ -516,7 +736,7 @@ impl App {
         // - main area: terminal_height - 2 - 4 - 2 = terminal_height - 8
 
         let main_height = terminal_height.saturating_sub(8);
-        let search_height: u16 = if self.search_mode { 3 } else { 3 };
+        let search_height: u16 = 3;
         let playlists_height = main_height.saturating_sub(search_height + self.recently_added_height);
 
         // Recently Added: 動的なサイズ
@@ -570,6 +790,9 @@ impl App {
                         self.repeat = repeat;
                         self.full_pending = false;
                     }
+                    Response::Failed(err) => {
+                        self.report_error(&err.into());
+                    }
                 },
                 Err(TryRecvError::Empty) => break,
                 Err(TryRecvError::Disconnected) => break,
@@ -582,22 +805,25 @@ impl App {
         loop {
             match self.cache_resp_rx.try_recv() {
                 Ok(resp) => match resp {
-                    CacheResponse::BatchLoaded { tracks, loaded, total } => {
+                    CacheResponse::BatchLoaded { tracks, malformed, skipped, loaded, total } => {
+                        let batch_len = tracks.len() + malformed.len();
+                        self.report_malformed_tracks(&malformed);
                         self.cache.add_tracks(tracks);
+                        self.cache.skipped_tracks = skipped;
                         self.cache.total_tracks = total;
 
                         // Recently Addedを更新（キャッシュから最新30アルバム）
                         self.recently_added = Self::albums_to_list_items(&self.cache.get_recent_albums(30));
 
-                        // 定期的に保存（100曲ごと）、完了時はタイムスタンプも更新
+                        // 定期的に保存（100曲の区切りをまたぐごと）、完了時はタイムスタンプも更新
                         if loaded >= total {
                             self.cache.update_timestamp();
                             let _ = self.cache.save();
-                        } else if loaded % 100 == 0 {
+                        } else if loaded % 100 < batch_len {
                             let _ = self.cache.save();
                         }
                     }
-                    CacheResponse::Upsert { tracks, total } => {
+                    CacheResponse::Upsert { tracks, malformed, total } => {
                         // 差分更新（upsert）
                         let added = self.cache.upsert_tracks(tracks);
                         self.cache.total_tracks = total;
@@ -606,11 +832,68 @@ impl App {
                         self.recently_added = Self::albums_to_list_items(&self.cache.get_recent_albums(30));
 
                         if added > 0 {
+                            self.refresh_saved_search_view();
                             self.message = Some(format!("{} new tracks added", added));
                             // 新規トラックが追加された場合のみタイムスタンプを更新
                             self.cache.update_timestamp();
                             let _ = self.cache.save();
                         }
+                        self.report_malformed_tracks(&malformed);
+                    }
+                    CacheResponse::Replaced { tracks, malformed, total } => {
+                        self.report_malformed_tracks(&malformed);
+                        self.cache.replace_tracks(tracks);
+                        self.cache.skipped_tracks = malformed.len();
+                        self.cache.total_tracks = total;
+                        // 検索結果はキャッシュの添字なので検索し直す
+                        if self.search_mode {
+                            self.do_search();
+                        }
+                        self.refresh_saved_search_view();
+                        self.recently_added = Self::albums_to_list_items(&self.cache.get_recent_albums(30));
+                        self.recently_added_selected = self.recently_added_selected
+                            .min(self.recently_added.len().saturating_sub(1));
+                        self.cache.update_timestamp();
+                        let _ = self.cache.save();
+                    }
+                    CacheResponse::Removed { persistent_ids, total } => {
+                        let removed = self.cache.remove_tracks(&persistent_ids.into_iter().collect());
+                        self.cache.total_tracks = total;
+                        if removed > 0 {
+                            // 検索結果はキャッシュの添字なので検索し直す
+                            if self.search_mode {
+                                self.do_search();
+                            }
+                            self.refresh_saved_search_view();
+                            self.recently_added = Self::albums_to_list_items(&self.cache.get_recent_albums(30));
+                            self.recently_added_selected = self.recently_added_selected
+                                .min(self.recently_added.len().saturating_sub(1));
+                            self.message = Some(format!("{} tracks removed", removed));
+                            self.cache.update_timestamp();
+                            let _ = self.cache.save();
+                        }
+                    }
+                    CacheResponse::StatsUpdated(stats) => {
+                        let stats: HashMap<String, TrackStats> =
+                            stats.into_iter().map(|s| (s.persistent_id.clone(), s)).collect();
+                        let tracks_changed = self.cache.apply_stats(&stats) > 0;
+                        // プレイリストのキャッシュにも書き込む（開き直したときに古い値に戻さない）
+                        let playlists_changed = self.playlist_cache.apply_stats(&stats) > 0;
+                        if tracks_changed || playlists_changed {
+                            // 表示中の一覧にも反映する（並び順はそのまま）
+                            for item in self.content_items.iter_mut() {
+                                if let Some(s) = stats.get(&item.persistent_id) {
+                                    item.played_count = s.played_count;
+                                    item.favorited = s.favorited;
+                                }
+                            }
+                        }
+                        if tracks_changed {
+                            let _ = self.cache.save();
+                        }
+                        if playlists_changed {
+                            let _ = self.playlist_cache.save();
+                        }
                     }
                     CacheResponse::Complete => {
                         self.cache_loading = false;
@@ -626,21 +909,96 @@ impl App {
         }
     }
 
+    /// バックエンドのエラーを種類に応じて表示・対応する
+    fn report_error(&mut self, e: &anyhow::Error) {
+        match e.downcast_ref::<MusicError>() {
+            Some(MusicError::NotRunning) => {
+                self.launch_offered = true;
+                self.message = Some("Music.app is not running. Press L to launch it".to_string());
+            }
+            Some(MusicError::PermissionDenied) => {
+                self.message = Some(
+                    "Allow your terminal to control Music in System Settings > Privacy & Security > Automation"
+                        .to_string(),
+                );
+            }
+            Some(MusicError::PlaylistNotFound { persistent_id, name }) => {
+                // 削除されたプレイリストは一覧とキャッシュから外す
+                // 同名のプレイリストを巻き込まないよう ID で特定できたときだけ
+                if !persistent_id.is_empty() {
+                    let id = persistent_id.clone();
+                    self.playlist_tree.retain(|p| p.persistent_id != id);
+                    self.rebuild_playlist_rows();
+                    self.playlist_cache.tree.retain(|p| p.persistent_id != id);
+                    if self.playlist_cache.playlists.remove(&id).is_some() {
+                        let _ = self.playlist_cache.save();
+                    }
+                }
+                self.message = Some(format!("Playlist '{}' no longer exists", name));
+            }
+            Some(MusicError::Timeout) => {
+                self.message = Some("Music.app did not respond in time. Try again".to_string());
+            }
+            _ => {
+                self.message = Some(format!("Error: {}", e));
+            }
+        }
+    }
+
+    /// 提案中であれば Music.app を起動する
+    pub fn launch_player(&mut self) {
+        if !self.launch_offered {
+            return;
+        }
+        self.launch_offered = false;
+        self.message = Some("Launching Music.app...".to_string());
+        let backend = Arc::clone(&self.backend);
+        thread::spawn(move || {
+            backend.launch();
+        });
+    }
+
+    /// 解釈できずにスキップしたトラックを通知
+    fn report_malformed_tracks(&mut self, malformed: &[String]) {
+        if let Some(first) = malformed.first() {
+            self.message = Some(format!("Skipped {} malformed tracks ({})", malformed.len(), first));
+        }
+    }
+
     /// プレイリスト読み込みスレッドからのレスポンスを処理
     pub fn poll_playlist_responses(&mut self) {
         loop {
             match self.playlist_load_rx.try_recv() {
                 Ok(resp) => match resp {
                     PlaylistLoadResponse::PlaylistList(items) => {
-                        // プレイリスト一覧を更新
-                        self.playlists = items;
+                        // 一覧とフォルダ構成を更新（保存は Complete で）
+                        self.playlist_cache.tree = items.clone();
+                        self.playlist_tree = items;
+                        self.rebuild_playlist_rows();
                     }
                     PlaylistLoadResponse::Progress { current, total, name } => {
                         self.playlist_loading_progress = format!("Loading playlists ({}/{}) {}...", current, total, name);
                     }
                     PlaylistLoadResponse::PlaylistLoaded(playlist) => {
+                        // 表示中のプレイリストが変わっていれば表示も更新
+                        if self.is_playlist_detail && !self.search_mode
+                            && !playlist.persistent_id.is_empty() && self.content_source_id == playlist.persistent_id
+                        {
+                            self.content_items = cached_playlist_items(&playlist);
+                            self.content_items_changed();
+                            self.content_title = playlist.name.clone();
+                            self.content_source_name = playlist.name.clone();
+                            let len = self.content_items.len();
+                            self.content_selected = self.content_selected.min(len.saturating_sub(1));
+                            self.adjust_scroll(len);
+                        }
                         self.playlist_cache.insert(playlist);
                     }
+                    PlaylistLoadResponse::Removed(keys) => {
+                        for key in keys {
+                            self.playlist_cache.playlists.remove(&key);
+                        }
+                    }
                     PlaylistLoadResponse::Complete => {
                         self.playlist_loading = false;
                         self.playlist_loading_progress.clear();
@@ -673,43 +1031,43 @@ impl App {
 
     pub fn play_pause(&mut self) {
         self.track.is_playing = !self.track.is_playing;
-        if let Err(e) = MusicController::play_pause() {
-            self.message = Some(format!("Error: {}", e));
+        if let Err(e) = self.backend.play_pause() {
+            self.report_error(&e);
         }
     }
 
     pub fn next_track(&mut self) {
-        if let Err(e) = MusicController::next_track() {
-            self.message = Some(format!("Error: {}", e));
+        if let Err(e) = self.backend.next_track() {
+            self.report_error(&e);
         }
     }
 
     pub fn previous_track(&mut self) {
-        if let Err(e) = MusicController::previous_track() {
-            self.message = Some(format!("Error: {}", e));
+        if let Err(e) = self.backend.previous_track() {
+            self.report_error(&e);
         }
     }
 
     pub fn toggle_shuffle(&mut self) {
         // 同期的に実行して即座にフィードバック
-        match MusicController::toggle_shuffle() {
+        match self.backend.toggle_shuffle() {
             Ok(state) => {
                 self.shuffle = state;
             }
             Err(e) => {
-                self.message = Some(format!("Error: {}", e));
+                self.report_error(&e);
             }
         }
     }
 
     pub fn cycle_repeat(&mut self) {
         // 同期的に実行して即座にフィードバック
-        match MusicController::cycle_repeat() {
+        match self.backend.cycle_repeat() {
             Ok(mode) => {
                 self.repeat = mode;
             }
             Err(e) => {
-                self.message = Some(format!("Error: {}", e));
+                self.report_error(&e);
             }
         }
     }
@@ -724,24 +1082,28 @@ impl App {
 
     pub fn cycle_highlight_color(&mut self) {
         self.highlight_color = self.highlight_color.next();
-        // 設定を保存
-        let settings = Settings {
-            highlight_color: self.highlight_color,
-        };
-        let _ = settings.save();
+        self.settings.highlight_color = self.highlight_color;
+        self.save_settings();
+    }
+
+    /// 設定を保存（新しいバージョンの設定ファイルは書き換えない）
+    fn save_settings(&mut self) {
+        if let Err(e) = self.settings.save() {
+            self.message = Some(e.to_string());
+        }
     }
 
     pub fn seek_backward(&mut self) {
         self.track.position = (self.track.position - 10.0).max(0.0);
-        if let Err(e) = MusicController::seek_backward() {
-            self.message = Some(format!("Error: {}", e));
+        if let Err(e) = self.backend.seek_backward() {
+            self.report_error(&e);
         }
     }
 
     pub fn seek_forward(&mut self) {
         self.track.position = (self.track.position + 10.0).min(self.track.duration);
-        if let Err(e) = MusicController::seek_forward() {
-            self.message = Some(format!("Error: {}", e));
+        if let Err(e) = self.backend.seek_forward() {
+            self.report_error(&e);
         }
     }
 
@@ -767,10 +1129,8 @@ impl App {
             Focus::RecentlyAdded => {
                 self.load_selected_album_tracks();
             }
-            Focus::Playlists => {
-                if !self.playlists.is_empty() {
-                    self.load_selected_playlist_tracks();
-                }
+            Focus::Playlists if !self.playlists.is_empty() => {
+                self.load_selected_playlist_tracks();
             }
             _ => {}
         }
@@ -778,23 +1138,18 @@ impl App {
 
     /// h: 左カラムへ移動（元いた左ペインに戻り、詳細を再読み込み）
     pub fn focus_left(&mut self) {
-        match self.focus {
-            Focus::Content => {
-                self.focus = self.last_left_focus;
-                // 戻り先に応じて詳細画面を再読み込み
-                match self.last_left_focus {
-                    Focus::RecentlyAdded => {
-                        self.load_selected_album_tracks();
-                    }
-                    Focus::Playlists => {
-                        if !self.playlists.is_empty() {
-                            self.load_selected_playlist_tracks();
-                        }
-                    }
-                    _ => {}
+        if self.focus == Focus::Content {
+            self.focus = self.last_left_focus;
+            // 戻り先に応じて詳細画面を再読み込み
+            match self.last_left_focus {
+                Focus::RecentlyAdded => {
+                    self.load_selected_album_tracks();
                 }
+                Focus::Playlists if !self.playlists.is_empty() => {
+                    self.load_selected_playlist_tracks();
+                }
+                _ => {}
             }
-            _ => {}
         }
     }
 
@@ -807,30 +1162,36 @@ impl App {
                 self.content_selected = 0;
                 self.content_scroll = 0;
             }
-            Focus::Content => {
-                // プレイリスト詳細表示中の場合、選択中の曲のアルバム全曲を表示
-                if self.is_playlist_detail {
-                    if let Some(item) = self.content_items.get(self.content_selected) {
-                        let album_name = item.album.clone();
-                        self.show_album_tracks(&album_name);
-                    }
+            // プレイリスト詳細表示中の場合、選択中の曲のアルバム全曲を表示
+            Focus::Content if self.is_playlist_detail => {
+                if let Some(item) = self.content_items.get(self.content_selected) {
+                    let (album_name, artist, id) = (item.album.clone(), item.artist.clone(), item.persistent_id.clone());
+                    // アルバムアーティストはライブラリの曲から引く（プレイリストの曲にはない）
+                    let album_artist = self
+                        .cache
+                        .track_by_id(&id)
+                        .map(|t| t.album_artist_or_artist().to_string())
+                        .unwrap_or(artist);
+                    self.show_album_tracks(&album_name, &album_artist);
                 }
             }
             _ => {}
         }
     }
 
-    /// アルバム名からアルバム全曲を詳細画面に表示（フォーカスはContentのまま）
-    pub fn show_album_tracks(&mut self, album_name: &str) {
-        let tracks = self.cache.get_tracks_by_album(album_name);
+    /// アルバム名とアルバムアーティストからアルバム全曲を詳細画面に表示（フォーカスはContentのまま）
+    pub fn show_album_tracks(&mut self, album_name: &str, album_artist: &str) {
+        let tracks = self.cache.get_tracks_by_album(album_name, album_artist);
         if !tracks.is_empty() {
             let year = tracks.first().map(|t| t.year).unwrap_or(0);
             let year_str = if year > 0 { format!(" ({})", year) } else { String::new() };
-            let artist = tracks.first().map(|t| t.artist.as_str()).unwrap_or("");
-            
-            self.content_title = format!("{} - {}{}", album_name, artist, year_str);
+
+            self.content_title = format!("{} - {}{}", album_name, album_artist, year_str);
             self.content_source_name = album_name.to_string();
+            self.content_source_artist = album_artist.to_string();
+            self.content_source_id.clear();
             self.is_playlist_detail = false;
+            self.content_saved_search = None;
             self.content_items = tracks
                 .into_iter()
                 .map(|t| ListItem {
@@ -842,8 +1203,10 @@ impl App {
                     track_number: t.track_number,
                     played_count: t.played_count,
                     favorited: t.favorited,
+                    persistent_id: t.persistent_id.clone(),
                 })
                 .collect();
+            self.content_items_changed();
             self.content_selected = 0;
             self.content_scroll = 0;
         }
@@ -886,7 +1249,7 @@ impl App {
         // 通常のクリック処理
         if x < column_divider_x {
             if relative_y < search_height {
-                return false;
+                false
             } else if relative_y < search_height + self.recently_added_height {
                 // Recently Added
                 let card_y = relative_y - search_height;
@@ -902,7 +1265,7 @@ impl App {
                 }
                 self.focus = Focus::RecentlyAdded;
                 self.last_left_focus = Focus::RecentlyAdded;
-                return true;
+                true
             } else {
                 // Playlists
                 let card_start = search_height + self.recently_added_height;
@@ -919,21 +1282,20 @@ impl App {
                 }
                 self.focus = Focus::Playlists;
                 self.last_left_focus = Focus::Playlists;
-                return true;
+                true
             }
         } else {
             // Right column (Content)
             if relative_y >= 3 {
                 let item_index = (relative_y - 3) as usize + self.content_scroll;
-                let items = if self.search_mode { &self.search_results } else { &self.content_items };
-                if item_index < items.len() {
+                if item_index < self.content_len() {
                     self.content_selected = item_index;
                     self.focus = Focus::Content;
                     return true;
                 }
             }
             self.focus = Focus::Content;
-            return true;
+            true
         }
     }
 
@@ -1011,7 +1373,7 @@ impl App {
     pub fn load_selected_album_tracks(&mut self) {
         if let Some(album_item) = self.recently_added.get(self.recently_added_selected) {
             let album_name = &album_item.album;
-            let tracks = self.cache.get_tracks_by_album(album_name);
+            let tracks = self.cache.get_tracks_by_album(album_name, &album_item.artist);
 
             // 年を取得（最初のトラックから）
             let year = tracks.first().map(|t| t.year).unwrap_or(0);
@@ -1019,7 +1381,10 @@ impl App {
 
             self.content_title = format!("{} - {}{}", album_name, album_item.artist, year_str);
             self.content_source_name = album_name.clone();
+            self.content_source_artist = album_item.artist.clone();
+            self.content_source_id.clear();
             self.is_playlist_detail = false;
+            self.content_saved_search = None;
             self.content_items = tracks
                 .into_iter()
                 .map(|t| ListItem {
@@ -1031,87 +1396,127 @@ impl App {
                     track_number: t.track_number,
                     played_count: t.played_count,
                     favorited: t.favorited,
+                    persistent_id: t.persistent_id.clone(),
                 })
                 .collect();
+            self.content_items_changed();
             self.content_selected = 0;
             self.content_scroll = 0;
         }
     }
 
-    /// 選択中のプレイリストのトラックを読み込む
+    /// 選択中のプレイリストのトラックを読み込む（保存した検索なら検索し直す）
     pub fn load_selected_playlist_tracks(&mut self) {
         if let Some(playlist_item) = self.playlists.get(self.playlists_selected) {
+            if let Some(name) = playlist_item.saved_search_name() {
+                let name = name.to_string();
+                self.content_selected = 0;
+                self.content_scroll = 0;
+                self.show_saved_search(&name);
+                return;
+            }
+            // 保存した検索の見出しは中身がないので表示を変えない
+            if playlist_item.persistent_id == playlist_tree::SAVED_SEARCHES_ID {
+                return;
+            }
             let playlist_name = playlist_item.name.clone();
+            let playlist_id = playlist_item.persistent_id.clone();
             self.content_title = playlist_name.clone();
             self.content_source_name = playlist_name.clone();
+            self.content_source_id = playlist_id.clone();
             self.is_playlist_detail = true;
+            self.content_saved_search = None;
+            let playlist = PlaylistRef { persistent_id: &playlist_id, name: &playlist_name };
 
             // キャッシュを確認
-            if let Some(cached) = self.playlist_cache.get(&playlist_name) {
+            if let Some(cached) = self.playlist_cache.get(playlist) {
                 // キャッシュから読み込み
-                self.content_items = cached.tracks.iter().map(|t| ListItem {
-                    name: t.name.clone(),
-                    artist: t.artist.clone(),
-                    album: t.album.clone(),
-                    year: t.year,
-                    time: t.time.clone(),
-                    played_count: t.played_count,
-                    favorited: t.favorited,
-                    track_number: 0,
-                }).collect();
+                self.content_items = cached_playlist_items(cached);
             } else {
                 // キャッシュになければAppleScriptで取得
                 self.content_loading = true;
-                match MusicController::get_playlist_tracks(&playlist_name) {
+                match self.backend.get_playlist_tracks(playlist) {
                     Ok(tracks) => {
-                        // キャッシュに保存
-                        let cached_tracks: Vec<CachedPlaylistTrack> = tracks.iter().map(|t| {
-                            CachedPlaylistTrack {
-                                name: t.name.clone(),
-                                artist: t.artist.clone(),
-                                album: t.album.clone(),
-                                year: t.year,
-                                time: t.time.clone(),
-                                played_count: t.played_count,
-                                favorited: t.favorited,
-                            }
-                        }).collect();
-                        let cached_playlist = CachedPlaylist {
-                            name: playlist_name.clone(),
-                            tracks: cached_tracks,
-                        };
-                        self.playlist_cache.insert(cached_playlist);
+                        // キャッシュに保存（変更検出用の値は次の再検証で入る）
+                        self.playlist_cache.insert(to_cached_playlist(playlist, "", &tracks));
                         let _ = self.playlist_cache.save();
 
                         self.content_items = tracks;
                     }
-                    Err(_) => {
+                    Err(e) => {
                         self.content_items = Vec::new();
+                        self.report_error(&e);
                     }
                 }
                 self.content_loading = false;
             }
+            self.content_items_changed();
             self.content_selected = 0;
             self.content_scroll = 0;
         }
     }
 
 
+    /// 保存した検索をキャッシュに対して実行して詳細に表示する（選択位置はできるだけ保つ）
+    fn show_saved_search(&mut self, name: &str) {
+        let Some(search) = self.saved_searches.iter().find(|s| s.name == name) else {
+            return;
+        };
+        let query = match Query::parse(&search.query) {
+            Ok(query) => Some(query),
+            Err(e) => {
+                self.message = Some(format!("Saved search '{}': {}", name, e));
+                None
+            }
+        };
+        let mut tracks: Vec<&CachedTrack> = query
+            .map(|q| self.cache.search(&q))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|i| &self.cache.tracks[i])
+            .collect();
+        tracks.sort_by(|a, b| default_track_order(a, b));
+        self.content_items = tracks.into_iter().map(track_list_item).collect();
+
+        self.content_title = name.to_string();
+        self.content_source_name = name.to_string();
+        self.content_source_id.clear();
+        self.is_playlist_detail = true;
+        self.content_saved_search = Some(name.to_string());
+        self.content_items_changed();
+        let len = self.content_items.len();
+        self.content_selected = self.content_selected.min(len.saturating_sub(1));
+        self.adjust_scroll(len);
+    }
+
+    /// 保存した検索を表示中なら、キャッシュの変更に合わせて検索し直す
+    fn refresh_saved_search_view(&mut self) {
+        if let Some(name) = self.content_saved_search.clone() {
+            self.show_saved_search(&name);
+        }
+    }
+
     /// プレイリストを強制リフレッシュ（キャッシュを無視して再取得）
     pub fn refresh_current_playlist(&mut self) {
+        // 保存した検索はキャッシュを検索し直すだけ
+        if self.content_saved_search.is_some() {
+            self.refresh_saved_search_view();
+            return;
+        }
         if !self.is_playlist_detail {
             self.message = Some("Not viewing a playlist".to_string());
             return;
         }
         
         let playlist_name = self.content_source_name.clone();
+        let playlist_id = self.content_source_id.clone();
         if playlist_name.is_empty() {
             return;
         }
         
         self.message = Some(format!("Refreshing {}...", playlist_name));
         // 非同期でリフレッシュ（スピナー表示）
-        self.refresh_playlist_cache(&playlist_name);
+        self.refresh_playlist_cache(PlaylistRef { persistent_id: &playlist_id, name: &playlist_name });
     }
 
     fn adjust_recently_added_scroll(&mut self) {
@@ -1171,17 +1576,20 @@ impl App {
         }
     }
 
+    /// 詳細カードの行数（検索中は見出しを含む表示行）
+    fn content_len(&self) -> usize {
+        if self.search_mode { self.search_rows.len() } else { self.content_items.len() }
+    }
+
     pub fn content_up(&mut self) {
-        let items = if self.search_mode { &self.search_results } else { &self.content_items };
         if self.content_selected > 0 {
             self.content_selected -= 1;
         }
-        self.adjust_scroll(items.len());
+        self.adjust_scroll(self.content_len());
     }
 
     pub fn content_down(&mut self) {
-        let items = if self.search_mode { &self.search_results } else { &self.content_items };
-        let len = items.len();
+        let len = self.content_len();
         if self.content_selected < len.saturating_sub(1) {
             self.content_selected += 1;
         }
@@ -1195,8 +1603,7 @@ impl App {
     }
 
     pub fn content_bottom(&mut self) {
-        let items = if self.search_mode { &self.search_results } else { &self.content_items };
-        let len = items.len();
+        let len = self.content_len();
         if len > 0 {
             self.content_selected = len - 1;
             self.adjust_scroll(len);
@@ -1217,29 +1624,48 @@ impl App {
 
     pub fn play_selected(&mut self) {
         if self.search_mode {
+            // 見出しならグループ全体を先頭から再生
+            if let Some(&SearchRow::Header(g)) = self.search_rows.get(self.content_selected) {
+                self.play_search_group(g);
+                return;
+            }
             // 検索結果からの再生
-            if let Some(item) = self.search_results.get(self.content_selected) {
-                let result = MusicController::play_track(&item.name, &item.artist);
+            if let Some(item) = self.selected_search_track() {
+                let result = self.backend.play_track(item.track_ref());
                 match result {
                     Ok(_) => {
                         self.message = Some(format!("▶ {}", item.name));
                     }
                     Err(e) => {
-                        self.message = Some(format!("Error: {}", e));
+                        self.report_error(&e);
+                    }
+                }
+            }
+        } else if self.content_saved_search.is_some() {
+            // 保存した検索からの再生（検索結果と同じく選択した曲だけ）
+            if let Some(item) = self.content_items.get(self.content_selected) {
+                match self.backend.play_track(item.track_ref()) {
+                    Ok(_) => {
+                        self.message = Some(format!("▶ {}", item.name));
+                    }
+                    Err(e) => {
+                        self.report_error(&e);
                     }
                 }
             }
         } else if self.is_playlist_detail {
             // プレイリスト詳細からの再生 - 選択した曲から巡回再生
             let playlist_name = self.content_source_name.clone();
-            let track_index = self.content_selected;
+            let playlist_id = self.content_source_id.clone();
+            let track_index = self.natural_content_index();
             if !playlist_name.is_empty() {
-                if let Some(item) = self.content_items.get(track_index) {
+                if let Some(item) = self.content_items.get(self.content_selected) {
                     self.message = Some(format!("▶ {}", item.name));
                 }
                 // 同期的に実行（競合を避けるため）
-                if let Err(e) = accessibility::play_playlist_with_context(&playlist_name, track_index) {
-                    self.message = Some(format!("Error: {}", e));
+                let playlist = PlaylistRef { persistent_id: &playlist_id, name: &playlist_name };
+                if let Err(e) = self.backend.play_playlist_with_context(playlist, track_index) {
+                    self.report_error(&e);
                 }
             }
         } else {
@@ -1248,14 +1674,14 @@ impl App {
                 .first()
                 .map(|item| item.album.clone())
                 .unwrap_or_else(|| self.content_source_name.clone());
-            let track_index = self.content_selected;
+            let track_index = self.natural_content_index();
             if !album_name.is_empty() {
-                if let Some(item) = self.content_items.get(track_index) {
+                if let Some(item) = self.content_items.get(self.content_selected) {
                     self.message = Some(format!("▶ {}", item.name));
                 }
                 // 同期的に実行（競合を避けるため）
-                if let Err(e) = accessibility::play_album_with_context(&album_name, track_index) {
-                    self.message = Some(format!("Error: {}", e));
+                if let Err(e) = self.backend.play_album_with_context(&album_name, &self.content_source_artist, track_index) {
+                    self.report_error(&e);
                 }
             }
         }
@@ -1265,16 +1691,24 @@ impl App {
         self.search_mode = true;
         self.search_query.clear();
         self.search_cursor = 0;
+        self.history_pos = None;
+        self.history_search = None;
         self.focus = Focus::Search;
         self.do_search();  // 空クエリで全曲表示
     }
 
     pub fn cancel_search(&mut self) {
+        self.record_search();
         self.search_mode = false;
         self.search_query.clear();
         self.search_cursor = 0;
         self.search_results.clear();
-        self.search_results_unsorted.clear();
+        self.search_natural_order = None;
+        self.search_groups.clear();
+        self.search_rows.clear();
+        self.search_collapsed.clear();
+        self.search_highlights.clear();
+        self.search_error = None;
         self.focus = Focus::RecentlyAdded;
     }
 
@@ -1283,6 +1717,7 @@ impl App {
         let byte_pos = self.search_query.chars().take(self.search_cursor).map(|c| c.len_utf8()).sum();
         self.search_query.insert(byte_pos, c);
         self.search_cursor += 1;
+        self.history_pos = None;
         self.do_search();
     }
 
@@ -1295,6 +1730,7 @@ impl App {
                 self.search_cursor -= 1;
             }
         }
+        self.history_pos = None;
         self.do_search();
     }
 
@@ -1327,47 +1763,169 @@ impl App {
     pub fn search_kill_line(&mut self) {
         let byte_pos: usize = self.search_query.chars().take(self.search_cursor).map(|c| c.len_utf8()).sum();
         self.search_query.truncate(byte_pos);
+        self.history_pos = None;
         self.do_search();
     }
 
-    fn do_search(&mut self) {
-        // キャッシュから検索（高速・同期）
-        let mut results: Vec<_> = self.cache
-            .search(&self.search_query)
-            .into_iter()
-            .collect();
+    /// クエリを置き換えてカーソルを行末に（履歴の呼び出し）
+    fn replace_search_query(&mut self, query: String) {
+        self.search_query = query;
+        self.search_cursor = self.search_query.chars().count();
+        self.do_search();
+    }
 
-        // Artist昇順, Year昇順, Album昇順, Disc昇順, Track昇順 でソート
-        results.sort_by(|a, b| {
-            a.artist.cmp(&b.artist)
-                .then_with(|| a.year.cmp(&b.year))
-                .then_with(|| a.album.cmp(&b.album))
-                .then_with(|| a.disc_number.cmp(&b.disc_number))
-                .then_with(|| a.track_number.cmp(&b.track_number))
-        });
+    /// ひとつ前の履歴を呼び出す (Up / Ctrl+P)
+    pub fn search_history_prev(&mut self) {
+        let pos = match self.history_pos {
+            Some(pos) => pos,
+            None => {
+                self.history_draft = self.search_query.clone();
+                self.search_history.queries.len()
+            }
+        };
+        if pos == 0 {
+            return;
+        }
+        self.history_pos = Some(pos - 1);
+        self.replace_search_query(self.search_history.queries[pos - 1].clone());
+    }
 
-        // 全結果をListItemに変換
-        self.search_results = results
-            .into_iter()
-            .map(|t| ListItem {
-                name: t.name.clone(),
-                artist: t.artist.clone(),
-                album: t.album.clone(),
-                time: t.time.clone(),
-                year: t.year,
-                track_number: t.track_number,
-                played_count: t.played_count,
-                favorited: t.favorited,
-            })
-            .collect();
+    /// ひとつ後の履歴を呼び出す。最新より後は呼び出す前の入力に戻る (Down / Ctrl+N)
+    pub fn search_history_next(&mut self) {
+        let Some(pos) = self.history_pos else {
+            return;
+        };
+        if pos + 1 < self.search_history.queries.len() {
+            self.history_pos = Some(pos + 1);
+            self.replace_search_query(self.search_history.queries[pos + 1].clone());
+        } else {
+            self.history_pos = None;
+            let draft = std::mem::take(&mut self.history_draft);
+            self.replace_search_query(draft);
+        }
+    }
+
+    /// Ctrl+R: 履歴の逆方向検索を始める。検索中ならさらに古い一致へ
+    pub fn search_history_reverse(&mut self) {
+        match &self.history_search {
+            Some(search) => {
+                let before = search.matched.unwrap_or(self.search_history.queries.len());
+                self.run_history_search(before);
+            }
+            None => {
+                self.history_draft = self.search_query.clone();
+                self.history_search = Some(HistorySearch::default());
+            }
+        }
+    }
+
+    pub fn history_search_input(&mut self, c: char) {
+        let Some(search) = &mut self.history_search else { return };
+        search.pattern.push(c);
+        // 表示中の履歴がまだ一致するならそのまま
+        let before = search.matched.map(|i| i + 1).unwrap_or(self.search_history.queries.len());
+        self.run_history_search(before);
+    }
+
+    pub fn history_search_backspace(&mut self) {
+        let Some(search) = &mut self.history_search else { return };
+        search.pattern.pop();
+        self.run_history_search(self.search_history.queries.len());
+    }
+
+    /// before より古い履歴から探して表示する。見つからなければ表示中のものを残す
+    fn run_history_search(&mut self, before: usize) {
+        let Some(search) = &mut self.history_search else { return };
+        match self.search_history.find_before(&search.pattern, before) {
+            Some(i) => {
+                search.matched = Some(i);
+                search.failing = false;
+                self.replace_search_query(self.search_history.queries[i].clone());
+            }
+            None => search.failing = true,
+        }
+    }
+
+    /// 表示中の履歴で検索を続ける（Up/Down はその位置から）
+    pub fn accept_history_search(&mut self) {
+        if let Some(search) = self.history_search.take() {
+            self.history_pos = search.matched;
+        }
+    }
+
+    /// Ctrl+R を始める前のクエリに戻す (Esc / Ctrl+G)
+    pub fn cancel_history_search(&mut self) {
+        if self.history_search.take().is_some() {
+            let draft = std::mem::take(&mut self.history_draft);
+            self.replace_search_query(draft);
+        }
+    }
+
+    /// 確定したクエリを履歴に追加して保存する（構文エラーのものは残さない）
+    fn record_search(&mut self) {
+        if self.search_error.is_some() || !self.search_history.push(&self.search_query) {
+            return;
+        }
+        if let Err(e) = self.search_history.save() {
+            self.message = Some(e.to_string());
+        }
+    }
 
-        self.search_results_unsorted = self.search_results.clone();
-        self.search_sort_mode = SearchSortMode::Default;
+    fn do_search(&mut self) {
+        // キャッシュから検索（高速・同期）。ListItem への変換は並べ替えの後に一度だけ
+        // 並びは default_track_order（あいまい検索ではスコア降順が先）
+
+        // 構文エラーは検索ボックスに表示し、結果は直前のまま残す
+        let query = match Query::parse(&self.search_query) {
+            Ok(query) => query,
+            Err(e) => {
+                self.search_error = Some(e.to_string());
+                return;
+            }
+        };
+        self.search_error = None;
+
+        self.search_highlights.clear();
+        self.search_results = if self.fuzzy_search {
+            let mut hits = self.cache.search_fuzzy(&query);
+            let tracks = &self.cache.tracks;
+            hits.sort_by(|a, b| {
+                b.score.cmp(&a.score).then_with(|| default_track_order(&tracks[a.index], &tracks[b.index]))
+            });
+            hits.into_iter()
+                .map(|hit| {
+                    if hit.highlights.iter().any(|h| !h.is_empty()) {
+                        self.search_highlights.insert(hit.index, hit.highlights);
+                    }
+                    hit.index
+                })
+                .collect()
+        } else {
+            let mut results = self.cache.search(&query);
+            let tracks = &self.cache.tracks;
+            results.sort_by(|&a, &b| default_track_order(&tracks[a], &tracks[b]));
+            results
+        };
+
+        // 選んだ並べ替えがあれば適用する
+        self.search_natural_order = None;
+        let keys = self.view_sorts.search.clone();
+        self.sort_search_results(&keys);
+        self.rebuild_search_rows();
         self.content_selected = 0;
         self.content_scroll = 0;
     }
 
+    /// あいまい検索の切り替え（検索ボックスで Tab）。設定に保存する
+    pub fn toggle_fuzzy_search(&mut self) {
+        self.fuzzy_search = !self.fuzzy_search;
+        self.do_search();
+        self.settings.fuzzy_search = self.fuzzy_search;
+        self.save_settings();
+    }
+
     pub fn confirm_search(&mut self) {
+        self.record_search();
         if !self.search_results.is_empty() {
             // 検索結果（Detailカード）にフォーカス移動
             self.focus = Focus::Content;
@@ -1376,26 +1934,361 @@ impl App {
         }
     }
 
-    /// 検索結果のソートモードを切り替え (s key)
-    pub fn toggle_search_sort(&mut self) {
-        if self.search_results.is_empty() {
+    // ========== 検索の保存 ==========
+
+    /// 現在のクエリに名前を付けて保存する（検索ボックスで Ctrl+S、検索結果で S）
+    /// 名前の初期値は同じクエリの保存済みの名前、なければクエリそのもの
+    pub fn start_save_search(&mut self) {
+        let query = self.search_query.trim();
+        if !self.search_mode || query.is_empty() {
+            return;
+        }
+        if self.search_error.is_some() {
+            self.message = Some("Fix the query before saving it".to_string());
+            return;
+        }
+        self.save_search_name = self.saved_searches
+            .iter()
+            .find(|s| s.query == query)
+            .map(|s| s.name.clone())
+            .unwrap_or_else(|| query.to_string());
+        self.save_search_input_mode = true;
+    }
+
+    pub fn save_search_input(&mut self, c: char) {
+        self.save_search_name.push(c);
+    }
+
+    pub fn save_search_backspace(&mut self) {
+        self.save_search_name.pop();
+    }
+
+    pub fn cancel_save_search(&mut self) {
+        self.save_search_input_mode = false;
+        self.save_search_name.clear();
+    }
+
+    /// 同じ名前の保存済みの検索は置き換える
+    pub fn confirm_save_search(&mut self) {
+        let name = self.save_search_name.trim().to_string();
+        if name.is_empty() {
             return;
         }
+        let query = self.search_query.trim().to_string();
+        self.record_search();
 
-        match self.search_sort_mode {
-            SearchSortMode::Default => {
-                // 再生回数降順でソート
-                self.search_results.sort_by(|a, b| b.played_count.cmp(&a.played_count));
-                self.search_sort_mode = SearchSortMode::PlayCount;
+        // 設定を保存（新しいバージョンの設定ファイルは書き換えない）
+        let settings = &mut self.settings;
+        let replaced = match settings.saved_searches.iter_mut().find(|s| s.name == name) {
+            Some(existing) => {
+                existing.query = query;
+                true
             }
-            SearchSortMode::PlayCount => {
-                // デフォルト順に戻す
-                self.search_results = self.search_results_unsorted.clone();
-                self.search_sort_mode = SearchSortMode::Default;
+            None => {
+                settings.saved_searches.push(SavedSearch { name: name.clone(), query });
+                false
+            }
+        };
+        self.message = Some(match settings.save() {
+            Ok(()) if replaced => format!("Updated saved search '{}'", name),
+            Ok(()) => format!("Saved search '{}'", name),
+            Err(e) => e.to_string(),
+        });
+        self.saved_searches = settings.saved_searches.clone();
+        self.cancel_save_search();
+        self.rebuild_playlist_rows();
+    }
+
+    /// d: 選択中の保存した検索を削除するか確認する（Playlists ペイン）
+    pub fn start_delete_saved_search(&mut self) {
+        let Some(name) = self.playlists
+            .get(self.playlists_selected)
+            .and_then(|row| row.saved_search_name())
+            .map(str::to_string)
+        else {
+            return;
+        };
+        self.message = Some(format!("Delete saved search '{}'? (y/n)", name));
+        self.delete_saved_search_pending = Some(name);
+    }
+
+    pub fn cancel_delete_saved_search(&mut self) {
+        self.delete_saved_search_pending = None;
+        self.message = None;
+    }
+
+    /// y: 確認中の保存した検索を削除する
+    pub fn confirm_delete_saved_search(&mut self) {
+        let Some(name) = self.delete_saved_search_pending.take() else {
+            return;
+        };
+
+        // 設定を保存（新しいバージョンの設定ファイルは書き換えない）
+        let settings = &mut self.settings;
+        settings.saved_searches.retain(|s| s.name != name);
+        self.message = Some(match settings.save() {
+            Ok(()) => format!("Deleted saved search '{}'", name),
+            Err(e) => e.to_string(),
+        });
+        self.saved_searches = settings.saved_searches.clone();
+
+        if self.content_saved_search.as_deref() == Some(name.as_str()) {
+            self.content_items.clear();
+            self.content_natural_order = None;
+            self.content_title.clear();
+            self.content_source_name.clear();
+            self.is_playlist_detail = false;
+            self.content_saved_search = None;
+        }
+        self.rebuild_playlist_rows();
+        self.load_selected_playlist_tracks();
+    }
+
+    /// 表示中の一覧の種類（並べ替えはこの単位で覚える）
+    pub fn sort_view(&self) -> SortView {
+        if self.search_mode || self.content_saved_search.is_some() {
+            SortView::Search
+        } else if self.is_playlist_detail {
+            SortView::Playlist
+        } else {
+            SortView::Album
+        }
+    }
+
+    /// content_items を入れ替えた後に呼ぶ（元の順を覚え直して並べ替える）
+    fn content_items_changed(&mut self) {
+        self.content_natural_order = None;
+        let keys = self.view_sorts.get(self.sort_view()).to_vec();
+        self.sort_content_items(&keys);
+    }
+
+    /// content_items を keys で並べ替える
+    fn sort_content_items(&mut self, keys: &[SortKey]) {
+        let dates = date_added_map(&mut self.cache, &self.content_items, keys);
+        apply_sort(&mut self.content_items, &mut self.content_natural_order, keys, |a, b| {
+            sort::compare_by_keys(&SortFields::of_item(a, &dates), &SortFields::of_item(b, &dates), keys)
+        });
+    }
+
+    /// 選択中の曲の元の順での位置（巡回再生は元の順の位置で指定する）
+    fn natural_content_index(&self) -> usize {
+        self.content_natural_order
+            .as_ref()
+            .and_then(|order| order.get(self.content_selected).copied())
+            .unwrap_or(self.content_selected)
+    }
+
+    /// o: 表示中の一覧の並べ替えメニューを開く（カーソルは1番目のキー）
+    pub fn open_sort_menu(&mut self) {
+        let view = self.sort_view();
+        let selected = self.view_sorts.get(view)
+            .first()
+            .and_then(|key| SortField::ALL.iter().position(|&f| f == key.field))
+            .unwrap_or(0);
+        self.sort_menu = Some(SortMenu { view, selected });
+    }
+
+    pub fn close_sort_menu(&mut self) {
+        self.sort_menu = None;
+    }
+
+    pub fn sort_menu_up(&mut self) {
+        if let Some(menu) = &mut self.sort_menu {
+            menu.selected = menu.selected.saturating_sub(1);
+        }
+    }
+
+    pub fn sort_menu_down(&mut self) {
+        if let Some(menu) = &mut self.sort_menu {
+            menu.selected = (menu.selected + 1).min(SortField::ALL.len() - 1);
+        }
+    }
+
+    /// Enter / 1: 1番目のキーにする（もう一度で向きを反転）
+    pub fn sort_menu_primary(&mut self) {
+        self.change_sort(sort::set_primary);
+    }
+
+    /// 2: 2番目のキーにする（もう一度で向きを反転）
+    pub fn sort_menu_secondary(&mut self) {
+        self.change_sort(sort::set_secondary);
+    }
+
+    /// x: 選択中の項目をキーから外す
+    pub fn sort_menu_remove(&mut self) {
+        self.change_sort(|keys, field| keys.retain(|k| k.field != field));
+    }
+
+    /// r: 元の順に戻す
+    pub fn sort_menu_reset(&mut self) {
+        self.change_sort(|keys, _| keys.clear());
+    }
+
+    fn change_sort(&mut self, change: impl FnOnce(&mut Vec<SortKey>, SortField)) {
+        let Some(menu) = self.sort_menu else { return };
+        change(self.view_sorts.get_mut(menu.view), SortField::ALL[menu.selected]);
+        self.resort_view(menu.view);
+
+        self.settings.sort = self.view_sorts.clone();
+        self.save_settings();
+    }
+
+    /// 表示中の一覧を並べ替え直す（選択中の曲は選択したまま）
+    fn resort_view(&mut self, view: SortView) {
+        if view != self.sort_view() {
+            return;
+        }
+        let keys = self.view_sorts.get(view).to_vec();
+        if self.search_mode {
+            let selected = self.selected_search_result();
+            self.sort_search_results(&keys);
+            self.rebuild_search_rows();
+            if let Some(selected) = selected {
+                self.select_search_result(selected);
             }
+        } else {
+            // 同じ曲が並ぶプレイリストもあるので、元の位置で選択を追う
+            let selected = self.natural_content_index();
+            self.sort_content_items(&keys);
+            self.content_selected = match &self.content_natural_order {
+                Some(order) => order.iter().position(|&i| i == selected).unwrap_or(0),
+                None => selected,
+            };
+        }
+        self.adjust_scroll(self.content_len());
+    }
+
+    // ========== 検索結果のグループ ==========
+
+    /// 検索結果を keys で並べ替える（曲の値はキャッシュから直接比べる）
+    fn sort_search_results(&mut self, keys: &[SortKey]) {
+        let tracks = &self.cache.tracks;
+        apply_sort(&mut self.search_results, &mut self.search_natural_order, keys, |&a, &b| {
+            sort::compare_by_keys(&SortFields::of_track(&tracks[a]), &SortFields::of_track(&tracks[b]), keys)
+        });
+    }
+
+    /// 検索結果の i 番目の曲
+    pub fn search_track(&self, i: usize) -> Option<&CachedTrack> {
+        self.search_results.get(i).and_then(|&t| self.cache.tracks.get(t))
+    }
+
+    /// 検索結果からグループと表示行を作り直す
+    fn rebuild_search_rows(&mut self) {
+        self.search_groups = search_groups::build_groups(&self.cache.tracks, &self.search_results, self.search_grouping);
+        self.search_rows = search_groups::visible_rows(self.search_results.len(), &self.search_groups, &self.search_collapsed);
+    }
+
+    /// 選択中の曲の cache.tracks の添字（見出しなら None）
+    fn selected_search_result(&self) -> Option<usize> {
+        match self.search_rows.get(self.content_selected)? {
+            SearchRow::Track(i) => self.search_results.get(*i).copied(),
+            SearchRow::Header(_) => None,
         }
+    }
+
+    /// 選択中の曲（見出しなら None）
+    pub fn selected_search_track(&self) -> Option<&CachedTrack> {
+        self.selected_search_result().and_then(|t| self.cache.tracks.get(t))
+    }
+
+    /// 選択中の行のグループ（曲ならその曲を含むグループ）
+    fn selected_search_group(&self) -> Option<usize> {
+        match *self.search_rows.get(self.content_selected)? {
+            SearchRow::Header(g) => Some(g),
+            SearchRow::Track(i) => self.search_groups.iter().position(|group| group.tracks.contains(&i)),
+        }
+    }
+
+    /// cache.tracks の添字が track の曲の行を選択する（折りたたまれていればそのグループの見出し）
+    fn select_search_result(&mut self, track: usize) {
+        let Some(index) = self.search_results.iter().position(|&t| t == track) else { return };
+        let row = self.search_rows.iter().position(|&r| r == SearchRow::Track(index)).or_else(|| {
+            let g = self.search_groups.iter().position(|group| group.tracks.contains(&index))?;
+            self.search_rows.iter().position(|&r| r == SearchRow::Header(g))
+        });
+        if let Some(row) = row {
+            self.content_selected = row;
+        }
+    }
+
+    /// v: 検索結果のまとめ方を切り替える（なし → アルバム → アーティスト）。設定に保存する
+    pub fn cycle_search_grouping(&mut self) {
+        let selected = self.selected_search_result();
+        self.search_grouping = self.search_grouping.next();
+        self.search_collapsed.clear();
+        self.rebuild_search_rows();
         self.content_selected = 0;
-        self.content_scroll = 0;
+        if let Some(selected) = selected {
+            self.select_search_result(selected);
+        }
+        self.adjust_scroll(self.content_len());
+
+        self.settings.search_grouping = self.search_grouping;
+        self.save_settings();
+    }
+
+    pub fn is_search_group_collapsed(&self, g: usize) -> bool {
+        self.search_groups.get(g).is_some_and(|group| self.search_collapsed.contains(&group.key()))
+    }
+
+    /// z: 選択中のグループを折りたたむ・開く（選択は見出しに移す）
+    pub fn toggle_search_group(&mut self) {
+        let Some(g) = self.selected_search_group() else { return };
+        let key = self.search_groups[g].key();
+        if !self.search_collapsed.remove(&key) {
+            self.search_collapsed.insert(key);
+        }
+        self.search_rows = search_groups::visible_rows(self.search_results.len(), &self.search_groups, &self.search_collapsed);
+        if let Some(row) = self.search_rows.iter().position(|&r| r == SearchRow::Header(g)) {
+            self.content_selected = row;
+        }
+        self.adjust_scroll(self.content_len());
+    }
+
+    /// Z: すべて折りたたむ（すべて折りたたみ済みならすべて開く）
+    pub fn toggle_all_search_groups(&mut self) {
+        let Some(g) = self.selected_search_group() else { return };
+        if self.search_groups.iter().all(|group| self.search_collapsed.contains(&group.key())) {
+            self.search_collapsed.clear();
+        } else {
+            self.search_collapsed = self.search_groups.iter().map(SearchGroup::key).collect();
+        }
+        self.search_rows = search_groups::visible_rows(self.search_results.len(), &self.search_groups, &self.search_collapsed);
+        if let Some(row) = self.search_rows.iter().position(|&r| r == SearchRow::Header(g)) {
+            self.content_selected = row;
+        }
+        self.adjust_scroll(self.content_len());
+    }
+
+    /// グループの曲を結果の順に先頭から巡回再生
+    fn play_search_group(&mut self, g: usize) {
+        let Some(group) = self.search_groups.get(g) else { return };
+        let tracks: Vec<TrackRef> = group.tracks.iter().filter_map(|&i| self.search_track(i)).map(CachedTrack::track_ref).collect();
+        let label = match self.search_grouping {
+            SearchGrouping::Album => format!("{} - {}", group.name, group.artist),
+            _ => group.name.clone(),
+        };
+        // 同期的に実行（競合を避けるため）
+        match self.backend.play_tracks_with_context(&tracks, 0) {
+            Ok(_) => self.message = Some(format!("▶ {} ({} tracks)", label, tracks.len())),
+            Err(e) => self.report_error(&e),
+        }
+    }
+
+    /// l: 選択中の曲（見出しならグループの最初の曲）のアルバム全曲を表示
+    pub fn show_selected_search_album(&mut self) {
+        let album = match self.search_rows.get(self.content_selected) {
+            Some(&SearchRow::Track(i)) => self.search_track(i),
+            Some(&SearchRow::Header(g)) => self.search_groups[g].tracks.first().and_then(|&i| self.search_track(i)),
+            None => None,
+        }
+        .map(|item| (item.album.clone(), item.album_artist_or_artist().to_string()));
+        if let Some((album, album_artist)) = album {
+            // 先に検索を抜ける（アルバムの並べ替えを使うため）
+            self.search_mode = false;
+            self.show_album_tracks(&album, &album_artist);
+        }
     }
 
     /// 検索結果で次のアルバムにジャンプ (Shift+J)
@@ -1403,15 +2296,25 @@ impl App {
         if self.search_results.is_empty() {
             return;
         }
+        // まとめて表示中は次の見出しへ
+        if self.search_grouping != SearchGrouping::Off {
+            let next = (self.content_selected + 1..self.search_rows.len())
+                .find(|&r| matches!(self.search_rows[r], SearchRow::Header(_)));
+            if let Some(row) = next {
+                self.content_selected = row;
+                self.adjust_scroll(self.content_len());
+            }
+            return;
+        }
 
-        let current_album = match self.search_results.get(self.content_selected) {
+        let current_album = match self.search_track(self.content_selected) {
             Some(item) => &item.album,
             None => return,
         };
 
         // 現在位置から次の異なるアルバムを探す
         for i in (self.content_selected + 1)..self.search_results.len() {
-            if &self.search_results[i].album != current_album {
+            if self.search_track(i).map(|t| &t.album) != Some(current_album) {
                 self.content_selected = i;
                 // スクロール調整
                 if self.content_selected >= self.content_scroll + self.content_visible {
@@ -1427,20 +2330,31 @@ impl App {
         if self.search_results.is_empty() || self.content_selected == 0 {
             return;
         }
+        // まとめて表示中は前の見出しへ
+        if self.search_grouping != SearchGrouping::Off {
+            let prev = (0..self.content_selected)
+                .rev()
+                .find(|&r| matches!(self.search_rows[r], SearchRow::Header(_)));
+            if let Some(row) = prev {
+                self.content_selected = row;
+                self.adjust_scroll(self.content_len());
+            }
+            return;
+        }
 
-        let current_album = match self.search_results.get(self.content_selected) {
+        let current_album = match self.search_track(self.content_selected) {
             Some(item) => &item.album,
             None => return,
         };
 
         // 現在位置から前の異なるアルバムを探す
         for i in (0..self.content_selected).rev() {
-            if &self.search_results[i].album != current_album {
+            if self.search_track(i).map(|t| &t.album) != Some(current_album) {
                 // そのアルバムの最初のトラックを探す
-                let target_album = &self.search_results[i].album;
+                let target_album = self.search_track(i).map(|t| &t.album);
                 let mut first_of_album = i;
                 for j in (0..i).rev() {
-                    if &self.search_results[j].album == target_album {
+                    if self.search_track(j).map(|t| &t.album) == target_album {
                         first_of_album = j;
                     } else {
                         break;
@@ -1470,10 +2384,15 @@ impl App {
             return;
         }
         
-        let items = if self.search_mode { &self.search_results } else { &self.content_items };
-        if let Some(item) = items.get(self.content_selected) {
-            self.track_to_add = Some(item.clone());
+        let item = if self.search_mode {
+            self.selected_search_track().map(track_list_item)
+        } else {
+            self.content_items.get(self.content_selected).cloned()
+        };
+        if let Some(item) = item {
+            self.track_to_add = Some(item);
             self.add_to_playlist_mode = true;
+            self.rebuild_playlist_rows();
             self.focus = Focus::Playlists;
             self.playlists_selected = 0;
             self.playlists_scroll = 0;
@@ -1487,6 +2406,7 @@ impl App {
         self.new_playlist_input_mode = false;
         self.new_playlist_name.clear();
         self.focus = Focus::Content;
+        self.rebuild_playlist_rows();
     }
 
     /// 選択したプレイリストに曲を追加
@@ -1507,25 +2427,31 @@ impl App {
             return;
         };
 
+        // 一覧の更新が間に合わず読み取り専用のプレイリストが残っていた場合
+        if !playlist.kind.is_editable() {
+            self.message = Some(format!("Cannot add tracks to '{}'", playlist.name));
+            return;
+        }
+
         let playlist_name = playlist.name.clone();
-        let track_name = track.name.clone();
-        let track_album = track.album.clone();
+        let playlist_id = playlist.persistent_id.clone();
 
         // AppleScriptでプレイリストに曲を追加
-        match Self::add_track_to_playlist(&track_name, &track_album, &playlist_name) {
+        match self.backend.add_track_to_playlist(track.track_ref(), playlist.playlist_ref()) {
             Ok(_) => {
                 self.message = Some(format!("Added to '{}'", playlist_name));
                 // プレイリストキャッシュを更新
-                self.refresh_playlist_cache(&playlist_name);
+                self.refresh_playlist_cache(PlaylistRef { persistent_id: &playlist_id, name: &playlist_name });
             }
             Err(e) => {
-                self.message = Some(format!("Error: {}", e));
+                self.report_error(&e);
             }
         }
 
         self.add_to_playlist_mode = false;
         self.track_to_add = None;
         self.focus = Focus::Content;
+        self.rebuild_playlist_rows();
     }
 
     /// 新規プレイリスト名の入力
@@ -1550,29 +2476,19 @@ impl App {
         };
 
         let playlist_name = self.new_playlist_name.clone();
-        let track_name = track.name.clone();
-        let track_album = track.album.clone();
 
         // AppleScriptで新規プレイリストを作成して曲を追加
-        match Self::create_playlist_and_add_track(&playlist_name, &track_name, &track_album) {
-            Ok(_) => {
+        match self.backend.create_playlist_and_add_track(&playlist_name, track.track_ref()) {
+            Ok(playlist_id) => {
                 self.message = Some(format!("Created '{}' and added track", playlist_name));
-                // プレイリスト一覧に追加
-                self.playlists.push(ListItem {
-                    name: playlist_name.clone(),
-                    artist: String::new(),
-                    album: String::new(),
-                    time: String::new(),
-                    year: 0,
-                    track_number: 0,
-                    played_count: 0,
-                    favorited: false,
-                });
+                // プレイリスト一覧に追加（新規プレイリストは最上位にできる）
+                self.playlist_tree.push(PlaylistInfo::top_level(playlist_name.clone(), playlist_id.clone()));
+                self.rebuild_playlist_rows();
                 // プレイリストキャッシュを更新
-                self.refresh_playlist_cache(&playlist_name);
+                self.refresh_playlist_cache(PlaylistRef { persistent_id: &playlist_id, name: &playlist_name });
             }
             Err(e) => {
-                self.message = Some(format!("Error: {}", e));
+                self.report_error(&e);
             }
         }
 
@@ -1581,59 +2497,88 @@ impl App {
         self.new_playlist_input_mode = false;
         self.new_playlist_name.clear();
         self.focus = Focus::Content;
+        self.rebuild_playlist_rows();
+    }
+
+    /// 表示する行を作り直す
+    /// 行が増減しても同じプレイリスト（追加モードでは "+ New playlist"）を選択したままにする
+    fn rebuild_playlist_rows(&mut self) {
+        let selected_id = self.playlists.get(self.playlists_selected).map(|p| p.persistent_id.clone());
+        let was_new_entry = self.add_to_playlist_mode && self.playlists_selected >= self.playlists.len();
+        // 追加先を選ぶときは曲を追加できるプレイリスト（とフォルダ）だけ
+        // 保存した検索は追加先にならないので追加モードでは出さない
+        let add_mode = self.add_to_playlist_mode;
+        let mut rows = if add_mode {
+            Vec::new()
+        } else {
+            playlist_tree::saved_search_rows(&self.saved_searches, &self.collapsed_folders)
+        };
+        rows.extend(playlist_tree::visible_rows(&self.playlist_tree, &self.collapsed_folders, |p| {
+            !add_mode || p.kind.is_editable()
+        }));
+        self.playlists = rows;
+        let len = self.playlists.len();
+        self.playlists_selected = if was_new_entry {
+            len
+        } else {
+            selected_id
+                .and_then(|id| self.playlists.iter().position(|p| p.persistent_id == id))
+                .unwrap_or(self.playlists_selected.min(len.saturating_sub(1)))
+        };
+        self.adjust_playlists_scroll();
     }
 
-    /// AppleScript: プレイリストに曲を追加
-    fn add_track_to_playlist(track_name: &str, track_album: &str, playlist_name: &str) -> Result<(), String> {
-        let script = format!(
-            r#"tell application "Music"
-                set targetTrack to (first track of library playlist 1 whose name is "{}" and album is "{}")
-                set targetPlaylist to (first playlist whose name is "{}")
-                duplicate targetTrack to targetPlaylist
-            end tell"#,
-            track_name.replace('"', "\\\""),
-            track_album.replace('"', "\\\""),
-            playlist_name.replace('"', "\\\"")
-        );
-
-        let output = std::process::Command::new("osascript")
-            .arg("-e")
-            .arg(&script)
-            .output()
-            .map_err(|e| format!("Failed to run osascript: {}", e))?;
-
-        if output.status.success() {
-            Ok(())
-        } else {
-            let err = String::from_utf8_lossy(&output.stderr);
-            Err(err.trim().to_string())
-        }
-    }
-
-    /// AppleScript: 新規プレイリストを作成して曲を追加
-    fn create_playlist_and_add_track(playlist_name: &str, track_name: &str, track_album: &str) -> Result<(), String> {
-        let script = format!(
-            r#"tell application "Music"
-                set newPlaylist to make new playlist with properties {{name:"{}"}}
-                set targetTrack to (first track of library playlist 1 whose name is "{}" and album is "{}")
-                duplicate targetTrack to newPlaylist
-            end tell"#,
-            playlist_name.replace('"', "\\\""),
-            track_name.replace('"', "\\\""),
-            track_album.replace('"', "\\\"")
-        );
-
-        let output = std::process::Command::new("osascript")
-            .arg("-e")
-            .arg(&script)
-            .output()
-            .map_err(|e| format!("Failed to run osascript: {}", e))?;
-
-        if output.status.success() {
-            Ok(())
+    /// フォルダの開閉を切り替えて設定に保存
+    fn set_folder_collapsed(&mut self, folder_id: String, collapsed: bool) {
+        if collapsed {
+            self.collapsed_folders.insert(folder_id);
         } else {
-            let err = String::from_utf8_lossy(&output.stderr);
-            Err(err.trim().to_string())
+            self.collapsed_folders.remove(&folder_id);
+        }
+        self.rebuild_playlist_rows();
+        self.settings.collapsed_playlist_folders = self.collapsed_folders.clone();
+        self.save_settings();
+    }
+
+    /// Enter: 選択中のフォルダを開閉する（フォルダでなければ false）
+    pub fn toggle_selected_folder(&mut self) -> bool {
+        match self.playlists.get(self.playlists_selected) {
+            Some(row) if row.is_folder() => {
+                let (id, expanded) = (row.persistent_id.clone(), row.expanded);
+                self.set_folder_collapsed(id, expanded);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// l: 閉じたフォルダを開く（開くものがなければ false）
+    pub fn expand_selected_folder(&mut self) -> bool {
+        match self.playlists.get(self.playlists_selected) {
+            Some(row) if row.is_folder() && !row.expanded => {
+                let id = row.persistent_id.clone();
+                self.set_folder_collapsed(id, false);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// h: 開いたフォルダを閉じる。フォルダの中の項目なら親フォルダへ移動
+    pub fn collapse_selected_folder(&mut self) {
+        let Some(row) = self.playlists.get(self.playlists_selected) else { return };
+        if row.is_folder() && row.expanded {
+            let id = row.persistent_id.clone();
+            self.set_folder_collapsed(id, true);
+        } else if row.depth > 0 {
+            let parent_id = row.parent_id.clone();
+            if let Some(parent) = self.playlists.iter().position(|p| p.persistent_id == parent_id) {
+                self.playlists_selected = parent;
+                self.adjust_playlists_scroll();
+                if !self.add_to_playlist_mode {
+                    self.load_selected_playlist_tracks();
+                }
+            }
         }
     }
 
@@ -1644,16 +2589,19 @@ impl App {
 
 
     /// 指定したプレイリストのキャッシュを非同期で更新
-    fn refresh_playlist_cache(&mut self, playlist_name: &str) {
-        let name = playlist_name.to_string();
+    fn refresh_playlist_cache(&mut self, playlist: PlaylistRef) {
+        let name = playlist.name.to_string();
+        let persistent_id = playlist.persistent_id.to_string();
         self.playlist_refreshing = Some(name.clone());
 
         let (tx, rx) = std::sync::mpsc::channel();
         self.playlist_refresh_rx = Some(rx);
 
+        let backend = Arc::clone(&self.backend);
         std::thread::spawn(move || {
-            if let Ok(tracks) = MusicController::get_playlist_tracks(&name) {
-                let _ = tx.send((name, tracks));
+            let playlist = PlaylistRef { persistent_id: &persistent_id, name: &name };
+            if let Ok(tracks) = backend.get_playlist_tracks(playlist) {
+                let _ = tx.send((name, persistent_id, tracks));
             }
         });
     }
@@ -1662,29 +2610,18 @@ impl App {
     /// プレイリスト更新の完了をポーリング
     pub fn poll_playlist_refresh(&mut self) {
         if let Some(rx) = &self.playlist_refresh_rx {
-            if let Ok((playlist_name, tracks)) = rx.try_recv() {
+            if let Ok((playlist_name, playlist_id, tracks)) = rx.try_recv() {
                 // キャッシュを更新
-                let cached_tracks: Vec<CachedPlaylistTrack> = tracks.iter().map(|t| {
-                    CachedPlaylistTrack {
-                        name: t.name.clone(),
-                        artist: t.artist.clone(),
-                        album: t.album.clone(),
-                        year: t.year,
-                        time: t.time.clone(),
-                        played_count: t.played_count,
-                        favorited: t.favorited,
-                    }
-                }).collect();
-                let cached_playlist = CachedPlaylist {
-                    name: playlist_name.clone(),
-                    tracks: cached_tracks,
-                };
-                self.playlist_cache.insert(cached_playlist);
+                // 変更検出用の値は前回のものを残す（食い違えば次の再検証で取り直される）
+                let playlist = PlaylistRef { persistent_id: &playlist_id, name: &playlist_name };
+                let fingerprint = self.playlist_cache.fingerprint(playlist);
+                self.playlist_cache.insert(to_cached_playlist(playlist, &fingerprint, &tracks));
                 let _ = self.playlist_cache.save();
 
                 // 現在表示中のプレイリストなら content_items も更新
-                if self.is_playlist_detail && self.content_source_name == playlist_name {
+                if self.is_playlist_detail && self.content_source_id == playlist_id && self.content_source_name == playlist_name {
                     self.content_items = tracks;
+                    self.content_items_changed();
                 }
 
                 self.playlist_refreshing = None;
@@ -1693,3 +2630,168 @@ impl App {
         }
     }
 }
+
+/// keys で並べ替える（安定ソート）。order に各行の元の位置を残し、keys が空なら元の順に戻す
+fn apply_sort<T>(
+    items: &mut Vec<T>,
+    order: &mut Option<Vec<usize>>,
+    keys: &[SortKey],
+    compare: impl Fn(&T, &T) -> std::cmp::Ordering,
+) {
+    if let Some(previous) = order.take() {
+        let mut rows: Vec<(usize, T)> = previous.into_iter().zip(items.drain(..)).collect();
+        rows.sort_by_key(|(i, _)| *i);
+        items.extend(rows.into_iter().map(|(_, item)| item));
+    }
+    if keys.is_empty() {
+        return;
+    }
+    let mut rows: Vec<(usize, T)> = items.drain(..).enumerate().collect();
+    rows.sort_by(|(_, a), (_, b)| compare(a, b));
+    let (natural, sorted): (Vec<usize>, Vec<T>) = rows.into_iter().unzip();
+    *items = sorted;
+    *order = Some(natural);
+}
+
+/// 追加日で並べるときだけ、一覧の曲の追加日をキャッシュから引いておく（persistent ID → 追加日）
+fn date_added_map(cache: &mut TrackCache, items: &[ListItem], keys: &[SortKey]) -> HashMap<String, u64> {
+    if !sort::needs_dates(keys) {
+        return HashMap::new();
+    }
+    items
+        .iter()
+        .filter_map(|item| Some((item.persistent_id.clone(), cache.date_added(&item.persistent_id)?)))
+        .collect()
+}
+
+/// 検索結果の並び: Artist昇順, Year昇順, Album昇順, Disc昇順, Track昇順
+fn default_track_order(a: &CachedTrack, b: &CachedTrack) -> std::cmp::Ordering {
+    a.artist.cmp(&b.artist)
+        .then_with(|| a.year.cmp(&b.year))
+        .then_with(|| a.album.cmp(&b.album))
+        .then_with(|| a.disc_number.cmp(&b.disc_number))
+        .then_with(|| a.track_number.cmp(&b.track_number))
+}
+
+fn track_list_item(t: &CachedTrack) -> ListItem {
+    ListItem {
+        name: t.name.clone(),
+        artist: t.artist.clone(),
+        album: t.album.clone(),
+        time: t.time.clone(),
+        year: t.year,
+        track_number: t.track_number,
+        played_count: t.played_count,
+        favorited: t.favorited,
+        persistent_id: t.persistent_id.clone(),
+    }
+}
+
+fn to_cached_playlist(playlist: PlaylistRef, fingerprint: &str, tracks: &[ListItem]) -> CachedPlaylist {
+    CachedPlaylist {
+        name: playlist.name.to_string(),
+        tracks: tracks
+            .iter()
+            .map(|t| CachedPlaylistTrack {
+                name: t.name.clone(),
+                artist: t.artist.clone(),
+                album: t.album.clone(),
+                year: t.year,
+                time: t.time.clone(),
+                played_count: t.played_count,
+                favorited: t.favorited,
+                persistent_id: t.persistent_id.clone(),
+            })
+            .collect(),
+        persistent_id: playlist.persistent_id.to_string(),
+        fingerprint: fingerprint.to_string(),
+    }
+}
+
+fn cached_playlist_items(playlist: &CachedPlaylist) -> Vec<ListItem> {
+    playlist
+        .tracks
+        .iter()
+        .map(|t| ListItem {
+            name: t.name.clone(),
+            artist: t.artist.clone(),
+            album: t.album.clone(),
+            year: t.year,
+            time: t.time.clone(),
+            played_count: t.played_count,
+            favorited: t.favorited,
+            track_number: 0,
+            persistent_id: t.persistent_id.clone(),
+        })
+        .collect()
+}
+
+/// 削除・名前変更されたプレイリスト（と ID のない古いエントリ）をキャッシュから外す
+/// current は全プレイリストの一覧であること
+fn drop_removed_playlists(
+    tx: &Sender<PlaylistLoadResponse>,
+    known: &mut HashMap<String, PlaylistFingerprint>,
+    current: &[PlaylistFingerprint],
+) {
+    let current_by_id: HashMap<&str, &PlaylistFingerprint> =
+        current.iter().map(|p| (p.persistent_id.as_str(), p)).collect();
+    let removed: Vec<String> = known
+        .iter()
+        .filter(|(_, cached)| current_by_id.get(cached.persistent_id.as_str()).is_none_or(|live| live.name != cached.name))
+        .map(|(key, _)| key.clone())
+        .collect();
+    if !removed.is_empty() {
+        for key in &removed {
+            known.remove(key);
+        }
+        let _ = tx.send(PlaylistLoadResponse::Removed(removed));
+    }
+}
+
+/// バックエンドの変更検出用の値とキャッシュを比べ、未取得・変更されたプレイリストだけ取り直す
+fn refetch_changed_playlists(
+    backend: &dyn PlayerBackend,
+    tx: &Sender<PlaylistLoadResponse>,
+    known: &mut HashMap<String, PlaylistFingerprint>,
+    current: &[PlaylistFingerprint],
+    show_progress: bool,
+) {
+    let stale: Vec<&PlaylistFingerprint> = current
+        .iter()
+        .filter(|live| known.get(&live.persistent_id).is_none_or(|cached| cached.fingerprint != live.fingerprint))
+        .collect();
+    let total = stale.len();
+    for (i, live) in stale.into_iter().enumerate() {
+        if show_progress {
+            let _ = tx.send(PlaylistLoadResponse::Progress {
+                current: i + 1,
+                total,
+                name: live.name.clone(),
+            });
+        }
+        let playlist = PlaylistRef { persistent_id: &live.persistent_id, name: &live.name };
+        if let Ok(tracks) = retry_on_timeout(|| backend.get_playlist_tracks(playlist)) {
+            known.insert(live.persistent_id.clone(), live.clone());
+            if tx.send(PlaylistLoadResponse::PlaylistLoaded(to_cached_playlist(playlist, &live.fingerprint, &tracks))).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn to_cached_track(t: SimpleTrack) -> CachedTrack {
+    CachedTrack::new(
+        t.name, t.artist, t.album, t.album_artist, t.date_added,
+        t.year, t.track_number, t.disc_number,
+        t.time, t.played_count, t.favorited,
+        t.rating, t.played_date, t.persistent_id,
+    )
+}
+
+/// タイムアウトした読み取りを1回だけ再試行する
+fn retry_on_timeout<T>(f: impl Fn() -> anyhow::Result<T>) -> anyhow::Result<T> {
+    match f() {
+        Err(e) if matches!(e.downcast_ref::<MusicError>(), Some(MusicError::Timeout)) => f(),
+        result => result,
+    }
+}
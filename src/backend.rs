@@ -0,0 +1,115 @@
+use anyhow::Result;
+
+use crate::cache::CachedTrack;
+use crate::music::{ListItem, PlayerState, PlaylistFingerprint, PlaylistInfo, TrackBatch, TrackStats};
+
+/// 曲の参照
+/// persistent_id があればそれで特定し、空（古いキャッシュ）の場合のみ名前で照合する
+#[derive(Debug, Clone, Copy)]
+pub struct TrackRef<'a> {
+    pub persistent_id: &'a str,
+    pub name: &'a str,
+    pub artist: &'a str,
+    pub album: &'a str,
+}
+
+/// プレイリストの参照（TrackRef と同様に persistent_id を優先する）
+#[derive(Debug, Clone, Copy)]
+pub struct PlaylistRef<'a> {
+    pub persistent_id: &'a str,
+    pub name: &'a str,
+}
+
+impl ListItem {
+    /// 曲として参照する
+    pub fn track_ref(&self) -> TrackRef<'_> {
+        TrackRef {
+            persistent_id: &self.persistent_id,
+            name: &self.name,
+            artist: &self.artist,
+            album: &self.album,
+        }
+    }
+}
+
+impl CachedTrack {
+    /// 曲として参照する
+    pub fn track_ref(&self) -> TrackRef<'_> {
+        TrackRef {
+            persistent_id: &self.persistent_id,
+            name: &self.name,
+            artist: &self.artist,
+            album: &self.album,
+        }
+    }
+}
+
+/// プレイヤーバックエンド
+/// 再生制御・状態取得・ライブラリ列挙・プレイリスト操作をまとめた抽象化
+/// App はこのトレイト経由でのみプレイヤーを操作する
+pub trait PlayerBackend: Send + Sync {
+    /// 起動時の初期化（バックグラウンドスレッドから呼ばれる）
+    fn launch(&self) {}
+
+    // ========== 再生制御 ==========
+
+    fn play_pause(&self) -> Result<()>;
+    fn next_track(&self) -> Result<()>;
+    fn previous_track(&self) -> Result<()>;
+    /// シャッフルを切り替え、切替後の状態を返す
+    fn toggle_shuffle(&self) -> Result<bool>;
+    /// リピートを off → all → one → off と切り替え、切替後のモードを返す
+    fn cycle_repeat(&self) -> Result<String>;
+    fn seek_backward(&self) -> Result<()>;
+    fn seek_forward(&self) -> Result<()>;
+
+    // ========== 状態取得 ==========
+
+    /// 再生位置と再生中かどうか
+    fn get_position(&self) -> Result<(f64, bool)>;
+    /// 曲情報・音量・シャッフル・リピートをまとめて取得
+    fn get_all_state(&self) -> Result<PlayerState>;
+
+    // ========== ライブラリ列挙 ==========
+
+    /// ライブラリの総曲数
+    fn get_total_track_count(&self) -> Result<usize>;
+    /// 指定範囲のトラックを取得（1-indexed）
+    /// 解釈できなかった行も row_count に数えるので、呼び出し側はそのぶん先に進める
+    fn get_tracks_batch(&self, start: usize, count: usize) -> Result<TrackBatch>;
+    /// ライブラリ全体を一度に取得（初回のキャッシュ構築用）
+    /// プロパティごとの列でまとめて取得できるバックエンドは、get_tracks_batch の繰り返しより速い
+    fn get_all_tracks(&self) -> Result<TrackBatch>;
+    /// 指定日時（Unix timestamp）以降に追加されたトラックを取得
+    fn get_tracks_added_since(&self, unix_timestamp: u64) -> Result<TrackBatch>;
+    /// ライブラリの全曲の persistent ID（削除された曲の検出用）
+    fn get_all_persistent_ids(&self) -> Result<Vec<String>>;
+    /// 全曲の再生回数・お気に入りなど再生で変わる値（定期同期用）
+    fn get_track_stats(&self) -> Result<Vec<TrackStats>>;
+    /// プレイリスト・フォルダの一覧（表示順、子は親フォルダの後）
+    fn get_playlists(&self) -> Result<Vec<PlaylistInfo>>;
+    /// プレイリストごとの変更検出用の値（キャッシュの再検証用）
+    /// ids を指定するとそのプレイリストだけ（見つからない ID は結果に含めない）
+    fn get_playlist_fingerprints(&self, ids: Option<&[String]>) -> Result<Vec<PlaylistFingerprint>>;
+    /// プレイリストのトラックを取得
+    fn get_playlist_tracks(&self, playlist: PlaylistRef) -> Result<Vec<ListItem>>;
+
+    // ========== 再生 ==========
+
+    /// 単一の曲を再生（検索結果から）
+    fn play_track(&self, track: TrackRef) -> Result<()>;
+    /// プレイリストを track_index 番目（0-indexed）から再生
+    fn play_playlist_with_context(&self, playlist: PlaylistRef, track_index: usize) -> Result<()>;
+    /// アルバムを track_index 番目（0-indexed）から再生
+    /// album_artist はアルバムアーティスト（空の曲は曲のアーティスト）で、同じ名前の別のアルバムと区別する
+    fn play_album_with_context(&self, album_name: &str, album_artist: &str, track_index: usize) -> Result<()>;
+    /// 曲の並び（検索結果のグループなど）を track_index 番目から再生
+    fn play_tracks_with_context(&self, tracks: &[TrackRef], track_index: usize) -> Result<()>;
+
+    // ========== プレイリスト操作 ==========
+
+    /// 既存のプレイリストに曲を追加
+    fn add_track_to_playlist(&self, track: TrackRef, playlist: PlaylistRef) -> Result<()>;
+    /// 新規プレイリストを作成して曲を追加し、作成したプレイリストの persistent ID を返す
+    fn create_playlist_and_add_track(&self, playlist_name: &str, track: TrackRef) -> Result<String>;
+}
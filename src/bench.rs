@@ -0,0 +1,174 @@
+// Search benchmark on a synthetic library (test builds only)
+// `cargo test --release bench -- --ignored --nocapture` compares the indexed TrackCache::search with a
+// full linear scan, checks both return the same tracks, then times the fuzzy mode, which scores every track
+// BENCH_TRACKS sets the library size
+
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::cache::{CachedTrack, TrackCache};
+use crate::query::Query;
+
+const DEFAULT_TRACKS: usize = 60_000;
+const RUNS: u32 = 20;
+
+const SYLLABLES: &[&str] = &[
+    "ka", "ri", "mo", "la", "ne", "so", "tu", "vi", "ba", "el", "or", "an", "de", "mi", "ra", "to",
+    "lu", "shi", "ko", "na", "be", "ya", "zo", "fe", "ha", "ru", "ni", "po", "ge", "wa",
+];
+const JAPANESE: &[&str] = &["夜", "空", "海", "花", "雨", "光", "風", "星", "アイ", "ソラ", "ユメ", "ヒカリ"];
+
+// 典型的な入力（部分一致、大文字、フィールド指定、短い語、ヒットなし）
+const QUERIES: &[&str] = &[
+    "love",
+    "night",
+    "Blake",
+    "love ka",
+    "artist:\"James Blake\"",
+    "name:night ka",
+    "name:Love",
+    "love -night year:1990..1999",
+    "(love OR night) fav:true plays:>50",
+    "ka",
+    "夜空",
+    "zzzz",
+];
+
+const FUZZY_QUERIES: &[&str] = &["jmsblk", "lvnight", "artist:\"James Blake\" lv"];
+
+#[test]
+fn indexed_search_matches_linear_scan() -> Result<()> {
+    let mut cache = synthetic_cache(2_000);
+    for text in QUERIES {
+        let query = &Query::parse(text)?;
+        let linear = cache.search_linear(query);
+        let indexed = cache.search(query);
+        assert_eq!(linear, indexed, "results differ for {:?}", text);
+    }
+    Ok(())
+}
+
+#[test]
+#[ignore = "benchmark; run with --release -- --ignored --nocapture"]
+fn bench_search() -> Result<()> {
+    let count = match std::env::var("BENCH_TRACKS") {
+        Ok(n) => n.parse().map_err(|_| anyhow::anyhow!("Invalid track count: {}", n))?,
+        Err(_) => DEFAULT_TRACKS,
+    };
+
+    let mut cache = synthetic_cache(count);
+    println!("{} synthetic tracks, {} runs per query", count, RUNS);
+
+    let started = Instant::now();
+    cache.search(&Query::parse("")?);
+    println!("index build: {:.1} ms\n", ms(started.elapsed()));
+
+    println!("{:<40} {:>7} {:>12} {:>12} {:>8}", "query", "hits", "linear ms", "indexed ms", "speed-up");
+    for text in QUERIES {
+        let query = &Query::parse(text)?;
+        let linear = cache.search_linear(query);
+        let indexed = cache.search(query);
+        anyhow::ensure!(linear == indexed, "results differ for {:?}", text);
+
+        let linear_time = time(|| cache.search_linear(query).len());
+        let indexed_time = time(|| cache.search(query).len());
+        println!(
+            "{:<40} {:>7} {:>12.3} {:>12.3} {:>7.1}x",
+            text,
+            indexed.len(),
+            ms(linear_time),
+            ms(indexed_time),
+            linear_time.as_secs_f64() / indexed_time.as_secs_f64().max(1e-9),
+        );
+    }
+
+    // あいまい検索は部分列一致なので全件を採点する
+    println!("\n{:<40} {:>7} {:>12}", "fuzzy query", "hits", "ms");
+    for text in FUZZY_QUERIES {
+        let query = &Query::parse(text)?;
+        let hits = cache.search_fuzzy(query).len();
+        let fuzzy_time = time(|| cache.search_fuzzy(query).len());
+        println!("{:<40} {:>7} {:>12.3}", text, hits, ms(fuzzy_time));
+    }
+    Ok(())
+}
+
+/// 1回あたりの平均時間
+fn time(mut f: impl FnMut() -> usize) -> Duration {
+    let started = Instant::now();
+    for _ in 0..RUNS {
+        std::hint::black_box(f());
+    }
+    started.elapsed() / RUNS
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+/// 乱数の種を固定した架空のライブラリ
+fn synthetic_cache(count: usize) -> TrackCache {
+    let mut rng = StdRng::seed_from_u64(42);
+    let artists: Vec<String> = (0..count / 20 + 1)
+        .map(|i| if i % 50 == 0 { "James Blake".to_string() } else { title(&mut rng, 2) })
+        .collect();
+
+    let mut tracks = Vec::with_capacity(count);
+    let mut album = String::new();
+    let mut artist = String::new();
+    let mut year = 1970;
+    for i in 0..count {
+        // 12曲ごとに新しいアルバム
+        if i % 12 == 0 {
+            artist = artists.choose(&mut rng).cloned().unwrap_or_default();
+            album = title(&mut rng, 3);
+            year = rng.gen_range(1960..2025);
+        }
+        let name = match rng.gen_range(0..10) {
+            0 => format!("Love {}", title(&mut rng, 1)),
+            1 => format!("{} Night", title(&mut rng, 2)),
+            2 => JAPANESE.choose(&mut rng).copied().unwrap_or_default().to_string()
+                + JAPANESE.choose(&mut rng).copied().unwrap_or_default(),
+            _ => title(&mut rng, 3),
+        };
+        tracks.push(CachedTrack::new(
+            name,
+            artist.clone(),
+            album.clone(),
+            String::new(),
+            1_400_000_000 + i as u64 * 600,
+            year,
+            (i % 12) as u32 + 1,
+            1,
+            "3:30".to_string(),
+            rng.gen_range(0..100),
+            rng.gen_bool(0.1),
+            0,
+            0,
+            format!("{:016X}", i),
+        ));
+    }
+
+    let mut cache = TrackCache::default();
+    cache.total_tracks = count;
+    cache.add_tracks(tracks);
+    cache
+}
+
+/// 音節をつないだ架空の単語を words 個並べる
+fn title(rng: &mut StdRng, words: usize) -> String {
+    (0..rng.gen_range(1..=words))
+        .map(|_| {
+            let word: String = (0..rng.gen_range(2..4))
+                .filter_map(|_| SYLLABLES.choose(rng).copied())
+                .collect();
+            let mut chars = word.chars();
+            chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
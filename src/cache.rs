@@ -1,16 +1,381 @@
 use anyhow::Result;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::fs;
-use std::path::PathBuf;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::backend::PlaylistRef;
+use crate::fuzzy::FieldHighlights;
+use crate::query::Query;
+use crate::music::{PlaylistInfo, TrackStats};
+use crate::normalize::{self, SearchKey};
+use crate::search_groups::SearchGrouping;
+use crate::search_index::SearchIndex;
+use crate::sort::ViewSorts;
+
+// キャッシュディレクトリの差し替え先（デモモードなど）
+static CACHE_DIR_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// キャッシュディレクトリを差し替える（起動時、App::new より前に一度だけ呼ぶ）
+pub fn set_cache_dir(path: PathBuf) {
+    let _ = CACHE_DIR_OVERRIDE.set(path);
+}
+
+/// キャッシュ・設定ファイルを置くディレクトリ
+fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = CACHE_DIR_OVERRIDE.get() {
+        return Some(dir.clone());
+    }
+    dirs::cache_dir().map(|p| p.join("macos-music-tui"))
+}
+
+// ========== スキーマバージョン ==========
+
+/// 各ファイルの現在のスキーマバージョン（schema_version のない古いファイルは 0 とみなす）
+pub const TRACK_CACHE_VERSION: u32 = 2;
+pub const PLAYLIST_CACHE_VERSION: u32 = 2;
+pub const SETTINGS_VERSION: u32 = 1;
+pub const SEARCH_HISTORY_VERSION: u32 = 1;
+
+/// バージョン n のファイルを n + 1 に変換する（MIGRATIONS[n]）
+type Migration = fn(&mut serde_json::Value);
+
+/// 0 -> 1: schema_version を付けただけで中身は同じ
+fn migrate_unversioned(_: &mut serde_json::Value) {}
+
+const TRACK_CACHE_MIGRATIONS: &[Migration] = &[migrate_unversioned, migrate_date_added_to_epoch];
+const PLAYLIST_CACHE_MIGRATIONS: &[Migration] = &[migrate_unversioned, migrate_playlist_kind];
+const SETTINGS_MIGRATIONS: &[Migration] = &[migrate_unversioned];
+const SEARCH_HISTORY_MIGRATIONS: &[Migration] = &[migrate_unversioned];
+
+/// playlists 1 -> 2: 一覧の is_folder を kind にする（それ以外の種類は次の一覧取得で入る）
+fn migrate_playlist_kind(value: &mut serde_json::Value) {
+    let Some(tree) = value.get_mut("tree").and_then(|t| t.as_array_mut()) else {
+        return;
+    };
+    for entry in tree.iter_mut().filter_map(|e| e.as_object_mut()) {
+        let is_folder = entry.remove("is_folder").and_then(|f| f.as_bool()).unwrap_or(false);
+        entry.insert("kind".to_string(), if is_folder { "folder" } else { "user" }.into());
+    }
+}
+
+/// tracks 1 -> 2: date_added を AppleScript の日付文字列から Unix timestamp にする
+/// 文字列はローカル時刻なので、その日時の UTC オフセット（夏時間を含む）で補正する
+fn migrate_date_added_to_epoch(value: &mut serde_json::Value) {
+    let Some(tracks) = value.get_mut("tracks").and_then(|t| t.as_array_mut()) else {
+        return;
+    };
+    for track in tracks {
+        let Some(date) = track.get_mut("date_added") else { continue };
+        if let Some(text) = date.as_str() {
+            let epoch = parse_legacy_date(text).map(|local| local_to_epoch(local).max(0) as u64).unwrap_or(0);
+            *date = epoch.into();
+        }
+    }
+}
+
+/// 現在のローカル時刻の UTC からのずれ（秒）
+pub fn local_utc_offset() -> i64 {
+    chrono::Local::now().offset().local_minus_utc() as i64
+}
+
+/// ローカル時刻の秒数（1970-01-01 00:00 起点）を Unix timestamp にする
+/// 夏時間の切り替えで存在しない時刻は現在のずれで補正する
+fn local_to_epoch(local: i64) -> i64 {
+    chrono::DateTime::from_timestamp(local, 0)
+        .and_then(|utc| utc.naive_utc().and_local_timezone(chrono::Local).earliest())
+        .map(|t| t.timestamp())
+        .unwrap_or_else(|| local - local_utc_offset())
+}
+
+/// 月の名前（ロケールごとの正式名と略称、1月から順）
+const MONTH_NAMES: [[&str; 12]; 8] = [
+    ["january", "february", "march", "april", "may", "june", "july", "august", "september", "october", "november", "december"],
+    ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"],
+    ["januar", "februar", "märz", "april", "mai", "juni", "juli", "august", "september", "oktober", "november", "dezember"],
+    ["jan", "feb", "mär", "apr", "mai", "jun", "jul", "aug", "sep", "okt", "nov", "dez"],
+    ["janvier", "février", "mars", "avril", "mai", "juin", "juillet", "août", "septembre", "octobre", "novembre", "décembre"],
+    ["janv", "févr", "mars", "avr", "mai", "juin", "juil", "août", "sept", "oct", "nov", "déc"],
+    ["enero", "febrero", "marzo", "abril", "mayo", "junio", "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre"],
+    ["ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sep", "oct", "nov", "dic"],
+];
+
+/// 表に入らない別表記
+const MONTH_VARIANTS: [(&str, i64); 5] = [("sept", 9), ("mrz", 3), ("jänner", 1), ("fév", 2), ("setiembre", 9)];
+
+/// 語全体が月の名前なら月の番号（曜日 "martes" "mardi" などは月にしない）
+fn month_number(token: &str) -> Option<i64> {
+    MONTH_NAMES
+        .iter()
+        .find_map(|names| names.iter().position(|m| *m == token).map(|i| i as i64 + 1))
+        .or_else(|| MONTH_VARIANTS.iter().find(|(m, _)| *m == token).map(|&(_, n)| n))
+}
+
+/// 数字（と時刻の ":"）の並びと文字の並びに分ける（それ以外の記号と空白は区切り）
+/// 例: "2015年9月13日 午後3:44:42" -> [2015, 年, 9, 月, 13, 日, 午後, 3:44:42]
+fn legacy_date_tokens(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut current: Option<(usize, bool)> = None;  // (開始位置, 数字の並びか)
+    for (i, c) in text.char_indices() {
+        let class = if c.is_ascii_digit() || c == ':' {
+            Some(true)
+        } else if c.is_alphabetic() {
+            Some(false)
+        } else {
+            None
+        };
+        if let Some((start, numeric)) = current {
+            if class == Some(numeric) {
+                continue;
+            }
+            tokens.push(&text[start..i]);
+        }
+        current = class.map(|numeric| (i, numeric));
+    }
+    if let Some((start, _)) = current {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+/// 旧キャッシュの日付文字列をローカル時刻の秒数（1970-01-01 00:00 起点）にする
+/// 英語 "Sunday, September 13, 2015 at 3:44:42 PM"、日本語 "2015年9月13日 日曜日 15:44:42"、
+/// ドイツ語・フランス語・スペイン語の月名、数字だけの "2015/09/13 15:44:42" 形式を受け付ける。解釈できなければ None
+fn parse_legacy_date(text: &str) -> Option<i64> {
+    let lower = text.to_lowercase();
+
+    // 時刻（":" を含む数字の並び）とそれ以外の数字を分ける。曜日などの語は読み飛ばす
+    let mut time: Option<(i64, i64, i64)> = None;
+    let mut numbers: Vec<&str> = Vec::new();
+    let mut month_name = None;
+    let (mut am, mut pm) = (false, false);
+    for token in legacy_date_tokens(&lower) {
+        if token.contains(':') {
+            let mut parts = token.split(':').map(|p| p.parse::<i64>().ok());
+            let h = parts.next().flatten()?;
+            let m = parts.next().flatten().unwrap_or(0);
+            let s = parts.next().flatten().unwrap_or(0);
+            time = Some((h, m, s));
+        } else if token.starts_with(|c: char| c.is_ascii_digit()) {
+            numbers.push(token);
+        } else if token == "am" || token == "午前" {
+            am = true;
+        } else if token == "pm" || token == "午後" {
+            pm = true;
+        } else if let Some(month) = month_number(token) {
+            month_name.get_or_insert(month);
+        }
+    }
+
+    let year_pos = numbers.iter().position(|n| n.len() == 4)?;
+    let year: i64 = numbers[year_pos].parse().ok()?;
+    let rest: Vec<i64> = numbers.iter().enumerate()
+        .filter(|(i, _)| *i != year_pos)
+        .filter_map(|(_, n)| n.parse().ok())
+        .collect();
+    let (month, day) = match month_name {
+        Some(month) => (month, *rest.first()?),
+        // 年が先頭なら 年/月/日、末尾なら 日/月/年（13 以上が先なら）か 月/日/年
+        None if year_pos == 0 => (*rest.first()?, *rest.get(1)?),
+        None => {
+            let (a, b) = (*rest.first()?, *rest.get(1)?);
+            if a > 12 { (b, a) } else { (a, b) }
+        }
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let (mut hour, minute, second) = time.unwrap_or((0, 0, 0));
+    if pm && hour < 12 {
+        hour += 12;
+    } else if am && hour == 12 {
+        hour = 0;
+    }
+    Some(days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+/// 1970-01-01 からの日数
+pub fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// このバージョンのアプリより新しいスキーマのファイル
+/// データを捨てないよう、読み込んだ側は保存しない
+#[derive(Debug, Clone)]
+pub struct NewerSchema {
+    pub file: &'static str,
+    pub found: u32,
+    pub supported: u32,
+}
+
+impl fmt::Display for NewerSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} was written by a newer version of mmt (schema {}, this build supports {}). It will not be modified; please update mmt",
+            self.file, self.found, self.supported
+        )
+    }
+}
+
+impl std::error::Error for NewerSchema {}
+
+/// 保存時にデータの先頭へ schema_version を付ける
+#[derive(Serialize)]
+struct Versioned<'a, T> {
+    schema_version: u32,
+    #[serde(flatten)]
+    data: &'a T,
+}
+
+/// バージョン付きの JSON を読み込む（ファイルがなければ None）
+/// 古いスキーマは migrations を順に適用し、変換後の内容でファイルを書き換える
+/// 新しすぎるスキーマは NewerSchema エラーにする
+fn read_versioned<T: DeserializeOwned + Serialize>(
+    path: &Path,
+    file: &'static str,
+    current: u32,
+    migrations: &[Migration],
+) -> Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path)?;
+    let mut value: serde_json::Value = serde_json::from_str(&content)?;
+    let found = value
+        .get("schema_version")
+        .and_then(|v| v.as_u64())
+        .map(|v| v as u32)
+        .unwrap_or(0);
+    if found > current {
+        return Err(NewerSchema { file, found, supported: current }.into());
+    }
+
+    for migrate in &migrations[found as usize..current as usize] {
+        migrate(&mut value);
+    }
+    if let Some(object) = value.as_object_mut() {
+        object.remove("schema_version");
+    }
+    let data: T = serde_json::from_value(value)?;
+    if found < current {
+        write_versioned(path, &data, current)?;
+    }
+    Ok(Some(data))
+}
+
+/// 一時ファイルに書いてから rename で置き換える（書き込み途中で落ちても本体は壊れない）
+/// 置き換える前の本体は `.bak` にコピーして残す（本体がない瞬間を作らない）
+fn write_versioned<T: Serialize>(path: &Path, data: &T, version: u32) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let content = serde_json::to_string(&Versioned { schema_version: version, data })?;
+
+    let tmp = with_suffix(path, ".tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+
+    if path.exists() {
+        fs::copy(path, with_suffix(path, ".bak"))?;
+    }
+    fs::rename(&tmp, path)?;
+    sync_parent_dir(path)
+}
+
+/// rename を確実にディスクへ残すため、ディレクトリも fsync する
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        File::open(parent)?.sync_all()?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// "tracks.json" -> "tracks.json.bak" など
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// load_versioned の結果
+enum Loaded<T> {
+    /// ファイルがない（初回起動）
+    Missing,
+    Ok(T),
+    /// 本体が読めなかったのでバックアップから復元した（理由つき）
+    Recovered(T, String),
+    /// 本体もバックアップも読めなかった
+    Unreadable(String),
+    Newer(NewerSchema),
+}
+
+/// 本体を読み、なければ・壊れていれば `.bak` から復元する
+fn load_versioned<T: DeserializeOwned + Serialize>(
+    path: &Path,
+    file: &'static str,
+    current: u32,
+    migrations: &[Migration],
+) -> Loaded<T> {
+    let problem = match read_versioned(path, file, current, migrations) {
+        Ok(Some(data)) => return Loaded::Ok(data),
+        Ok(None) => format!("{} was missing", file),
+        Err(e) => match e.downcast::<NewerSchema>() {
+            Ok(newer) => return Loaded::Newer(newer),
+            Err(e) => format!("{} could not be read ({})", file, e),
+        },
+    };
+
+    let backup = with_suffix(path, ".bak");
+    if !backup.exists() {
+        return if path.exists() {
+            Loaded::Unreadable(format!("{}; no backup, starting over", problem))
+        } else {
+            Loaded::Missing
+        };
+    }
+    // バックアップを本体に戻してから読む（壊れた本体を次の保存で .bak に回さないため）
+    if let Err(e) = fs::copy(&backup, path) {
+        return Loaded::Unreadable(format!("{}; backup could not be restored ({})", problem, e));
+    }
+    match read_versioned(path, file, current, migrations) {
+        Ok(Some(data)) => Loaded::Recovered(data, format!("{}; restored the last backup", problem)),
+        Ok(None) => Loaded::Missing,
+        Err(e) => match e.downcast::<NewerSchema>() {
+            Ok(newer) => Loaded::Newer(newer),
+            Err(e) => Loaded::Unreadable(format!("{}; backup is unreadable too ({})", problem, e)),
+        },
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CachedTrack {
     pub name: String,
     pub artist: String,
     pub album: String,
     #[serde(default)]
-    pub date_added: String,
+    pub album_artist: String,  // アルバムアーティスト（未設定・古いキャッシュでは空）
+    #[serde(default)]
+    pub date_added: u64,  // 追加日（Unix timestamp）
     #[serde(default)]
     pub year: u32,
     #[serde(default)]
@@ -23,29 +388,42 @@ pub struct CachedTrack {
     pub played_count: u32,
     #[serde(default)]
     pub favorited: bool,
-    // 検索用に小文字化した文字列
+    #[serde(default)]
+    pub rating: u32,  // 0〜100
+    #[serde(default)]
+    pub played_date: u64,  // 最終再生日（Unix timestamp、未再生は 0）
+    // Music の persistent ID（古いキャッシュでは空）
+    #[serde(default)]
+    pub persistent_id: String,
+    // 検索用に正規化した文字列
     #[serde(skip)]
-    pub search_key: String,
+    pub search_key: SearchKey,
 }
 
 impl CachedTrack {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         name: String,
         artist: String,
         album: String,
-        date_added: String,
+        album_artist: String,
+        date_added: u64,
         year: u32,
         track_number: u32,
         disc_number: u32,
         time: String,
         played_count: u32,
         favorited: bool,
+        rating: u32,
+        played_date: u64,
+        persistent_id: String,
     ) -> Self {
-        let search_key = format!("{} {} {}", name, artist, album).to_lowercase();
+        let search_key = SearchKey::new([&name, &artist, &album]);
         Self {
             name,
             artist,
             album,
+            album_artist,
             date_added,
             year,
             track_number,
@@ -53,12 +431,26 @@ impl CachedTrack {
             time,
             played_count,
             favorited,
+            rating,
+            played_date,
+            persistent_id,
             search_key,
         }
     }
 
     pub fn init_search_key(&mut self) {
-        self.search_key = format!("{} {} {}", self.name, self.artist, self.album).to_lowercase();
+        self.search_key = SearchKey::new(self.search_fields());
+    }
+
+    /// 検索対象のフィールド（name, artist, album）
+    pub fn search_fields(&self) -> [&str; 3] {
+        [&self.name, &self.artist, &self.album]
+    }
+
+    /// アルバムを区別するアーティスト（アルバムアーティスト、空なら曲のアーティスト）
+    /// コンピレーションは1枚のまま、同じ名前の別のアルバムは分けて扱う
+    pub fn album_artist_or_artist(&self) -> &str {
+        if self.album_artist.is_empty() { &self.artist } else { &self.album_artist }
     }
 }
 
@@ -66,17 +458,28 @@ impl CachedTrack {
 pub struct TrackCache {
     pub total_tracks: usize,
     pub loaded_tracks: usize,
+    /// 解釈できずに飛ばしたライブラリの行数（total_tracks には含まれ、tracks には入らない）
+    #[serde(default)]
+    pub skipped_tracks: usize,
     pub last_updated: Option<u64>,  // Unix timestamp
     pub tracks: Vec<CachedTrack>,
     #[serde(skip)]
     search_keys_initialized: bool,
     #[serde(skip)]
+    search_index: Option<SearchIndex>,  // 初回検索時に作る
+    #[serde(skip)]
+    id_positions: Option<HashMap<String, usize>>,  // persistent ID → 添字（ID で引くときに作る）
+    #[serde(skip)]
     pub is_fresh_build: bool,  // true if no prior cache existed
+    #[serde(skip)]
+    pub newer_schema: Option<NewerSchema>,  // 新しいバージョンのファイル（保存しない）
+    #[serde(skip)]
+    pub load_notice: Option<String>,  // 読み込み時の復元・破損の報告
 }
 
 impl TrackCache {
     fn cache_path() -> Option<PathBuf> {
-        dirs::cache_dir().map(|p| p.join("macos-music-tui").join("tracks.json"))
+        cache_dir().map(|p| p.join("tracks.json"))
     }
 
     pub fn load() -> Self {
@@ -84,31 +487,27 @@ impl TrackCache {
             return Self { is_fresh_build: true, ..Self::default() };
         };
 
-        if !path.exists() {
-            return Self { is_fresh_build: true, ..Self::default() };
-        }
-
-        match fs::read_to_string(&path) {
-            Ok(content) => {
-                serde_json::from_str::<TrackCache>(&content).unwrap_or_default()
-            }
-            Err(_) => Self { is_fresh_build: true, ..Self::default() },
+        match load_versioned::<TrackCache>(&path, "tracks.json", TRACK_CACHE_VERSION, TRACK_CACHE_MIGRATIONS) {
+            Loaded::Ok(cache) => cache,
+            Loaded::Recovered(cache, notice) => Self { load_notice: Some(notice), ..cache },
+            Loaded::Newer(newer) => Self {
+                load_notice: Some(newer.to_string()),
+                newer_schema: Some(newer),
+                ..Self::default()
+            },
+            Loaded::Unreadable(notice) => Self { is_fresh_build: true, load_notice: Some(notice), ..Self::default() },
+            Loaded::Missing => Self { is_fresh_build: true, ..Self::default() },
         }
     }
 
     pub fn save(&mut self) -> Result<()> {
+        if let Some(newer) = &self.newer_schema {
+            return Err(newer.clone().into());
+        }
         let Some(path) = Self::cache_path() else {
             anyhow::bail!("Could not determine cache directory");
         };
-
-        // ディレクトリを作成
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-
-        let content = serde_json::to_string(self)?;
-        fs::write(&path, content)?;
-        Ok(())
+        write_versioned(&path, self, TRACK_CACHE_VERSION)
     }
 
     /// last_updated を現在時刻に更新
@@ -122,20 +521,46 @@ impl TrackCache {
     }
 
     pub fn add_tracks(&mut self, new_tracks: Vec<CachedTrack>) {
+        let start = self.tracks.len();
         self.tracks.extend(new_tracks);
         self.loaded_tracks = self.tracks.len();
+        self.id_positions = None;
+        if let Some(index) = &mut self.search_index {
+            for (i, track) in self.tracks.iter().enumerate().skip(start) {
+                index.insert(i, &track.search_key.folded);
+            }
+        }
     }
 
     /// トラックを upsert（既存なら更新、なければ追加）
-    /// キーは name + artist + album
+    /// キーは persistent ID（ID のない古い行は起動時の取り直しで置き換えるので、ここでは照合しない）
     pub fn upsert_tracks(&mut self, new_tracks: Vec<CachedTrack>) -> usize {
+        // 既存トラックの位置は最初に一度だけ引いておく
+        let positions: Vec<Option<usize>> = {
+            let by_id: HashMap<&str, usize> = self.tracks
+                .iter()
+                .enumerate()
+                .filter(|(_, t)| !t.persistent_id.is_empty())
+                .map(|(i, t)| (t.persistent_id.as_str(), i))
+                .collect();
+            new_tracks.iter().map(|t| by_id.get(t.persistent_id.as_str()).copied()).collect()
+        };
+        // この呼び出しで追加した曲（同じ ID が2回来ても1曲にする）
+        let mut appended: HashMap<String, usize> = HashMap::new();
+
         let mut added_count = 0;
-        for new_track in new_tracks {
-            // 既存トラックを検索
-            if let Some(existing) = self.tracks.iter_mut().find(|t| {
-                t.name == new_track.name && t.artist == new_track.artist && t.album == new_track.album
-            }) {
+        for (new_track, position) in new_tracks.into_iter().zip(positions) {
+            let position = position.or_else(|| appended.get(&new_track.persistent_id).copied());
+            if let Some(i) = position {
                 // 既存トラックを更新
+                let existing = &mut self.tracks[i];
+                if let Some(index) = &mut self.search_index {
+                    index.remove(i, &existing.search_key.folded);
+                }
+                existing.name = new_track.name;
+                existing.artist = new_track.artist;
+                existing.album = new_track.album;
+                existing.persistent_id = new_track.persistent_id;
                 existing.date_added = new_track.date_added;
                 existing.year = new_track.year;
                 existing.track_number = new_track.track_number;
@@ -143,26 +568,107 @@ impl TrackCache {
                 existing.time = new_track.time;
                 existing.played_count = new_track.played_count;
                 existing.favorited = new_track.favorited;
+                existing.rating = new_track.rating;
+                existing.played_date = new_track.played_date;
                 existing.init_search_key();
+                if let Some(index) = &mut self.search_index {
+                    index.insert(i, &existing.search_key.folded);
+                }
             } else {
                 // 新規トラックを追加
+                if let Some(index) = &mut self.search_index {
+                    index.insert(self.tracks.len(), &new_track.search_key.folded);
+                }
+                if !new_track.persistent_id.is_empty() {
+                    appended.insert(new_track.persistent_id.clone(), self.tracks.len());
+                }
                 self.tracks.push(new_track);
                 added_count += 1;
             }
         }
         self.loaded_tracks = self.tracks.len();
+        if added_count > 0 {
+            self.id_positions = None;
+        }
         added_count
     }
 
+    /// 再生で変わる値だけを更新し、値が変わった曲の数を返す
+    pub fn apply_stats(&mut self, stats: &HashMap<String, TrackStats>) -> usize {
+        let mut changed = 0;
+        for track in &mut self.tracks {
+            let Some(s) = stats.get(&track.persistent_id) else { continue };
+            if track.played_count != s.played_count
+                || track.favorited != s.favorited
+                || track.rating != s.rating
+                || track.played_date != s.played_date
+            {
+                track.played_count = s.played_count;
+                track.favorited = s.favorited;
+                track.rating = s.rating;
+                track.played_date = s.played_date;
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    /// ID のない古い行があるか（あれば起動時にライブラリ全体を取り直す）
+    pub fn has_untracked_tracks(&self) -> bool {
+        self.tracks.iter().any(|t| t.persistent_id.is_empty())
+    }
+
+    /// 取り直したライブラリ全体で置き換える
+    pub fn replace_tracks(&mut self, tracks: Vec<CachedTrack>) {
+        self.tracks = tracks;
+        self.loaded_tracks = self.tracks.len();
+        self.search_index = None;
+        self.id_positions = None;
+    }
+
+    /// 指定した persistent ID の曲を取り除き、取り除いた数を返す
+    pub fn remove_tracks(&mut self, persistent_ids: &std::collections::HashSet<String>) -> usize {
+        let before = self.tracks.len();
+        self.tracks.retain(|t| t.persistent_id.is_empty() || !persistent_ids.contains(&t.persistent_id));
+        self.loaded_tracks = self.tracks.len();
+        if self.tracks.len() != before {
+            // 添字がずれるので次の検索で作り直す
+            self.search_index = None;
+            self.id_positions = None;
+        }
+        before - self.tracks.len()
+    }
+
+    /// persistent ID の曲の追加日
+    pub fn date_added(&mut self, persistent_id: &str) -> Option<u64> {
+        self.track_by_id(persistent_id).map(|t| t.date_added)
+    }
+
+    /// persistent ID で曲を引く
+    /// ID → 添字の表は初回に作り、曲が増減するまで使い回す
+    pub fn track_by_id(&mut self, persistent_id: &str) -> Option<&CachedTrack> {
+        let tracks = &self.tracks;
+        let positions = self.id_positions.get_or_insert_with(|| {
+            tracks
+                .iter()
+                .enumerate()
+                .filter(|(_, t)| !t.persistent_id.is_empty())
+                .map(|(i, t)| (t.persistent_id.clone(), i))
+                .collect()
+        });
+        positions.get(persistent_id).map(|&i| &tracks[i])
+    }
+
+    /// 飛ばした行も読み込み済みに数える（壊れた行のせいで毎回取り直さない）
     pub fn is_complete(&self) -> bool {
-        self.total_tracks > 0 && self.loaded_tracks >= self.total_tracks
+        self.total_tracks > 0 && self.loaded_tracks + self.skipped_tracks >= self.total_tracks
     }
 
-    /// 最終更新日を "Last updated: yyyy/MM/dd hh:mm" 形式で返す（JST）
+    /// 最終更新日を "Last updated: yyyy/MM/dd hh:mm" 形式で返す（ローカル時刻）
     pub fn format_last_updated(&self) -> Option<String> {
         self.last_updated.map(|ts| {
-            // Unix timestamp (UTC) を JST (UTC+9) に変換
-            let ts_local = ts as i64 + 9 * 3600;
+            // Unix timestamp (UTC) をローカル時刻に変換
+            let ts_local = ts as i64 + local_utc_offset();
 
             // 時刻を計算
             let seconds_in_day = ((ts_local % 86400) + 86400) % 86400; // 負数対応
@@ -213,172 +719,57 @@ impl TrackCache {
         }
     }
 
-    /// クエリを引用符を尊重してトークン化
-    /// 例: `artist:"James Blake" love` -> ["artist:\"James Blake\"", "love"]
-    fn tokenize_query(query: &str) -> Vec<String> {
-        let mut tokens = Vec::new();
-        let mut current = String::new();
-        let mut in_quotes = false;
-        let mut quote_char = '"';
-
-        for ch in query.chars() {
-            match ch {
-                '"' | '\'' if !in_quotes => {
-                    in_quotes = true;
-                    quote_char = ch;
-                    current.push(ch);
-                }
-                c if c == quote_char && in_quotes => {
-                    in_quotes = false;
-                    current.push(ch);
-                }
-                ' ' | '\t' if !in_quotes => {
-                    if !current.is_empty() {
-                        tokens.push(current.clone());
-                        current.clear();
-                    }
-                }
-                _ => {
-                    current.push(ch);
-                }
-            }
-        }
-
-        if !current.is_empty() {
-            tokens.push(current);
+    /// 検索インデックスを遅延構築
+    fn ensure_search_index(&mut self) {
+        self.ensure_search_keys();
+        if self.search_index.is_none() {
+            let tracks = self.tracks.iter().map(|t| t.search_key.folded.as_str()).enumerate();
+            self.search_index = Some(SearchIndex::build(tracks));
         }
-
-        tokens
     }
 
-    /// あいまい検索 - クエリの各単語がトラック情報に含まれているか
-    /// 全て小文字の場合は case insensitive、大文字が含まれる場合は case sensitive
-    /// 高度な検索: "Name:{key} Artist:{key} Album:{key}" でフィールド指定検索
-    /// フィールド名は大文字小文字を区別しない (name:, Name:, NAME: など)
-    /// "" または '' で囲むと完全一致検索 (例: artist:"James Blake")
-    pub fn search(&mut self, query: &str) -> Vec<CachedTrack> {
-        self.ensure_search_keys();
-
-        // 引用符を尊重してトークン化
-        let tokens = Self::tokenize_query(query);
-
-        // フィールド指定フィルタと一般検索語を分離
-        // SearchFilter: (value, is_exact_match)
-        let mut name_filters: Vec<(String, bool)> = Vec::new();
-        let mut artist_filters: Vec<(String, bool)> = Vec::new();
-        let mut album_filters: Vec<(String, bool)> = Vec::new();
-        let mut general_words: Vec<String> = Vec::new();
-
-        for token in &tokens {
-            let token_lower = token.to_lowercase();
-            if token_lower.starts_with("name:") {
-                if let Some((value, exact)) = Self::parse_filter_value(&token[5..]) {
-                    name_filters.push((value, exact));
-                }
-            } else if token_lower.starts_with("artist:") {
-                if let Some((value, exact)) = Self::parse_filter_value(&token[7..]) {
-                    artist_filters.push((value, exact));
-                }
-            } else if token_lower.starts_with("album:") {
-                if let Some((value, exact)) = Self::parse_filter_value(&token[6..]) {
-                    album_filters.push((value, exact));
-                }
-            } else {
-                general_words.push(token.clone());
-            }
+    /// クエリに一致するトラックの添字（tracks の順）。構文は query.rs を参照
+    /// trigram インデックスで候補を絞ってから判定する（結果と順序は全件走査と同じ）
+    pub fn search(&mut self, query: &Query) -> Vec<usize> {
+        self.ensure_search_index();
+        let candidates = self.search_index.as_ref().and_then(|index| index.candidates(&query.needles(false)));
+        match candidates {
+            Some(indices) => indices.into_iter().filter(|&i| query.matches(&self.tracks[i])).collect(),
+            None => (0..self.tracks.len()).filter(|&i| query.matches(&self.tracks[i])).collect(),
         }
+    }
 
-        self.tracks
-            .iter()
-            .filter(|track| {
-                // Name フィルタ (AND条件)
-                for (key, exact) in &name_filters {
-                    if !Self::field_match(&track.name, key, *exact) {
-                        return false;
-                    }
-                }
-
-                // Artist フィルタ (AND条件)
-                for (key, exact) in &artist_filters {
-                    if !Self::field_match(&track.artist, key, *exact) {
-                        return false;
-                    }
-                }
-
-                // Album フィルタ (AND条件)
-                for (key, exact) in &album_filters {
-                    if !Self::field_match(&track.album, key, *exact) {
-                        return false;
-                    }
-                }
-
-                // 一般検索語 (各語がname/artist/albumのいずれかに含まれる)
-                for word in &general_words {
-                    let has_uppercase = word.chars().any(|c| c.is_uppercase());
-                    let matched = if has_uppercase {
-                        let search_target = format!("{} {} {}", track.name, track.artist, track.album);
-                        search_target.contains(word.as_str())
-                    } else {
-                        track.search_key.contains(&word.to_lowercase())
-                    };
-                    if !matched {
-                        return false;
-                    }
-                }
+    /// あいまい検索（fzf 風）: 一般検索語を部分列として一致させ、スコアを付けて返す（tracks の順）
+    /// フィールド指定などのフィルタは search と同じく絞り込みに使う
+    pub fn search_fuzzy(&mut self, query: &Query) -> Vec<FuzzyHit> {
+        self.ensure_search_index();
 
-                true
+        // 部分列はインデックスで絞れないので、フィルタの値だけで候補を絞る
+        let candidates = self.search_index.as_ref().and_then(|index| index.candidates(&query.needles(true)));
+        let indices: Box<dyn Iterator<Item = usize>> = match candidates {
+            Some(indices) => Box::new(indices.into_iter()),
+            None => Box::new(0..self.tracks.len()),
+        };
+        indices
+            .filter_map(|index| {
+                let (score, highlights) = query.fuzzy_match(&self.tracks[index])?;
+                Some(FuzzyHit { index, score, highlights })
             })
-            .cloned()
             .collect()
     }
 
-    /// フィルタ値をパース: 引用符で囲まれていれば (値, true)、そうでなければ (値, false)
-    fn parse_filter_value(value: &str) -> Option<(String, bool)> {
-        if value.is_empty() {
-            return None;
-        }
-
-        // "" または '' で囲まれている場合は完全一致
-        if value.len() >= 2 &&
-           ((value.starts_with('"') && value.ends_with('"')) ||
-            (value.starts_with('\'') && value.ends_with('\''))) {
-            let inner = &value[1..value.len()-1];
-            if inner.is_empty() {
-                return None;
-            }
-            Some((inner.to_string(), true))
-        } else {
-            // 引用符が閉じていない場合はそのまま部分一致として扱う
-            Some((value.to_string(), false))
-        }
-    }
-
-    /// フィールドマッチ: exact=true なら完全一致、false ならスマートケース部分一致
-    fn field_match(target: &str, key: &str, exact: bool) -> bool {
-        if exact {
-            // 完全一致
-            target == key
-        } else {
-            // スマートケース部分一致
-            Self::smart_case_match(target, key)
-        }
-    }
-
-    /// スマートケースマッチ: キーが全て小文字なら case insensitive、大文字を含むなら case sensitive
-    fn smart_case_match(target: &str, key: &str) -> bool {
-        let has_uppercase = key.chars().any(|c| c.is_uppercase());
-        if has_uppercase {
-            target.contains(key)
-        } else {
-            target.to_lowercase().contains(&key.to_lowercase())
-        }
+    /// インデックスを使わない全件走査（テストとベンチマークの比較用）
+    #[cfg(test)]
+    pub fn search_linear(&mut self, query: &Query) -> Vec<usize> {
+        self.ensure_search_keys();
+        (0..self.tracks.len()).filter(|&i| query.matches(&self.tracks[i])).collect()
     }
 
-    /// アルバム名でトラックを取得（トラック番号順）
-    pub fn get_tracks_by_album(&self, album_name: &str) -> Vec<&CachedTrack> {
+    /// アルバム名とアルバムアーティストでトラックを取得（トラック番号順）
+    pub fn get_tracks_by_album(&self, album_name: &str, album_artist: &str) -> Vec<&CachedTrack> {
         let mut tracks: Vec<_> = self.tracks
             .iter()
-            .filter(|t| t.album == album_name)
+            .filter(|t| t.album == album_name && t.album_artist_or_artist() == album_artist)
             .collect();
         // ディスク番号 → トラック番号でソート
         tracks.sort_by(|a, b| {
@@ -389,21 +780,19 @@ impl TrackCache {
     }
 
     /// 最近追加された曲からユニークなアルバムを取得（追加日順）
+    /// (アルバム名, アルバムアーティスト) の組で区別する
     pub fn get_recent_albums(&self, limit: usize) -> Vec<(String, String)> {
         // 追加日でソート（降順 = 最新が先）
         let mut sorted_tracks: Vec<_> = self.tracks.iter().collect();
-        sorted_tracks.sort_by(|a, b| {
-            let date_a = parse_date_to_sortable(&a.date_added);
-            let date_b = parse_date_to_sortable(&b.date_added);
-            date_b.cmp(&date_a)
-        });
+        sorted_tracks.sort_by_key(|t| std::cmp::Reverse(t.date_added));
 
         let mut seen = std::collections::HashSet::new();
         sorted_tracks
             .iter()
             .filter_map(|t| {
-                if !t.album.is_empty() && seen.insert(t.album.clone()) {
-                    Some((t.album.clone(), t.artist.clone()))
+                let album = (t.album.clone(), t.album_artist_or_artist().to_string());
+                if !t.album.is_empty() && seen.insert(album.clone()) {
+                    Some(album)
                 } else {
                     None
                 }
@@ -413,6 +802,13 @@ impl TrackCache {
     }
 }
 
+/// search_fuzzy の1件
+pub struct FuzzyHit {
+    pub index: usize,  // tracks の添字
+    pub score: i32,
+    pub highlights: FieldHighlights,
+}
+
 fn is_leap_year(year: i32) -> bool {
     (year % 4 == 0 && year % 100 != 0) || (year % 400 == 0)
 }
@@ -426,44 +822,6 @@ fn days_in_month(year: i32, month: u32) -> u32 {
     }
 }
 
-/// AppleScript日付文字列 "Weekday, Month DD, YYYY at HH:MM:SS" をソート可能な形式に変換
-fn parse_date_to_sortable(date_str: &str) -> String {
-    if date_str.is_empty() {
-        return String::new();
-    }
-
-    // "Sunday, September 13, 2015 at 3:44:42" のような形式をパース
-    let parts: Vec<&str> = date_str.split(", ").collect();
-    if parts.len() < 2 {
-        return date_str.to_string();
-    }
-
-    // "September 13" と "2015 at 3:44:42" を取得
-    let month_day = parts.get(1).unwrap_or(&"");
-    let year_time = parts.get(2).unwrap_or(&"");
-
-    // 月と日を分離
-    let md_parts: Vec<&str> = month_day.split_whitespace().collect();
-    let month_name = md_parts.get(0).unwrap_or(&"");
-    let day: u32 = md_parts.get(1).unwrap_or(&"1").parse().unwrap_or(1);
-
-    // 年と時刻を分離
-    let yt_parts: Vec<&str> = year_time.split(" at ").collect();
-    let year: u32 = yt_parts.get(0).unwrap_or(&"1970").parse().unwrap_or(1970);
-    let time = yt_parts.get(1).unwrap_or(&"00:00:00");
-
-    // 月名を数字に変換
-    let month = match *month_name {
-        "January" => 1, "February" => 2, "March" => 3, "April" => 4,
-        "May" => 5, "June" => 6, "July" => 7, "August" => 8,
-        "September" => 9, "October" => 10, "November" => 11, "December" => 12,
-        _ => 1,
-    };
-
-    // YYYY-MM-DD HH:MM:SS 形式で返す
-    format!("{:04}-{:02}-{:02} {}", year, month, day, time)
-}
-
 /// プレイリストのトラック情報
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CachedPlaylistTrack {
@@ -474,6 +832,8 @@ pub struct CachedPlaylistTrack {
     pub time: String,
     pub played_count: u32,
     pub favorited: bool,
+    #[serde(default)]
+    pub persistent_id: String,
 }
 
 /// キャッシュされたプレイリスト
@@ -481,17 +841,37 @@ pub struct CachedPlaylistTrack {
 pub struct CachedPlaylist {
     pub name: String,
     pub tracks: Vec<CachedPlaylistTrack>,
+    #[serde(default)]
+    pub persistent_id: String,
+    /// 取得時のバックエンドの変更検出用の値（空なら未確認で、次の再検証で取り直す）
+    #[serde(default)]
+    pub fingerprint: String,
+}
+
+impl CachedPlaylist {
+    /// PlaylistCache のキー（persistent ID、古いキャッシュでは名前）
+    pub fn key(&self) -> &str {
+        if self.persistent_id.is_empty() { &self.name } else { &self.persistent_id }
+    }
 }
 
 /// プレイリストキャッシュ
+/// キーは persistent ID（ID のない古いエントリは名前）
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct PlaylistCache {
     pub playlists: std::collections::HashMap<String, CachedPlaylist>,
+    /// 一覧の順序とフォルダの親子関係（起動直後のツリー表示用）
+    #[serde(default)]
+    pub tree: Vec<PlaylistInfo>,
+    #[serde(skip)]
+    pub newer_schema: Option<NewerSchema>,  // 新しいバージョンのファイル（保存しない）
+    #[serde(skip)]
+    pub load_notice: Option<String>,  // 読み込み時の復元・破損の報告
 }
 
 impl PlaylistCache {
     fn cache_path() -> Option<PathBuf> {
-        dirs::cache_dir().map(|p| p.join("macos-music-tui").join("playlists.json"))
+        cache_dir().map(|p| p.join("playlists.json"))
     }
 
     pub fn load() -> Self {
@@ -499,38 +879,66 @@ impl PlaylistCache {
             return Self::default();
         };
 
-        if !path.exists() {
-            return Self::default();
-        }
-
-        match fs::read_to_string(&path) {
-            Ok(content) => {
-                serde_json::from_str(&content).unwrap_or_default()
-            }
-            Err(_) => Self::default(),
+        match load_versioned::<PlaylistCache>(&path, "playlists.json", PLAYLIST_CACHE_VERSION, PLAYLIST_CACHE_MIGRATIONS) {
+            Loaded::Ok(cache) => cache,
+            Loaded::Recovered(cache, notice) => Self { load_notice: Some(notice), ..cache },
+            Loaded::Newer(newer) => Self {
+                load_notice: Some(newer.to_string()),
+                newer_schema: Some(newer),
+                ..Self::default()
+            },
+            Loaded::Unreadable(notice) => Self { load_notice: Some(notice), ..Self::default() },
+            Loaded::Missing => Self::default(),
         }
     }
 
     pub fn save(&self) -> Result<()> {
+        if let Some(newer) = &self.newer_schema {
+            return Err(newer.clone().into());
+        }
         let Some(path) = Self::cache_path() else {
             anyhow::bail!("Could not determine cache directory");
         };
+        write_versioned(&path, self, PLAYLIST_CACHE_VERSION)
+    }
 
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
+    /// ID で引き、なければ名前で登録された古いエントリを探す
+    pub fn get(&self, playlist: PlaylistRef) -> Option<&CachedPlaylist> {
+        if !playlist.persistent_id.is_empty() {
+            if let Some(cached) = self.playlists.get(playlist.persistent_id) {
+                return Some(cached);
+            }
         }
-
-        let content = serde_json::to_string(self)?;
-        fs::write(&path, content)?;
-        Ok(())
+        self.playlists.get(playlist.name).filter(|p| p.persistent_id.is_empty())
     }
 
-    pub fn get(&self, playlist_name: &str) -> Option<&CachedPlaylist> {
-        self.playlists.get(playlist_name)
+    /// 変更検出用の値（未取得なら空）
+    pub fn fingerprint(&self, playlist: PlaylistRef) -> String {
+        self.get(playlist).map(|p| p.fingerprint.clone()).unwrap_or_default()
     }
 
+    /// 同じプレイリストの名前キーのエントリは置き換える
     pub fn insert(&mut self, playlist: CachedPlaylist) {
-        self.playlists.insert(playlist.name.clone(), playlist);
+        if !playlist.persistent_id.is_empty()
+            && self.playlists.get(&playlist.name).is_some_and(|p| p.persistent_id.is_empty())
+        {
+            self.playlists.remove(&playlist.name);
+        }
+        self.playlists.insert(playlist.key().to_string(), playlist);
+    }
+
+    /// 定期同期で取得した再生回数・お気に入りを各プレイリストの曲に反映（変更のあった曲数を返す）
+    pub fn apply_stats(&mut self, stats: &HashMap<String, TrackStats>) -> usize {
+        let mut changed = 0;
+        for track in self.playlists.values_mut().flat_map(|p| p.tracks.iter_mut()) {
+            let Some(s) = stats.get(&track.persistent_id) else { continue };
+            if track.played_count != s.played_count || track.favorited != s.favorited {
+                track.played_count = s.played_count;
+                track.favorited = s.favorited;
+                changed += 1;
+            }
+        }
+        changed
     }
 }
 
@@ -541,8 +949,44 @@ use crate::app::HighlightColor;
 pub struct Settings {
     #[serde(default)]
     pub highlight_color: HighlightColor,
+    /// 再生回数・お気に入りなどを同期する間隔（分、0 で無効）
+    #[serde(default = "default_stats_refresh_minutes")]
+    pub stats_refresh_minutes: u64,
+    /// プレイリストのキャッシュを再検証する間隔（分、0 で起動時のみ）
+    #[serde(default = "default_playlist_refresh_minutes")]
+    pub playlist_refresh_minutes: u64,
+    /// スマートプレイリストを再検証する間隔（分、0 で通常の間隔のみ）
+    #[serde(default = "default_smart_playlist_refresh_minutes")]
+    pub smart_playlist_refresh_minutes: u64,
+    /// 折りたたんだプレイリストフォルダの persistent ID
+    #[serde(default)]
+    pub collapsed_playlist_folders: BTreeSet<String>,
+    /// 検索をあいまい検索（スコア順）で行う
+    #[serde(default)]
+    pub fuzzy_search: bool,
+    /// 名前を付けて保存した検索（保存順）
+    #[serde(default)]
+    pub saved_searches: Vec<SavedSearch>,
+    /// 表示の種類（検索・アルバム・プレイリスト）ごとの並べ替え
+    #[serde(default)]
+    pub sort: ViewSorts,
+    /// 検索結果をアルバム・アーティストごとにまとめる
+    #[serde(default)]
+    pub search_grouping: SearchGrouping,
+    #[serde(skip)]
+    pub newer_schema: Option<NewerSchema>,  // 新しいバージョンのファイル（保存しない）
+    #[serde(skip)]
+    pub load_notice: Option<String>,  // 読み込み時の復元・破損の報告
+}
+
+/// 名前を付けて保存した検索クエリ（選ぶたびにキャッシュを検索し直す）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub name: String,
+    pub query: String,
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for HighlightColor {
     fn default() -> Self {
         HighlightColor::Cyan
@@ -553,13 +997,35 @@ impl Default for Settings {
     fn default() -> Self {
         Self {
             highlight_color: HighlightColor::Cyan,
+            stats_refresh_minutes: default_stats_refresh_minutes(),
+            playlist_refresh_minutes: default_playlist_refresh_minutes(),
+            smart_playlist_refresh_minutes: default_smart_playlist_refresh_minutes(),
+            collapsed_playlist_folders: BTreeSet::new(),
+            fuzzy_search: false,
+            saved_searches: Vec::new(),
+            sort: ViewSorts::default(),
+            search_grouping: SearchGrouping::Off,
+            newer_schema: None,
+            load_notice: None,
         }
     }
 }
 
+fn default_stats_refresh_minutes() -> u64 {
+    30
+}
+
+fn default_playlist_refresh_minutes() -> u64 {
+    10
+}
+
+fn default_smart_playlist_refresh_minutes() -> u64 {
+    2
+}
+
 impl Settings {
     fn settings_path() -> Option<PathBuf> {
-        dirs::cache_dir().map(|p| p.join("macos-music-tui").join("settings.json"))
+        cache_dir().map(|p| p.join("settings.json"))
     }
 
     pub fn load() -> Self {
@@ -568,26 +1034,308 @@ impl Settings {
             None => return Self::default(),
         };
 
-        if !path.exists() {
-            return Self::default();
-        }
-
-        match fs::read_to_string(&path) {
-            Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
-            Err(_) => Self::default(),
+        match load_versioned::<Settings>(&path, "settings.json", SETTINGS_VERSION, SETTINGS_MIGRATIONS) {
+            Loaded::Ok(settings) => settings,
+            Loaded::Recovered(settings, notice) => Self { load_notice: Some(notice), ..settings },
+            Loaded::Newer(newer) => Self {
+                load_notice: Some(newer.to_string()),
+                newer_schema: Some(newer),
+                ..Self::default()
+            },
+            Loaded::Unreadable(notice) => Self { load_notice: Some(notice), ..Self::default() },
+            Loaded::Missing => Self::default(),
         }
     }
 
     pub fn save(&self) -> Result<()> {
+        if let Some(newer) = &self.newer_schema {
+            return Err(newer.clone().into());
+        }
         let path = Self::settings_path()
             .ok_or_else(|| anyhow::anyhow!("Could not determine settings path"))?;
+        write_versioned(&path, self, SETTINGS_VERSION)
+    }
+}
 
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
+// 検索履歴
+
+/// 保存する検索クエリの数
+const SEARCH_HISTORY_LIMIT: usize = 200;
+
+/// 確定した検索クエリ（古い順、重複なし）
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct SearchHistory {
+    pub queries: Vec<String>,
+    #[serde(skip)]
+    pub newer_schema: Option<NewerSchema>,  // 新しいバージョンのファイル（保存しない）
+    #[serde(skip)]
+    pub load_notice: Option<String>,  // 読み込み時の復元・破損の報告
+}
+
+impl SearchHistory {
+    fn history_path() -> Option<PathBuf> {
+        cache_dir().map(|p| p.join("search_history.json"))
+    }
+
+    pub fn load() -> Self {
+        let Some(path) = Self::history_path() else {
+            return Self::default();
+        };
+
+        match load_versioned::<SearchHistory>(&path, "search_history.json", SEARCH_HISTORY_VERSION, SEARCH_HISTORY_MIGRATIONS) {
+            Loaded::Ok(history) => history,
+            Loaded::Recovered(history, notice) => Self { load_notice: Some(notice), ..history },
+            Loaded::Newer(newer) => Self {
+                load_notice: Some(newer.to_string()),
+                newer_schema: Some(newer),
+                ..Self::default()
+            },
+            Loaded::Unreadable(notice) => Self { load_notice: Some(notice), ..Self::default() },
+            Loaded::Missing => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(newer) = &self.newer_schema {
+            return Err(newer.clone().into());
         }
+        let path = Self::history_path()
+            .ok_or_else(|| anyhow::anyhow!("Could not determine search history path"))?;
+        write_versioned(&path, self, SEARCH_HISTORY_VERSION)
+    }
+
+    /// 末尾（最新）に追加する。同じクエリは前のものを消して1つにまとめる
+    /// 追加も並べ替えもなければ false
+    pub fn push(&mut self, query: &str) -> bool {
+        let query = query.trim();
+        if query.is_empty() || self.queries.last().is_some_and(|q| q == query) {
+            return false;
+        }
+        self.queries.retain(|q| q != query);
+        self.queries.push(query.to_string());
+        if self.queries.len() > SEARCH_HISTORY_LIMIT {
+            self.queries.drain(..self.queries.len() - SEARCH_HISTORY_LIMIT);
+        }
+        true
+    }
+
+    /// before より前（古い方）で pattern を含む最も新しいクエリの添字（Ctrl+R）
+    /// 検索ボックスと同じく正規化して大文字小文字を区別しない
+    pub fn find_before(&self, pattern: &str, before: usize) -> Option<usize> {
+        let pattern = normalize::fold_case(&normalize::normalize(pattern));
+        self.queries[..before.min(self.queries.len())]
+            .iter()
+            .rposition(|q| normalize::fold_case(&normalize::normalize(q)).contains(&pattern))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::music::PlaylistKind;
+
+    #[test]
+    fn am_pm_inside_words_is_ignored() {
+        // "Samstag" の "am" で 12 時を 0 時にしない
+        let noon = parse_legacy_date("Samstag, 13. September 2015 um 12:30:00");
+        assert_eq!(noon, Some(days_from_civil(2015, 9, 13) * 86400 + 12 * 3600 + 30 * 60));
+    }
+
+    #[test]
+    fn am_pm_tokens_are_applied() {
+        let day = days_from_civil(2015, 9, 13) * 86400;
+        assert_eq!(
+            parse_legacy_date("Sunday, September 13, 2015 at 3:44:42 PM"),
+            Some(day + 15 * 3600 + 44 * 60 + 42)
+        );
+        assert_eq!(parse_legacy_date("9/13/2015 12:05:00AM"), Some(day + 5 * 60));
+        assert_eq!(parse_legacy_date("2015年9月13日 午後3:44:42"), Some(day + 15 * 3600 + 44 * 60 + 42));
+    }
+
+    #[test]
+    fn month_names_match_whole_words() {
+        let local = |y, m, d| days_from_civil(y, m, d) * 86400 + 15 * 3600 + 44 * 60 + 42;
+        // 曜日の "martes" "mardi" を3月にしない
+        assert_eq!(parse_legacy_date("martes, 15 de septiembre de 2015, 15:44:42"), Some(local(2015, 9, 15)));
+        assert_eq!(parse_legacy_date("mardi 15 septembre 2015 à 15:44:42"), Some(local(2015, 9, 15)));
+        assert_eq!(parse_legacy_date("Dienstag, 10. März 2015 um 15:44:42"), Some(local(2015, 3, 10)));
+        assert_eq!(parse_legacy_date("Freitag, 1. Mai 2015 um 15:44:42"), Some(local(2015, 5, 1)));
+        assert_eq!(parse_legacy_date("13. Okt. 2015 15:44:42"), Some(local(2015, 10, 13)));
+        assert_eq!(parse_legacy_date("24. Dez. 2015 15:44:42"), Some(local(2015, 12, 24)));
+        assert_eq!(parse_legacy_date("13 sept. 2015 15:44:42"), Some(local(2015, 9, 13)));
+        assert_eq!(parse_legacy_date("Sonntag, 13. Blah 2015"), None);
+    }
+
+    #[test]
+    fn legacy_dates_are_migrated() {
+        let mut value = serde_json::json!({ "tracks": [
+            { "date_added": "Sunday, September 13, 2015 at 3:44:42 PM" },
+            { "date_added": "2015年9月13日 日曜日 15:44:42" },
+            { "date_added": "Sonntag, 13. September 2015 um 15:44:42" },
+            { "date_added": "dimanche 13 septembre 2015 à 15:44:42" },
+            { "date_added": "not a date" },
+        ]});
+        migrate_date_added_to_epoch(&mut value);
+
+        let expected = local_to_epoch(days_from_civil(2015, 9, 13) * 86400 + 15 * 3600 + 44 * 60 + 42) as u64;
+        let dates: Vec<u64> = value["tracks"].as_array().unwrap().iter().map(|t| t["date_added"].as_u64().unwrap()).collect();
+        assert_eq!(dates, vec![expected, expected, expected, expected, 0]);
+    }
+
+    /// テストごとの一時ディレクトリ（並列に走るので名前を分ける）
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("macos-music-tui-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn history(query: &str) -> SearchHistory {
+        SearchHistory { queries: vec![query.to_string()], ..SearchHistory::default() }
+    }
+
+    fn load_history(path: &Path) -> Loaded<SearchHistory> {
+        load_versioned(path, "search_history.json", SEARCH_HISTORY_VERSION, SEARCH_HISTORY_MIGRATIONS)
+    }
 
-        let content = serde_json::to_string(self)?;
-        fs::write(&path, content)?;
-        Ok(())
+    #[test]
+    fn save_keeps_previous_file_as_backup() {
+        let dir = test_dir("backup");
+        let path = dir.join("search_history.json");
+        write_versioned(&path, &history("first"), SEARCH_HISTORY_VERSION).unwrap();
+        write_versioned(&path, &history("second"), SEARCH_HISTORY_VERSION).unwrap();
+
+        let read = |p: &Path| fs::read_to_string(p).unwrap();
+        assert!(read(&path).contains("second"));
+        assert!(read(&with_suffix(&path, ".bak")).contains("first"));
+        assert!(!with_suffix(&path, ".tmp").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unversioned_files_are_migrated_to_current() {
+        let dir = test_dir("migrate");
+        let tracks_path = dir.join("tracks.json");
+        let tracks = serde_json::json!({
+            "total_tracks": 1,
+            "loaded_tracks": 1,
+            "last_updated": null,
+            "tracks": [{ "name": "Love Song", "artist": "James Blake", "album": "Overgrown",
+                         "date_added": "Sunday, September 13, 2015 at 3:44:42 PM" }],
+        });
+        fs::write(&tracks_path, tracks.to_string()).unwrap();
+        let Loaded::Ok(cache) = load_versioned::<TrackCache>(&tracks_path, "tracks.json", TRACK_CACHE_VERSION, TRACK_CACHE_MIGRATIONS) else {
+            panic!("tracks.json v0 was not loaded");
+        };
+        let expected = local_to_epoch(days_from_civil(2015, 9, 13) * 86400 + 15 * 3600 + 44 * 60 + 42) as u64;
+        assert_eq!(cache.tracks[0].date_added, expected);
+        assert_eq!(cache.tracks[0].name, "Love Song");
+
+        // 変換後の内容で書き換え、元のファイルは .bak に残る
+        let saved: serde_json::Value = serde_json::from_str(&fs::read_to_string(&tracks_path).unwrap()).unwrap();
+        assert_eq!(saved["schema_version"], TRACK_CACHE_VERSION);
+        assert_eq!(saved["tracks"][0]["date_added"], expected);
+        assert!(fs::read_to_string(with_suffix(&tracks_path, ".bak")).unwrap().contains("September"));
+
+        let playlists_path = dir.join("playlists.json");
+        let playlists = serde_json::json!({
+            "playlists": {},
+            "tree": [
+                { "name": "Folder", "persistent_id": "F", "is_folder": true },
+                { "name": "Mix", "persistent_id": "M", "parent_id": "F", "is_folder": false },
+            ],
+        });
+        fs::write(&playlists_path, playlists.to_string()).unwrap();
+        let Loaded::Ok(cache) =
+            load_versioned::<PlaylistCache>(&playlists_path, "playlists.json", PLAYLIST_CACHE_VERSION, PLAYLIST_CACHE_MIGRATIONS)
+        else {
+            panic!("playlists.json v0 was not loaded");
+        };
+        let kinds: Vec<_> = cache.tree.iter().map(|p| p.kind).collect();
+        assert_eq!(kinds, vec![PlaylistKind::Folder, PlaylistKind::User]);
+        assert_eq!(cache.tree[1].parent_id, "F");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn newer_schema_is_refused_and_left_alone() {
+        let dir = test_dir("newer");
+        let path = dir.join("search_history.json");
+        let content = r#"{"schema_version":99,"queries":["from the future"],"new_field":true}"#;
+        fs::write(&path, content).unwrap();
+
+        let Loaded::Newer(newer) = load_history(&path) else {
+            panic!("a newer schema was loaded");
+        };
+        assert_eq!((newer.found, newer.supported), (99, SEARCH_HISTORY_VERSION));
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+        assert!(!with_suffix(&path, ".bak").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn corrupt_or_truncated_file_is_recovered_from_backup() {
+        let dir = test_dir("recover");
+        let path = dir.join("search_history.json");
+        write_versioned(&path, &history("first"), SEARCH_HISTORY_VERSION).unwrap();
+        write_versioned(&path, &history("second"), SEARCH_HISTORY_VERSION).unwrap();
+
+        // 書き込み途中で切れたファイル
+        let content = fs::read_to_string(&path).unwrap();
+        fs::write(&path, &content[..content.len() / 2]).unwrap();
+        let Loaded::Recovered(recovered, notice) = load_history(&path) else {
+            panic!("truncated file was not recovered");
+        };
+        assert_eq!(recovered.queries, vec!["first"]);
+        assert!(notice.contains("restored the last backup"));
+        // 本体もバックアップの内容に戻す
+        assert_eq!(fs::read_to_string(&path).unwrap(), fs::read_to_string(with_suffix(&path, ".bak")).unwrap());
+
+        // JSON ですらないファイル
+        fs::write(&path, "\0\0garbage").unwrap();
+        assert!(matches!(load_history(&path), Loaded::Recovered(h, _) if h.queries == ["first"]));
+
+        // バックアップもなければ読めないと報告する
+        fs::remove_file(with_suffix(&path, ".bak")).unwrap();
+        fs::write(&path, "{").unwrap();
+        assert!(matches!(load_history(&path), Loaded::Unreadable(_)));
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(load_history(&path), Loaded::Missing));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn stats_are_written_into_cached_playlists() {
+        let track = |id: &str| CachedPlaylistTrack {
+            name: id.to_string(),
+            artist: String::new(),
+            album: String::new(),
+            year: 0,
+            time: String::new(),
+            played_count: 1,
+            favorited: false,
+            persistent_id: id.to_string(),
+        };
+        let mut cache = PlaylistCache::default();
+        for id in ["P1", "P2"] {
+            cache.insert(CachedPlaylist {
+                name: id.to_string(),
+                tracks: vec![track("A"), track("B")],
+                persistent_id: id.to_string(),
+                fingerprint: "fp".to_string(),
+            });
+        }
+        let stats = HashMap::from([(
+            "A".to_string(),
+            TrackStats { persistent_id: "A".to_string(), played_count: 5, favorited: true, rating: 0, played_date: 0 },
+        )]);
+
+        assert_eq!(cache.apply_stats(&stats), 2);
+        for playlist in cache.playlists.values() {
+            assert_eq!((playlist.tracks[0].played_count, playlist.tracks[0].favorited), (5, true));
+            assert_eq!((playlist.tracks[1].played_count, playlist.tracks[1].favorited), (1, false));
+            assert_eq!(playlist.fingerprint, "fp");
+        }
+        assert_eq!(cache.apply_stats(&stats), 0);
     }
 }
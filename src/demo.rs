@@ -0,0 +1,737 @@
+// Offline demo mode: a simulated player driven by a JSON fixture library
+// Used for screenshots, onboarding and reproducing UI bugs without Music.app
+
+use anyhow::{Context, Result};
+use rand::Rng;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::backend::{PlayerBackend, PlaylistRef, TrackRef};
+use crate::cache::{CachedPlaylist, CachedPlaylistTrack, CachedTrack, PlaylistCache, TrackCache};
+use crate::music::{ListItem, PlayerState, PlaylistFingerprint, PlaylistInfo, SimpleTrack, TrackBatch, TrackInfo, TrackStats};
+
+/// デモ用フィクスチャ
+/// tracks.json (TrackCache) と playlists.json (PlaylistCache) のフィールドをそのまま使う
+/// tracks.json をそのまま渡すこともできる
+#[derive(Debug, Default, Deserialize)]
+pub struct DemoLibrary {
+    #[serde(default)]
+    pub tracks: Vec<CachedTrack>,
+    #[serde(default)]
+    pub playlists: HashMap<String, CachedPlaylist>,
+}
+
+impl DemoLibrary {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        let mut library: DemoLibrary = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid demo library {}", path.display()))?;
+        library.assign_ids();
+        Ok(library)
+    }
+
+    /// persistent ID のないフィクスチャに ID を振る
+    /// プレイリストの曲はライブラリの同じ曲（name + album）の ID を使い、キーは ID に揃える
+    fn assign_ids(&mut self) {
+        for (i, track) in self.tracks.iter_mut().enumerate() {
+            if track.persistent_id.is_empty() {
+                track.persistent_id = format!("DE40000000{:06X}", i);
+            }
+        }
+        let mut names: Vec<String> = self.playlists.keys().cloned().collect();
+        names.sort();
+        let mut playlists = HashMap::new();
+        for (i, name) in names.into_iter().enumerate() {
+            let Some(mut playlist) = self.playlists.remove(&name) else { continue };
+            if playlist.persistent_id.is_empty() {
+                playlist.persistent_id = format!("DE41000000{:06X}", i);
+            }
+            for track in playlist.tracks.iter_mut().filter(|t| t.persistent_id.is_empty()) {
+                if let Some(found) = self.tracks.iter().find(|t| t.name == track.name && t.album == track.album) {
+                    track.persistent_id = found.persistent_id.clone();
+                }
+            }
+            playlists.insert(playlist.persistent_id.clone(), playlist);
+        }
+        self.playlists = playlists;
+    }
+
+    /// フィクスチャから完成済みのキャッシュを作る（起動直後から全曲を表示するため）
+    pub fn to_caches(&self) -> (TrackCache, PlaylistCache) {
+        let mut track_cache = TrackCache::default();
+        track_cache.total_tracks = self.tracks.len();
+        track_cache.add_tracks(self.tracks.clone());
+        track_cache.update_timestamp();
+
+        let mut playlists = self.playlists.clone();
+        for playlist in playlists.values_mut() {
+            playlist.fingerprint = playlist_fingerprint(playlist);
+        }
+        let playlist_cache = PlaylistCache {
+            playlists,
+            ..PlaylistCache::default()
+        };
+        (track_cache, playlist_cache)
+    }
+}
+
+/// 再生キューの1曲
+#[derive(Debug, Clone)]
+struct QueueEntry {
+    name: String,
+    artist: String,
+    album: String,
+    duration: f64,
+}
+
+#[derive(Debug)]
+struct PlayerSim {
+    queue: Vec<QueueEntry>,
+    current: Option<usize>,
+    // 一時停止中の再生位置（再生中は resumed_at からの経過時間を加算）
+    position: f64,
+    resumed_at: Option<Instant>,
+    volume: i32,
+    shuffle: bool,
+    repeat: String,
+}
+
+impl PlayerSim {
+    fn current_position(&self) -> f64 {
+        self.position + self.resumed_at.map(|t| t.elapsed().as_secs_f64()).unwrap_or(0.0)
+    }
+
+    fn is_playing(&self) -> bool {
+        self.resumed_at.is_some()
+    }
+
+    fn start(&mut self, index: usize) {
+        self.current = Some(index);
+        self.position = 0.0;
+        self.resumed_at = Some(Instant::now());
+    }
+
+    fn stop(&mut self) {
+        self.current = None;
+        self.position = 0.0;
+        self.resumed_at = None;
+    }
+
+    fn set_position(&mut self, position: f64) {
+        self.position = position.max(0.0);
+        if self.resumed_at.is_some() {
+            self.resumed_at = Some(Instant::now());
+        }
+    }
+
+    /// 次の曲のインデックス（シャッフル・リピートを考慮）
+    fn next_index(&self, current: usize) -> Option<usize> {
+        let len = self.queue.len();
+        if len == 0 {
+            return None;
+        }
+        if self.shuffle && len > 1 {
+            let mut rng = rand::thread_rng();
+            loop {
+                let i = rng.gen_range(0..len);
+                if i != current {
+                    return Some(i);
+                }
+            }
+        }
+        if current + 1 < len {
+            Some(current + 1)
+        } else if self.repeat == "all" {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    /// 経過時間を反映し、曲の終わりに達していれば次の曲へ進める
+    fn tick(&mut self) {
+        while let Some(current) = self.current {
+            if !self.is_playing() {
+                return;
+            }
+            let duration = self.queue[current].duration;
+            let elapsed = self.current_position();
+            if elapsed < duration {
+                return;
+            }
+
+            // 曲の終端を過ぎた分は次の曲に持ち越す
+            let overflow = elapsed - duration;
+            let next = if self.repeat == "one" {
+                Some(current)
+            } else {
+                self.next_index(current)
+            };
+            match next {
+                Some(i) => {
+                    self.current = Some(i);
+                    self.position = overflow;
+                    self.resumed_at = Some(Instant::now());
+                }
+                None => self.stop(),
+            }
+        }
+    }
+
+    fn play_queue(&mut self, queue: Vec<QueueEntry>, start: usize) -> Result<()> {
+        if queue.is_empty() {
+            anyhow::bail!("Nothing to play");
+        }
+        let start = start.min(queue.len() - 1);
+        self.queue = queue;
+        self.start(start);
+        Ok(())
+    }
+}
+
+/// フィクスチャライブラリ上で再生をシミュレートするバックエンド
+pub struct DemoBackend {
+    tracks: Vec<CachedTrack>,
+    playlists: Mutex<HashMap<String, CachedPlaylist>>,
+    player: Mutex<PlayerSim>,
+}
+
+impl DemoBackend {
+    pub fn new(library: DemoLibrary) -> Self {
+        Self {
+            tracks: library.tracks,
+            playlists: Mutex::new(library.playlists),
+            player: Mutex::new(PlayerSim {
+                queue: Vec::new(),
+                current: None,
+                position: 0.0,
+                resumed_at: None,
+                volume: 50,
+                shuffle: false,
+                repeat: "off".to_string(),
+            }),
+        }
+    }
+
+    fn player(&self) -> std::sync::MutexGuard<'_, PlayerSim> {
+        let mut player = self.player.lock().unwrap_or_else(|e| e.into_inner());
+        player.tick();
+        player
+    }
+
+    fn entry_from_track(track: &CachedTrack) -> QueueEntry {
+        QueueEntry {
+            name: track.name.clone(),
+            artist: track.artist.clone(),
+            album: track.album.clone(),
+            duration: parse_duration(&track.time),
+        }
+    }
+
+    fn to_simple_track(track: &CachedTrack) -> SimpleTrack {
+        SimpleTrack {
+            name: track.name.clone(),
+            artist: track.artist.clone(),
+            album: track.album.clone(),
+            album_artist: track.album_artist.clone(),
+            date_added: track.date_added,
+            year: track.year,
+            track_number: track.track_number,
+            disc_number: track.disc_number,
+            time: track.time.clone(),
+            played_count: track.played_count,
+            favorited: track.favorited,
+            rating: track.rating,
+            played_date: track.played_date,
+            persistent_id: track.persistent_id.clone(),
+        }
+    }
+
+    /// ID で探し、ID がなければ name + album で探す
+    fn find_track(&self, track: TrackRef) -> Result<&CachedTrack> {
+        self.tracks
+            .iter()
+            .find(|t| {
+                if track.persistent_id.is_empty() {
+                    t.name == track.name && t.album == track.album
+                } else {
+                    t.persistent_id == track.persistent_id
+                }
+            })
+            .ok_or_else(|| anyhow::anyhow!("Track not found: {}", track.name))
+    }
+
+    /// プレイリストのキー（ID、なければ名前で探す）
+    fn playlist_key(playlists: &HashMap<String, CachedPlaylist>, playlist: PlaylistRef) -> Result<String> {
+        if playlists.contains_key(playlist.persistent_id) {
+            return Ok(playlist.persistent_id.to_string());
+        }
+        playlists
+            .iter()
+            .find(|(_, p)| playlist.persistent_id.is_empty() && p.name == playlist.name)
+            .map(|(key, _)| key.clone())
+            .ok_or_else(|| anyhow::anyhow!("Playlist not found: {}", playlist.name))
+    }
+}
+
+impl PlayerBackend for DemoBackend {
+    fn play_pause(&self) -> Result<()> {
+        let mut player = self.player();
+        if player.current.is_none() {
+            // 何も再生していなければライブラリの先頭から再生
+            let queue: Vec<QueueEntry> = self.tracks.iter().map(Self::entry_from_track).collect();
+            return player.play_queue(queue, 0);
+        }
+        if player.is_playing() {
+            player.position = player.current_position();
+            player.resumed_at = None;
+        } else {
+            player.resumed_at = Some(Instant::now());
+        }
+        Ok(())
+    }
+
+    fn next_track(&self) -> Result<()> {
+        let mut player = self.player();
+        if let Some(current) = player.current {
+            match player.next_index(current) {
+                Some(i) => player.start(i),
+                None => player.stop(),
+            }
+        }
+        Ok(())
+    }
+
+    fn previous_track(&self) -> Result<()> {
+        let mut player = self.player();
+        if let Some(current) = player.current {
+            // 3秒以上再生していれば曲の先頭へ、そうでなければ前の曲へ
+            if player.current_position() > 3.0 || current == 0 {
+                player.set_position(0.0);
+            } else {
+                player.start(current - 1);
+            }
+        }
+        Ok(())
+    }
+
+    fn toggle_shuffle(&self) -> Result<bool> {
+        let mut player = self.player();
+        player.shuffle = !player.shuffle;
+        Ok(player.shuffle)
+    }
+
+    fn cycle_repeat(&self) -> Result<String> {
+        let mut player = self.player();
+        player.repeat = match player.repeat.as_str() {
+            "off" => "all",
+            "all" => "one",
+            _ => "off",
+        }
+        .to_string();
+        Ok(player.repeat.clone())
+    }
+
+    fn seek_backward(&self) -> Result<()> {
+        let mut player = self.player();
+        let position = player.current_position() - 10.0;
+        player.set_position(position);
+        Ok(())
+    }
+
+    fn seek_forward(&self) -> Result<()> {
+        let mut player = self.player();
+        let position = player.current_position() + 10.0;
+        player.set_position(position);
+        player.tick();
+        Ok(())
+    }
+
+    fn get_position(&self) -> Result<(f64, bool)> {
+        let player = self.player();
+        if player.current.is_none() {
+            return Ok((0.0, false));
+        }
+        Ok((player.current_position(), player.is_playing()))
+    }
+
+    fn get_all_state(&self) -> Result<PlayerState> {
+        let player = self.player();
+        let track = match player.current {
+            Some(i) => {
+                let entry = &player.queue[i];
+                TrackInfo {
+                    name: entry.name.clone(),
+                    artist: entry.artist.clone(),
+                    album: entry.album.clone(),
+                    duration: entry.duration,
+                    position: player.current_position(),
+                    is_playing: player.is_playing(),
+                }
+            }
+            None => TrackInfo::default(),
+        };
+        Ok(PlayerState {
+            track,
+            volume: player.volume,
+            shuffle: player.shuffle,
+            repeat: player.repeat.clone(),
+        })
+    }
+
+    fn get_total_track_count(&self) -> Result<usize> {
+        Ok(self.tracks.len())
+    }
+
+    fn get_tracks_batch(&self, start: usize, count: usize) -> Result<TrackBatch> {
+        let from = start.saturating_sub(1).min(self.tracks.len());
+        let to = (from + count).min(self.tracks.len());
+        Ok(TrackBatch {
+            tracks: self.tracks[from..to].iter().map(Self::to_simple_track).collect(),
+            malformed: Vec::new(),
+        })
+    }
+
+    fn get_all_tracks(&self) -> Result<TrackBatch> {
+        Ok(TrackBatch {
+            tracks: self.tracks.iter().map(Self::to_simple_track).collect(),
+            malformed: Vec::new(),
+        })
+    }
+
+    fn get_tracks_added_since(&self, _unix_timestamp: u64) -> Result<TrackBatch> {
+        // フィクスチャは起動中に変化しない
+        Ok(TrackBatch::default())
+    }
+
+    fn get_all_persistent_ids(&self) -> Result<Vec<String>> {
+        Ok(self.tracks.iter().map(|t| t.persistent_id.clone()).collect())
+    }
+
+    fn get_track_stats(&self) -> Result<Vec<TrackStats>> {
+        Ok(self
+            .tracks
+            .iter()
+            .map(|t| TrackStats {
+                persistent_id: t.persistent_id.clone(),
+                played_count: t.played_count,
+                favorited: t.favorited,
+                rating: t.rating,
+                played_date: t.played_date,
+            })
+            .collect())
+    }
+
+    fn get_playlists(&self) -> Result<Vec<PlaylistInfo>> {
+        let playlists = self.playlists.lock().unwrap_or_else(|e| e.into_inner());
+        let mut sorted: Vec<&CachedPlaylist> = playlists.values().collect();
+        sorted.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(sorted
+            .into_iter()
+            .map(|playlist| PlaylistInfo::top_level(playlist.name.clone(), playlist.persistent_id.clone()))
+            .collect())
+    }
+
+    fn get_playlist_fingerprints(&self, ids: Option<&[String]>) -> Result<Vec<PlaylistFingerprint>> {
+        let playlists = self.playlists.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(playlists
+            .values()
+            .filter(|playlist| ids.is_none_or(|ids| ids.contains(&playlist.persistent_id)))
+            .map(|playlist| PlaylistFingerprint {
+                persistent_id: playlist.persistent_id.clone(),
+                name: playlist.name.clone(),
+                fingerprint: playlist_fingerprint(playlist),
+            })
+            .collect())
+    }
+
+    fn get_playlist_tracks(&self, playlist: PlaylistRef) -> Result<Vec<ListItem>> {
+        let playlists = self.playlists.lock().unwrap_or_else(|e| e.into_inner());
+        let playlist = &playlists[&Self::playlist_key(&playlists, playlist)?];
+        Ok(playlist
+            .tracks
+            .iter()
+            .map(|t| ListItem {
+                name: t.name.clone(),
+                artist: t.artist.clone(),
+                album: t.album.clone(),
+                time: t.time.clone(),
+                year: t.year,
+                track_number: 0,
+                played_count: t.played_count,
+                favorited: t.favorited,
+                persistent_id: t.persistent_id.clone(),
+            })
+            .collect())
+    }
+
+    fn play_track(&self, track: TrackRef) -> Result<()> {
+        let track = self.find_track(track)?;
+        self.player().play_queue(vec![Self::entry_from_track(track)], 0)
+    }
+
+    fn play_playlist_with_context(&self, playlist: PlaylistRef, track_index: usize) -> Result<()> {
+        let queue: Vec<QueueEntry> = {
+            let playlists = self.playlists.lock().unwrap_or_else(|e| e.into_inner());
+            let playlist = &playlists[&Self::playlist_key(&playlists, playlist)?];
+            playlist
+                .tracks
+                .iter()
+                .map(|t| QueueEntry {
+                    name: t.name.clone(),
+                    artist: t.artist.clone(),
+                    album: t.album.clone(),
+                    duration: parse_duration(&t.time),
+                })
+                .collect()
+        };
+        self.player().play_queue(queue, track_index)
+    }
+
+    fn play_album_with_context(&self, album_name: &str, album_artist: &str, track_index: usize) -> Result<()> {
+        let mut album: Vec<&CachedTrack> = self
+            .tracks
+            .iter()
+            .filter(|t| t.album == album_name && t.album_artist_or_artist() == album_artist)
+            .collect();
+        album.sort_by(|a, b| {
+            a.disc_number.cmp(&b.disc_number)
+                .then(a.track_number.cmp(&b.track_number))
+        });
+        let queue = album.into_iter().map(Self::entry_from_track).collect();
+        self.player().play_queue(queue, track_index)
+    }
+
+    fn play_tracks_with_context(&self, tracks: &[TrackRef], track_index: usize) -> Result<()> {
+        let queue = tracks
+            .iter()
+            .map(|&t| self.find_track(t).map(Self::entry_from_track))
+            .collect::<Result<Vec<_>>>()?;
+        self.player().play_queue(queue, track_index)
+    }
+
+    fn add_track_to_playlist(&self, track: TrackRef, playlist: PlaylistRef) -> Result<()> {
+        let track = self.find_track(track)?;
+        let mut playlists = self.playlists.lock().unwrap_or_else(|e| e.into_inner());
+        let key = Self::playlist_key(&playlists, playlist)?;
+        if let Some(playlist) = playlists.get_mut(&key) {
+            playlist.tracks.push(to_playlist_track(track));
+        }
+        Ok(())
+    }
+
+    fn create_playlist_and_add_track(&self, playlist_name: &str, track: TrackRef) -> Result<String> {
+        let track = self.find_track(track)?;
+        let mut playlists = self.playlists.lock().unwrap_or_else(|e| e.into_inner());
+        let persistent_id = format!("DE42{:012X}", rand::thread_rng().gen::<u64>() & 0xFFFF_FFFF_FFFF);
+        playlists.insert(
+            persistent_id.clone(),
+            CachedPlaylist {
+                name: playlist_name.to_string(),
+                tracks: vec![to_playlist_track(track)],
+                persistent_id: persistent_id.clone(),
+                fingerprint: String::new(),
+            },
+        );
+        Ok(persistent_id)
+    }
+}
+
+/// デモのプレイリストは曲の追加でしか変わらないので曲数で足りる
+fn playlist_fingerprint(playlist: &CachedPlaylist) -> String {
+    playlist.tracks.len().to_string()
+}
+
+fn to_playlist_track(track: &CachedTrack) -> CachedPlaylistTrack {
+    CachedPlaylistTrack {
+        name: track.name.clone(),
+        artist: track.artist.clone(),
+        album: track.album.clone(),
+        year: track.year,
+        time: track.time.clone(),
+        played_count: track.played_count,
+        favorited: track.favorited,
+        persistent_id: track.persistent_id.clone(),
+    }
+}
+
+/// "3:08" / "1:02:03" 形式を秒数に変換（不明な場合は3分とみなす）
+fn parse_duration(time: &str) -> f64 {
+    let seconds = time
+        .split(':')
+        .try_fold(0u32, |acc, part| {
+            // 桁あふれする値は不正な時間として扱う
+            acc.checked_mul(60)?.checked_add(part.trim().parse::<u32>().ok()?)
+        });
+    match seconds {
+        Some(s) if s > 0 => s as f64,
+        _ => 180.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// (名前, アルバム, アルバムアーティスト, ディスク, トラック, 時間)
+    fn with_tracks(tracks: &[(&str, &str, &str, u32, u32, &str)]) -> DemoBackend {
+        let tracks = tracks
+            .iter()
+            .enumerate()
+            .map(|(i, &(name, album, album_artist, disc, number, time))| {
+                CachedTrack::new(
+                    name.into(),
+                    format!("Artist {}", i),
+                    album.into(),
+                    album_artist.into(),
+                    0,
+                    0,
+                    number,
+                    disc,
+                    time.into(),
+                    0,
+                    false,
+                    0,
+                    0,
+                    format!("ID{}", i),
+                )
+            })
+            .collect();
+        let mut library = DemoLibrary { tracks, playlists: HashMap::new() };
+        library.assign_ids();
+        DemoBackend::new(library)
+    }
+
+    fn album() -> DemoBackend {
+        with_tracks(&[("One", "A", "X", 1, 1, "0:10"), ("Two", "A", "X", 1, 2, "0:20"), ("Three", "A", "X", 1, 3, "0:30")])
+    }
+
+    /// 今の曲を secs 秒前に先頭から再生し始めたことにする
+    fn played_for(backend: &DemoBackend, secs: u64) {
+        let mut player = backend.player.lock().unwrap();
+        player.position = 0.0;
+        player.resumed_at = Some(Instant::now() - Duration::from_secs(secs));
+    }
+
+    fn current(backend: &DemoBackend) -> Option<String> {
+        let player = backend.player();
+        player.current.map(|i| player.queue[i].name.clone())
+    }
+
+    #[test]
+    fn position_advances_in_real_time() {
+        let backend = album();
+        backend.play_album_with_context("A", "X", 0).unwrap();
+        played_for(&backend, 4);
+        let (position, playing) = backend.get_position().unwrap();
+        assert!(playing && (4.0..5.0).contains(&position), "{}", position);
+
+        // 一時停止中は進まない
+        backend.play_pause().unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        let (paused, playing) = backend.get_position().unwrap();
+        assert!(!playing && paused == backend.player().position);
+
+        // 曲の終わりを過ぎた分は次の曲に持ち越す
+        backend.play_pause().unwrap();
+        played_for(&backend, 12);
+        assert_eq!(current(&backend).as_deref(), Some("Two"));
+        assert!((2.0..3.0).contains(&backend.get_position().unwrap().0));
+
+        // 最後の曲が終われば止まる
+        played_for(&backend, 60);
+        assert_eq!(current(&backend), None);
+        assert_eq!(backend.get_position().unwrap(), (0.0, false));
+    }
+
+    #[test]
+    fn next_and_previous_at_the_ends_of_the_queue() {
+        let backend = album();
+        backend.play_album_with_context("A", "X", 0).unwrap();
+        // 先頭で前の曲に戻ると先頭から
+        played_for(&backend, 2);
+        backend.previous_track().unwrap();
+        assert_eq!(current(&backend).as_deref(), Some("One"));
+        assert!(backend.get_position().unwrap().0 < 1.0);
+
+        backend.next_track().unwrap();
+        assert_eq!(current(&backend).as_deref(), Some("Two"));
+        // 3秒以上再生していれば曲の先頭へ、そうでなければ前の曲へ
+        played_for(&backend, 5);
+        backend.previous_track().unwrap();
+        assert_eq!(current(&backend).as_deref(), Some("Two"));
+        backend.previous_track().unwrap();
+        assert_eq!(current(&backend).as_deref(), Some("One"));
+
+        // 最後の曲の次は止まる（リピートなし）
+        backend.play_album_with_context("A", "X", 2).unwrap();
+        backend.next_track().unwrap();
+        assert_eq!(current(&backend), None);
+        backend.next_track().unwrap();
+        backend.previous_track().unwrap();
+        assert_eq!(current(&backend), None);
+    }
+
+    #[test]
+    fn shuffle_picks_another_track() {
+        let backend = album();
+        assert!(backend.toggle_shuffle().unwrap());
+        backend.play_album_with_context("A", "X", 0).unwrap();
+        for _ in 0..20 {
+            let before = current(&backend);
+            backend.next_track().unwrap();
+            let after = current(&backend);
+            assert!(after.is_some() && after != before);
+        }
+        assert!(!backend.toggle_shuffle().unwrap());
+
+        // 1曲だけならシャッフルでも次はない
+        let single = with_tracks(&[("Solo", "S", "Y", 1, 1, "0:10")]);
+        single.toggle_shuffle().unwrap();
+        single.play_album_with_context("S", "Y", 0).unwrap();
+        single.next_track().unwrap();
+        assert_eq!(current(&single), None);
+    }
+
+    #[test]
+    fn repeat_one_and_all() {
+        let backend = album();
+        assert_eq!(backend.cycle_repeat().unwrap(), "all");
+        backend.play_album_with_context("A", "X", 2).unwrap();
+        backend.next_track().unwrap();
+        assert_eq!(current(&backend).as_deref(), Some("One"));
+        played_for(&backend, 11);
+        assert_eq!(current(&backend).as_deref(), Some("Two"));
+
+        // 1曲リピートは曲の終わりで同じ曲の先頭に戻る
+        assert_eq!(backend.cycle_repeat().unwrap(), "one");
+        played_for(&backend, 21);
+        assert_eq!(current(&backend).as_deref(), Some("Two"));
+        assert!(backend.get_position().unwrap().0 < 2.0);
+        assert_eq!(backend.cycle_repeat().unwrap(), "off");
+    }
+
+    #[test]
+    fn albums_are_told_apart_by_album_artist() {
+        let backend = with_tracks(&[
+            ("B2", "Greatest Hits", "B", 1, 2, "0:10"),
+            ("A1", "Greatest Hits", "A", 1, 1, "0:10"),
+            ("B1", "Greatest Hits", "B", 1, 1, "0:10"),
+            ("Other", "Other", "", 1, 1, "0:10"),
+        ]);
+        backend.play_album_with_context("Greatest Hits", "B", 0).unwrap();
+        let names: Vec<String> = backend.player().queue.iter().map(|e| e.name.clone()).collect();
+        assert_eq!(names, ["B1", "B2"]);
+
+        // アルバムアーティストが空なら曲のアーティストで引く
+        backend.play_album_with_context("Other", "Artist 3", 0).unwrap();
+        assert_eq!(current(&backend).as_deref(), Some("Other"));
+        assert!(backend.play_album_with_context("Greatest Hits", "C", 0).is_err());
+    }
+}
@@ -0,0 +1,145 @@
+// Errors reported by the Music.app backend
+// osascript の "execution error: ... (-1743)" 形式のエラーを種類ごとに分類する
+
+use std::fmt;
+
+/// Music.app の操作で起きるエラー
+/// anyhow::Error に包んで返し、App 側で downcast_ref して種類ごとに対応する
+#[derive(Debug, Clone, PartialEq)]
+pub enum MusicError {
+    /// Music.app が起動していない（-600 / -609）
+    NotRunning,
+    /// オートメーションの許可がない（-1743 / -1744）
+    PermissionDenied,
+    /// 曲が見つからない
+    TrackNotFound(String),
+    /// プレイリストが見つからない（persistent ID は呼び出し側で付ける）
+    PlaylistNotFound { persistent_id: String, name: String },
+    /// 応答がない（セッションのタイムアウト / -1712）
+    Timeout,
+    /// 出力を解釈できない
+    Malformed(String),
+    /// その他のスクリプトエラー
+    Script { code: Option<i32>, message: String },
+}
+
+/// スクリプト内で `error "<名前>" number ...` として使う独自のエラー番号
+/// 見つからなかった曲・プレイリストの名前をメッセージとして渡す
+pub const ERR_TRACK_NOT_FOUND: i32 = 10001;
+pub const ERR_PLAYLIST_NOT_FOUND: i32 = 10002;
+
+impl MusicError {
+    /// osascript のエラー文字列から分類する
+    /// 例: "execution error: Not authorized to send Apple events to Music. (-1743)"
+    pub fn from_script_error(text: &str) -> Self {
+        let text = text.trim();
+        let (message, code) = match text
+            .strip_suffix(')')
+            .and_then(|t| t.rsplit_once(" ("))
+            .and_then(|(m, code)| code.parse::<i32>().ok().map(|c| (m, c)))
+        {
+            Some((m, c)) => (m, Some(c)),
+            None => (text, None),
+        };
+        let message = message.strip_prefix("execution error: ").unwrap_or(message);
+
+        match code {
+            Some(-600) | Some(-609) => MusicError::NotRunning,
+            Some(-1743) | Some(-1744) => MusicError::PermissionDenied,
+            Some(-1712) => MusicError::Timeout,
+            // "Can’t get playlist "名前"." など、参照先がない
+            Some(-1728) if message.contains("playlist") => MusicError::PlaylistNotFound {
+                persistent_id: String::new(),
+                name: quoted(message).unwrap_or(message).to_string(),
+            },
+            Some(-1728) => MusicError::TrackNotFound(quoted(message).unwrap_or(message).to_string()),
+            Some(ERR_TRACK_NOT_FOUND) => MusicError::TrackNotFound(message.to_string()),
+            Some(ERR_PLAYLIST_NOT_FOUND) => MusicError::PlaylistNotFound {
+                persistent_id: String::new(),
+                name: message.to_string(),
+            },
+            _ if message.contains("isn’t running") || message.contains("isn't running") => MusicError::NotRunning,
+            _ => MusicError::Script { code, message: message.to_string() },
+        }
+    }
+}
+
+/// メッセージ中の引用符で囲まれた部分（"..." または “...”）
+fn quoted(message: &str) -> Option<&str> {
+    let start = message.find(['"', '“'])?;
+    let rest = &message[start..];
+    let rest = &rest[rest.chars().next()?.len_utf8()..];
+    let end = rest.rfind(['"', '”'])?;
+    Some(&rest[..end])
+}
+
+/// プレイリストが見つからないエラーに persistent ID を付ける
+/// スクリプトのエラーには名前しか入らないため、どのプレイリストを探したかを呼び出し側で補う
+pub fn with_playlist_id(e: anyhow::Error, persistent_id: &str) -> anyhow::Error {
+    match e.downcast::<MusicError>() {
+        Ok(MusicError::PlaylistNotFound { name, .. }) => {
+            MusicError::PlaylistNotFound { persistent_id: persistent_id.to_string(), name }.into()
+        }
+        Ok(other) => other.into(),
+        Err(e) => e,
+    }
+}
+
+impl fmt::Display for MusicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MusicError::NotRunning => write!(f, "Music.app is not running"),
+            MusicError::PermissionDenied => write!(f, "Not allowed to control Music.app (automation permission denied)"),
+            MusicError::TrackNotFound(name) => write!(f, "Track not found: {}", name),
+            MusicError::PlaylistNotFound { name, .. } => write!(f, "Playlist not found: {}", name),
+            MusicError::Timeout => write!(f, "Music.app did not respond in time"),
+            MusicError::Malformed(detail) => write!(f, "Malformed output from Music.app: {}", detail),
+            MusicError::Script { code: Some(code), message } => write!(f, "AppleScript error: {} ({})", message, code),
+            MusicError::Script { code: None, message } => write!(f, "AppleScript error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for MusicError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_errors_are_classified() {
+        let playlist = |name: &str| MusicError::PlaylistNotFound { persistent_id: String::new(), name: name.to_string() };
+        let cases = [
+            ("execution error: Music got an error: Can’t get playlist \"Road Trip\". (-1728)", playlist("Road Trip")),
+            ("execution error: Error: Error: Can't get object. (-1728)", MusicError::TrackNotFound("Error: Error: Can't get object.".into())),
+            ("execution error: Music got an error: Can’t get current track. (-1728)", MusicError::TrackNotFound("Music got an error: Can’t get current track.".into())),
+            ("execution error: Not authorized to send Apple events to Music. (-1743)", MusicError::PermissionDenied),
+            ("execution error: Not authorized (-1744)", MusicError::PermissionDenied),
+            ("execution error: Music got an error: Application isn’t running. (-600)", MusicError::NotRunning),
+            ("execution error: Connection is invalid. (-609)", MusicError::NotRunning),
+            ("Music isn't running", MusicError::NotRunning),
+            ("execution error: Music got an error: AppleEvent timed out. (-1712)", MusicError::Timeout),
+            ("execution error: Love Song (10001)", MusicError::TrackNotFound("Love Song".into())),
+            ("execution error: Road Trip (10002)\n", playlist("Road Trip")),
+            (
+                "execution error: Music got an error: Some failure. (-2700)",
+                MusicError::Script { code: Some(-2700), message: "Music got an error: Some failure.".into() },
+            ),
+            ("something went wrong (not a code)", MusicError::Script { code: None, message: "something went wrong (not a code)".into() }),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(MusicError::from_script_error(text), expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn playlist_id_is_attached() {
+        let e = with_playlist_id(MusicError::from_script_error("execution error: Mix (10002)").into(), "ID1");
+        assert_eq!(
+            e.downcast_ref::<MusicError>(),
+            Some(&MusicError::PlaylistNotFound { persistent_id: "ID1".into(), name: "Mix".into() })
+        );
+        let e = with_playlist_id(MusicError::Timeout.into(), "ID1");
+        assert_eq!(e.downcast_ref::<MusicError>(), Some(&MusicError::Timeout));
+    }
+}
@@ -0,0 +1,222 @@
+// fzf-style fuzzy matching for the search box: subsequence matches scored by
+// consecutive runs, word boundaries and gaps, with the matched character positions
+
+use crate::normalize::{self, SearchKey};
+
+/// name, artist, album それぞれで一致した文字の位置（文字番号、昇順）
+pub type FieldHighlights = [Vec<usize>; 3];
+
+/// フィールドごとの重み（%）。同じ一致なら name > artist > album
+const FIELD_WEIGHTS: [i32; 3] = [100, 80, 60];
+
+const SCORE_MATCH: i32 = 16;
+const SCORE_GAP_START: i32 = -3;
+const SCORE_GAP_EXTENSION: i32 = -1;
+const BONUS_BOUNDARY: i32 = 8;
+const BONUS_CAMEL: i32 = 7;
+const BONUS_CONSECUTIVE: i32 = 4;
+const BONUS_FIRST_CHAR_MULTIPLIER: i32 = 2;
+
+#[derive(Debug, Clone)]
+pub struct FuzzyMatch {
+    pub score: i32,
+    pub positions: Vec<usize>,
+}
+
+/// 検索語1つ（文字はあらかじめそろえておく）
+pub struct Pattern {
+    chars: Vec<char>,
+    case_sensitive: bool,
+}
+
+impl Pattern {
+    /// 全て小文字なら大文字小文字を区別しない（スマートケース）
+    pub fn new(word: &str) -> Self {
+        let case_sensitive = word.chars().any(|c| c.is_uppercase());
+        let mut pattern = Self { chars: Vec::new(), case_sensitive };
+        pattern.chars = normalize::normalize(word).chars().map(|c| pattern.fold(c)).collect();
+        pattern
+    }
+
+    /// 大文字小文字をそろえる（文字の位置を保つため1文字ずつ）
+    fn fold(&self, c: char) -> char {
+        if self.case_sensitive {
+            c
+        } else if c.is_ascii() {
+            c.to_ascii_lowercase()
+        } else {
+            match c.to_lowercase().next().unwrap_or(c) {
+                'ς' => 'σ',
+                c => c,
+            }
+        }
+    }
+
+    /// 文字が text に順に現れるか調べてスコアを付ける
+    /// text は正規化済み（大文字小文字は残したもの）で、位置も text の文字番号
+    pub fn match_in(&self, text: &str) -> Option<FuzzyMatch> {
+        let pattern = &self.chars;
+        if pattern.is_empty() {
+            return Some(FuzzyMatch { score: 0, positions: Vec::new() });
+        }
+
+        // 前から最初に全文字がそろう位置を探す（ほとんどのフィールドはここで外れるので確保なしで調べる）
+        let mut p = 0;
+        let mut end = None;
+        for (i, c) in text.chars().enumerate() {
+            if self.fold(c) == pattern[p] {
+                p += 1;
+                if p == pattern.len() {
+                    end = Some(i);
+                    break;
+                }
+            }
+        }
+        let end = end?;
+        let original: Vec<char> = text.chars().take(end + 1).collect();
+        let text: Vec<char> = original.iter().map(|&c| self.fold(c)).collect();
+
+        // そこから後ろ向きにたどり、できるだけ短い範囲にする
+        let mut p = pattern.len();
+        let mut start = end;
+        for i in (0..=end).rev() {
+            if text[i] == pattern[p - 1] {
+                p -= 1;
+                if p == 0 {
+                    start = i;
+                    break;
+                }
+            }
+        }
+
+        // 範囲内で前から順に一致させ、スコアを計算する
+        let mut positions = Vec::with_capacity(pattern.len());
+        let mut score = 0;
+        let mut p = 0;
+        let mut in_gap = false;
+        let mut prev_bonus = 0;
+        for (i, &c) in text.iter().enumerate().skip(start) {
+            if p < pattern.len() && c == pattern[p] {
+                let mut bonus = boundary_bonus(&original, i);
+                if positions.last() == Some(&(i.wrapping_sub(1))) {
+                    // 連続一致は先頭の境界ボーナスを引き継ぐ
+                    bonus = bonus.max(prev_bonus).max(BONUS_CONSECUTIVE);
+                }
+                if p == 0 {
+                    bonus *= BONUS_FIRST_CHAR_MULTIPLIER;
+                }
+                score += SCORE_MATCH + bonus;
+                prev_bonus = bonus;
+                positions.push(i);
+                p += 1;
+                in_gap = false;
+            } else {
+                score += if in_gap { SCORE_GAP_EXTENSION } else { SCORE_GAP_START };
+                in_gap = true;
+            }
+        }
+
+        Some(FuzzyMatch { score, positions })
+    }
+}
+
+/// 単語の先頭（文頭、空白・記号の直後）や camelCase の境目に一致したときのボーナス
+fn boundary_bonus(text: &[char], i: usize) -> i32 {
+    let Some(&prev) = i.checked_sub(1).and_then(|j| text.get(j)) else {
+        return BONUS_BOUNDARY;
+    };
+    let current = text[i];
+    if !prev.is_alphanumeric() && current.is_alphanumeric() {
+        BONUS_BOUNDARY
+    } else if prev.is_lowercase() && current.is_uppercase() {
+        BONUS_CAMEL
+    } else {
+        0
+    }
+}
+
+/// 各語を name, artist, album のうち最も高いスコアのフィールドで一致させる
+/// 正規化したキーで一致させ、位置は元の文字列（fields）の文字番号に戻す
+/// どれかの語がどのフィールドにも一致しなければ None
+pub fn match_fields(words: &[Pattern], key: &SearchKey, fields: [&str; 3]) -> Option<(i32, FieldHighlights)> {
+    let mut total = 0;
+    let mut highlights: FieldHighlights = Default::default();
+    for word in words {
+        let (field, m) = (0..3)
+            .filter_map(|f| word.match_in(key.field(f, true)).map(|m| (f, m)))
+            .max_by_key(|(f, m)| (m.score * FIELD_WEIGHTS[*f] / 100, std::cmp::Reverse(*f)))?;
+        total += m.score * FIELD_WEIGHTS[field] / 100;
+        highlights[field].extend(m.positions);
+    }
+    for (positions, text) in highlights.iter_mut().zip(fields) {
+        if positions.is_empty() || text.is_ascii() {
+            continue;
+        }
+        let source = normalize::source_positions(text);
+        let last = text.chars().count().saturating_sub(1);
+        for p in positions.iter_mut() {
+            *p = source.get(*p).copied().unwrap_or(last);
+        }
+    }
+    for positions in &mut highlights {
+        positions.sort_unstable();
+        positions.dedup();
+    }
+    Some((total, highlights))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn highlights(word: &str, fields: [&str; 3]) -> Option<(i32, FieldHighlights)> {
+        match_fields(&[Pattern::new(word)], &SearchKey::new(fields), fields)
+    }
+
+    #[test]
+    fn fields_are_weighted_name_artist_album() {
+        let name = highlights("drive", ["Drive", "X", "Y"]).unwrap();
+        let artist = highlights("drive", ["X", "Drive", "Y"]).unwrap();
+        let album = highlights("drive", ["X", "Y", "Drive"]).unwrap();
+        assert!(name.0 > artist.0 && artist.0 > album.0);
+        assert_eq!(album.1, [vec![], vec![], vec![0, 1, 2, 3, 4]]);
+
+        // 同じ語が複数のフィールドにあれば name を選ぶ
+        let (_, both) = highlights("drive", ["Drive", "Drive", "Drive"]).unwrap();
+        assert_eq!(both, [vec![0, 1, 2, 3, 4], vec![], vec![]]);
+    }
+
+    #[test]
+    fn word_boundaries_and_runs_score_higher() {
+        let score = |text: &str| Pattern::new("song").match_in(text).unwrap().score;
+        assert!(score("Love Song") > score("Lovesong"));
+        assert!(score("LoveSong") > score("Lovesong"));
+        assert!(score("Song") > score("S o n g"));
+        assert!(Pattern::new("sx").match_in("Song").is_none());
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(Pattern::new("love").match_in("LOVE").is_some());
+        assert!(Pattern::new("Love").match_in("love").is_none());
+    }
+
+    #[test]
+    fn positions_map_back_to_the_raw_field() {
+        // 最初に全文字がそろう範囲を後ろから詰める
+        let (_, h) = highlights("ng", ["Night Song", "", ""]).unwrap();
+        assert_eq!(h[0], vec![0, 2]);
+
+        // 半角カナの濁点は2文字で1文字に正規化される
+        let (_, h) = highlights("ガール", ["ｶﾞｰﾙ", "", ""]).unwrap();
+        assert_eq!(h[0], vec![0, 2, 3]);
+        let (_, h) = highlights("がーる", ["ｶﾞｰﾙ", "", ""]).unwrap();
+        assert_eq!(h[0], vec![0, 2, 3]);
+
+        // 結合文字のアクセント記号は前の文字と一緒に数える
+        let (_, h) = highlights("beyonce", ["", "Beyonce\u{301} Live", ""]).unwrap();
+        assert_eq!(h[1], vec![0, 1, 2, 3, 4, 5, 6]);
+        let (_, h) = highlights("el", ["", "", "Café Live"]).unwrap();
+        assert_eq!(h[2], vec![3, 5]);
+    }
+}
@@ -0,0 +1,481 @@
+// Key bindings: one key event is dispatched to App actions depending on the current mode
+// Kept apart from the event loop so tests can drive App without a terminal
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::app::{App, Focus};
+
+/// キー入力を処理する（表示中の画面・モードの順に判定）
+pub fn handle_key(app: &mut App, key: KeyEvent) {
+    // ウェルカム画面表示中
+    if app.should_show_welcome() {
+        match key.code {
+            KeyCode::Char('c') => {
+                app.cycle_highlight_color();
+            }
+            _ => {
+                app.dismiss_welcome();
+            }
+        }
+        return;
+    }
+
+    // ヘルプ画面表示中
+    if app.show_help {
+        app.show_help = false;
+        return;
+    }
+
+    // 並べ替えメニュー表示中
+    if app.sort_menu.is_some() {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('o') | KeyCode::Char('s') => {
+                app.close_sort_menu();
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                app.sort_menu_up();
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                app.sort_menu_down();
+            }
+            KeyCode::Enter | KeyCode::Char('1') => {
+                app.sort_menu_primary();
+            }
+            KeyCode::Char('2') => {
+                app.sort_menu_secondary();
+            }
+            KeyCode::Char('x') | KeyCode::Backspace => {
+                app.sort_menu_remove();
+            }
+            KeyCode::Char('r') => {
+                app.sort_menu_reset();
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    if app.delete_saved_search_pending.is_some() {
+        // 保存した検索の削除の確認（y 以外は取り消す）
+        match key.code {
+            KeyCode::Char('y') => {
+                app.confirm_delete_saved_search();
+            }
+            _ => {
+                app.cancel_delete_saved_search();
+            }
+        }
+        return;
+    }
+
+    if !app.search_mode && !app.add_to_playlist_mode {
+        app.message = None;
+    }
+
+    if app.save_search_input_mode {
+        // 保存する検索の名前入力モード
+        match key.code {
+            KeyCode::Esc => {
+                app.cancel_save_search();
+            }
+            KeyCode::Enter => {
+                app.confirm_save_search();
+            }
+            KeyCode::Backspace => {
+                app.save_search_backspace();
+            }
+            KeyCode::Char(c) => {
+                app.save_search_input(c);
+            }
+            _ => {}
+        }
+    } else if app.new_playlist_input_mode {
+        // 新規プレイリスト名入力モード
+        match key.code {
+            KeyCode::Esc => {
+                app.cancel_add_to_playlist();
+            }
+            KeyCode::Enter => {
+                app.confirm_new_playlist();
+            }
+            KeyCode::Backspace => {
+                app.new_playlist_backspace();
+            }
+            KeyCode::Char(c) => {
+                app.new_playlist_input(c);
+            }
+            _ => {}
+        }
+    } else if app.add_to_playlist_mode {
+        // プレイリスト追加モード
+        match key.code {
+            KeyCode::Esc => {
+                app.cancel_add_to_playlist();
+            }
+            KeyCode::Enter => {
+                // フォルダには曲を追加できないので開閉する
+                let toggled = app.toggle_selected_folder();
+                if !toggled {
+                    app.confirm_add_to_playlist();
+                }
+            }
+            KeyCode::Char('h') => {
+                app.collapse_selected_folder();
+            }
+            KeyCode::Char('l') => {
+                app.expand_selected_folder();
+            }
+            // プレイリスト選択（+ New playlist を含む）
+            KeyCode::Up | KeyCode::Char('k') if app.playlists_selected > 0 => {
+                app.playlists_selected -= 1;
+                if app.playlists_selected < app.playlists_scroll {
+                    app.playlists_scroll = app.playlists_selected;
+                }
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                let max_index = app.playlists_count_with_new() - 1;
+                if app.playlists_selected < max_index {
+                    app.playlists_selected += 1;
+                    if app.playlists_selected >= app.playlists_scroll + app.playlists_visible {
+                        app.playlists_scroll = app.playlists_selected.saturating_sub(app.playlists_visible - 1);
+                    }
+                }
+            }
+            _ => {}
+        }
+    } else if app.search_mode {
+        // 検索モード中のフォーカスによって動作を分岐
+        if app.focus == Focus::Content {
+            // 検索結果にフォーカス中: j/k/h でナビゲーション
+            match key.code {
+                KeyCode::Esc => {
+                    app.cancel_search();
+                }
+                KeyCode::Enter => {
+                    app.play_selected();
+                }
+                KeyCode::Up | KeyCode::Char('k') => {
+                    app.content_up();
+                }
+                KeyCode::Down | KeyCode::Char('j') => {
+                    app.content_down();
+                }
+                KeyCode::Char('J') => {
+                    app.search_next_album();
+                }
+                KeyCode::Char('K') => {
+                    app.search_prev_album();
+                }
+                KeyCode::Char('g') => {
+                    app.content_top();
+                }
+                KeyCode::Char('G') => {
+                    app.content_bottom();
+                }
+                KeyCode::Char('h') | KeyCode::Char('/') => {
+                    // Searchカードに戻る
+                    app.focus = Focus::Search;
+                }
+                KeyCode::Char('l') => {
+                    // 選択中の曲のアルバム全曲を表示
+                    app.show_selected_search_album();
+                }
+                KeyCode::Char('v') => {
+                    app.cycle_search_grouping();
+                }
+                KeyCode::Char('z') => {
+                    app.toggle_search_group();
+                }
+                KeyCode::Char('Z') => {
+                    app.toggle_all_search_groups();
+                }
+                KeyCode::Char('a') => {
+                    app.start_add_to_playlist();
+                }
+                KeyCode::Char('s') | KeyCode::Char('o') => {
+                    app.open_sort_menu();
+                }
+                KeyCode::Char('S') => {
+                    app.start_save_search();
+                }
+                KeyCode::Char('c') => {
+                    app.cycle_highlight_color();
+                }
+                KeyCode::Char('?') => {
+                    app.show_help = true;
+                }
+                _ => {}
+            }
+        } else {
+            // Searchカードにフォーカス中: 文字入力
+            let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+            // 履歴の逆方向検索中は入力を検索語に回す。それ以外のキーは表示中の履歴で確定してから通常どおり処理
+            let mut handled = false;
+            if app.history_search.is_some() {
+                handled = true;
+                match key.code {
+                    KeyCode::Esc => {
+                        app.cancel_history_search();
+                    }
+                    KeyCode::Char('g') if ctrl => {
+                        app.cancel_history_search();
+                    }
+                    KeyCode::Char('r') if ctrl => {
+                        app.search_history_reverse();
+                    }
+                    KeyCode::Backspace => {
+                        app.history_search_backspace();
+                    }
+                    KeyCode::Char('h') if ctrl => {
+                        app.history_search_backspace();
+                    }
+                    KeyCode::Char(c) if !ctrl => {
+                        app.history_search_input(c);
+                    }
+                    _ => {
+                        app.accept_history_search();
+                        handled = false;
+                    }
+                }
+            }
+            if !handled {
+                match key.code {
+                    KeyCode::Esc => {
+                        app.cancel_search();
+                    }
+                    KeyCode::Enter => {
+                        app.confirm_search();
+                    }
+                    KeyCode::Tab => {
+                        app.toggle_fuzzy_search();
+                    }
+                    KeyCode::Backspace => {
+                        app.search_backspace();
+                    }
+                    KeyCode::Char('a') if ctrl => {
+                        app.search_cursor_start();
+                    }
+                    KeyCode::Char('e') if ctrl => {
+                        app.search_cursor_end();
+                    }
+                    KeyCode::Char('h') if ctrl => {
+                        app.search_backspace();
+                    }
+                    KeyCode::Char('k') if ctrl => {
+                        app.search_kill_line();
+                    }
+                    KeyCode::Char('f') if ctrl => {
+                        app.search_cursor_forward();
+                    }
+                    KeyCode::Char('b') if ctrl => {
+                        app.search_cursor_backward();
+                    }
+                    KeyCode::Char('r') if ctrl => {
+                        app.search_history_reverse();
+                    }
+                    KeyCode::Char('s') if ctrl => {
+                        app.start_save_search();
+                    }
+                    KeyCode::Char('p') if ctrl => {
+                        app.search_history_prev();
+                    }
+                    KeyCode::Char('n') if ctrl => {
+                        app.search_history_next();
+                    }
+                    KeyCode::Up => {
+                        app.search_history_prev();
+                    }
+                    KeyCode::Down => {
+                        app.search_history_next();
+                    }
+                    KeyCode::Left => {
+                        app.search_cursor_backward();
+                    }
+                    KeyCode::Right => {
+                        app.search_cursor_forward();
+                    }
+                    KeyCode::Char(c) => {
+                        app.search_input(c);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    } else {
+        match key.code {
+            KeyCode::Char('q') => {
+                app.should_quit = true;
+            }
+            KeyCode::Char('?') => {
+                app.show_help = true;
+            }
+            KeyCode::Char('/') => {
+                app.start_search();
+            }
+            KeyCode::Tab => {
+                app.focus_next();
+            }
+            KeyCode::Char(' ') => {
+                app.play_pause();
+            }
+            KeyCode::Char('n') => {
+                app.next_track();
+            }
+            KeyCode::Char('p') => {
+                app.previous_track();
+            }
+            KeyCode::Char('s') => {
+                app.toggle_shuffle();
+            }
+            KeyCode::Char('r') => {
+                app.cycle_repeat();
+            }
+            KeyCode::Char('c') => {
+                app.cycle_highlight_color();
+            }
+            KeyCode::Char('R') => {
+                app.refresh_current_playlist();
+            }
+            KeyCode::Char('o') => {
+                app.open_sort_menu();
+            }
+            KeyCode::Char('L') if app.launch_offered => {
+                app.launch_player();
+            }
+            KeyCode::Left => {
+                app.seek_backward();
+            }
+            KeyCode::Right => {
+                app.seek_forward();
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                match app.focus {
+                    Focus::RecentlyAdded => app.recently_added_up(),
+                    Focus::Playlists => app.playlists_up(),
+                    Focus::Content => app.content_up(),
+                    _ => {}
+                }
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                match app.focus {
+                    Focus::RecentlyAdded => app.recently_added_down(),
+                    Focus::Playlists => app.playlists_down(),
+                    Focus::Content => app.content_down(),
+                    _ => {}
+                }
+            }
+            KeyCode::Char('g') => {
+                match app.focus {
+                    Focus::RecentlyAdded => app.recently_added_top(),
+                    Focus::Playlists => app.playlists_top(),
+                    Focus::Content => app.content_top(),
+                    _ => {}
+                }
+            }
+            KeyCode::Char('G') => {
+                match app.focus {
+                    Focus::RecentlyAdded => app.recently_added_bottom(),
+                    Focus::Playlists => app.playlists_bottom(),
+                    Focus::Content => app.content_bottom(),
+                    _ => {}
+                }
+            }
+            KeyCode::Char('h') if app.focus == Focus::Playlists => {
+                app.collapse_selected_folder();
+            }
+            KeyCode::Char('h') => {
+                app.focus_left();
+            }
+            KeyCode::Char('l') => {
+                // 閉じたフォルダは開き、それ以外は詳細へ移動
+                let expanded = app.focus == Focus::Playlists && app.expand_selected_folder();
+                if !expanded {
+                    app.focus_right();
+                }
+            }
+            KeyCode::Char('a') => {
+                app.start_add_to_playlist();
+            }
+            KeyCode::Char('d') if app.focus == Focus::Playlists => {
+                app.start_delete_saved_search();
+            }
+            KeyCode::Enter => {
+                match app.focus {
+                    Focus::RecentlyAdded => {
+                        // アルバムを再生せず、詳細paneにフォーカス移動
+                        app.focus = Focus::Content;
+                        app.content_selected = 0;
+                        app.content_scroll = 0;
+                    }
+                    Focus::Playlists => {
+                        // フォルダは開閉し、プレイリストは再生せず詳細paneにフォーカス移動
+                        let toggled = app.toggle_selected_folder();
+                        if !toggled {
+                            app.focus = Focus::Content;
+                            app.content_selected = 0;
+                            app.content_scroll = 0;
+                        }
+                    }
+                    Focus::Content => app.play_selected(),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant};
+
+    /// デモのライブラリで App を作る（キャッシュはデモ用の一時ディレクトリ）
+    fn demo_app() -> (App, PathBuf) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("doc/demo-library.json");
+        let (demo, dir) = crate::setup_demo(&path).unwrap();
+        (App::new(Box::new(demo)), dir)
+    }
+
+    fn press(app: &mut App, code: KeyCode) {
+        handle_key(app, KeyEvent::new(code, KeyModifiers::NONE));
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            press(app, KeyCode::Char(c));
+        }
+    }
+
+    #[test]
+    fn search_play_and_quit_with_keys() {
+        let (mut app, dir) = demo_app();
+        assert!(!app.should_show_welcome());
+
+        press(&mut app, KeyCode::Char('/'));
+        assert!(app.search_mode);
+        type_text(&mut app, "nyquist");
+        press(&mut app, KeyCode::Enter);
+        assert!(app.focus == Focus::Content);
+        assert_eq!(app.selected_search_track().map(|t| t.name.as_str()), Some("Nyquist"));
+
+        // 再生してプレイヤーの状態が届くのを待つ
+        press(&mut app, KeyCode::Enter);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while app.track.name != "Nyquist" && Instant::now() < deadline {
+            app.refresh_full();
+            std::thread::sleep(Duration::from_millis(20));
+            app.poll_responses();
+        }
+        assert_eq!(app.track.name, "Nyquist");
+
+        press(&mut app, KeyCode::Esc);
+        assert!(!app.search_mode);
+        press(&mut app, KeyCode::Tab);
+        assert!(app.focus == Focus::Playlists);
+        press(&mut app, KeyCode::Char('q'));
+        assert!(app.should_quit);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
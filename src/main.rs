@@ -1,22 +1,44 @@
+#[cfg(target_os = "macos")]
 mod accessibility;
 mod app;
+mod backend;
+#[cfg(test)]
+mod bench;
 mod cache;
+mod demo;
+mod error;
+mod fuzzy;
+mod keys;
+mod mpd;
 mod music;
+mod normalize;
+mod osascript;
+mod playlist_tree;
+mod query;
+mod script;
+mod search_groups;
+mod search_index;
+mod sort;
 mod ui;
 
 use std::env;
 use std::io;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use crossterm::{
-    event::{self, Event, KeyCode, KeyModifiers, MouseEventKind, EnableMouseCapture, DisableMouseCapture},
+    event::{self, Event, MouseEventKind, EnableMouseCapture, DisableMouseCapture},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
 
-use app::{App, Focus};
+use app::App;
+use backend::PlayerBackend;
+use demo::{DemoBackend, DemoLibrary};
+use mpd::MpdBackend;
+use music::MusicController;
 
 include!(concat!(env!("OUT_DIR"), "/version_info.rs"));
 
@@ -28,13 +50,34 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
+    // --demo <library.json>: Music.app の代わりにフィクスチャ上のシミュレータを使う
+    let mut demo_dir = None;
+    let player: Box<dyn PlayerBackend> = if args.len() > 1 && args[1] == "--demo" {
+        let Some(path) = args.get(2) else {
+            eprintln!("Usage: mmt --demo <library.json>");
+            std::process::exit(2);
+        };
+        let (demo, dir) = setup_demo(Path::new(path))?;
+        demo_dir = Some(dir);
+        Box::new(demo)
+    } else if args.len() > 1 && args[1] == "--mpd" {
+        // --mpd [host:port|socket]: MPD を操作する（キャッシュは Music.app 用と分ける）
+        let mpd = MpdBackend::connect(args.get(2).map(String::as_str))?;
+        if let Some(dir) = dirs::cache_dir() {
+            cache::set_cache_dir(dir.join("macos-music-tui").join("mpd"));
+        }
+        Box::new(mpd)
+    } else {
+        Box::new(MusicController)
+    };
+
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let result = run_app(&mut terminal);
+    let result = run_app(&mut terminal, player);
 
     disable_raw_mode()?;
     execute!(
@@ -44,6 +87,10 @@ fn main() -> Result<()> {
     )?;
     terminal.show_cursor()?;
 
+    if let Some(dir) = demo_dir {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
     if let Err(e) = result {
         eprintln!("Error: {}", e);
     }
@@ -51,8 +98,26 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
-    let mut app = App::new();
+/// デモモードの準備
+/// 実際のキャッシュを汚さないよう一時ディレクトリにフィクスチャからキャッシュを作成する
+fn setup_demo(path: &Path) -> Result<(DemoBackend, PathBuf)> {
+    let library = DemoLibrary::load(path)?;
+
+    let demo_dir = env::temp_dir().join(format!("macos-music-tui-demo-{}", std::process::id()));
+    cache::set_cache_dir(demo_dir.clone());
+
+    let (mut track_cache, playlist_cache) = library.to_caches();
+    track_cache.save()?;
+    playlist_cache.save()?;
+
+    Ok((DemoBackend::new(library), demo_dir))
+}
+
+fn run_app(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    player: Box<dyn PlayerBackend>,
+) -> Result<()> {
+    let mut app = App::new(player);
     let position_tick_rate = Duration::from_millis(200);
     let full_tick_rate = Duration::from_millis(1000);  // 1秒ごとに曲情報を更新
     let mut last_position_tick = Instant::now();
@@ -90,276 +155,7 @@ fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()>
                     }
                 }
                 Event::Key(key) => {
-                // ウェルカム画面表示中
-                if app.should_show_welcome() {
-                    match key.code {
-                        KeyCode::Char('c') => {
-                            app.cycle_highlight_color();
-                        }
-                        _ => {
-                            app.dismiss_welcome();
-                        }
-                    }
-                    continue;
-                }
-
-                // ヘルプ画面表示中
-                if app.show_help {
-                    app.show_help = false;
-                    continue;
-                }
-
-                if !app.search_mode && !app.add_to_playlist_mode {
-                    app.message = None;
-                }
-
-                if app.new_playlist_input_mode {
-                    // 新規プレイリスト名入力モード
-                    match key.code {
-                        KeyCode::Esc => {
-                            app.cancel_add_to_playlist();
-                        }
-                        KeyCode::Enter => {
-                            app.confirm_new_playlist();
-                        }
-                        KeyCode::Backspace => {
-                            app.new_playlist_backspace();
-                        }
-                        KeyCode::Char(c) => {
-                            app.new_playlist_input(c);
-                        }
-                        _ => {}
-                    }
-                } else if app.add_to_playlist_mode {
-                    // プレイリスト追加モード
-                    match key.code {
-                        KeyCode::Esc => {
-                            app.cancel_add_to_playlist();
-                        }
-                        KeyCode::Enter => {
-                            app.confirm_add_to_playlist();
-                        }
-                        KeyCode::Up | KeyCode::Char('k') => {
-                            // プレイリスト選択（+ New playlist を含む）
-                            if app.playlists_selected > 0 {
-                                app.playlists_selected -= 1;
-                                if app.playlists_selected < app.playlists_scroll {
-                                    app.playlists_scroll = app.playlists_selected;
-                                }
-                            }
-                        }
-                        KeyCode::Down | KeyCode::Char('j') => {
-                            let max_index = app.playlists_count_with_new() - 1;
-                            if app.playlists_selected < max_index {
-                                app.playlists_selected += 1;
-                                if app.playlists_selected >= app.playlists_scroll + app.playlists_visible {
-                                    app.playlists_scroll = app.playlists_selected.saturating_sub(app.playlists_visible - 1);
-                                }
-                            }
-                        }
-                        _ => {}
-                    }
-                } else if app.search_mode {
-                    // 検索モード中のフォーカスによって動作を分岐
-                    if app.focus == Focus::Content {
-                        // 検索結果にフォーカス中: j/k/h でナビゲーション
-                        match key.code {
-                            KeyCode::Esc => {
-                                app.cancel_search();
-                            }
-                            KeyCode::Enter => {
-                                app.play_selected();
-                            }
-                            KeyCode::Up | KeyCode::Char('k') => {
-                                app.content_up();
-                            }
-                            KeyCode::Down | KeyCode::Char('j') => {
-                                app.content_down();
-                            }
-                            KeyCode::Char('J') => {
-                                app.search_next_album();
-                            }
-                            KeyCode::Char('K') => {
-                                app.search_prev_album();
-                            }
-                            KeyCode::Char('g') => {
-                                app.content_top();
-                            }
-                            KeyCode::Char('G') => {
-                                app.content_bottom();
-                            }
-                            KeyCode::Char('h') | KeyCode::Char('/') => {
-                                // Searchカードに戻る
-                                app.focus = Focus::Search;
-                            }
-                            KeyCode::Char('l') => {
-                                // 選択中の曲のアルバム全曲を表示
-                                if let Some(item) = app.search_results.get(app.content_selected) {
-                                    let album_name = item.album.clone();
-                                    app.show_album_tracks(&album_name);
-                                    app.search_mode = false;
-                                }
-                            }
-                            KeyCode::Char('a') => {
-                                app.start_add_to_playlist();
-                            }
-                            KeyCode::Char('s') => {
-                                app.toggle_search_sort();
-                            }
-                            KeyCode::Char('c') => {
-                                app.cycle_highlight_color();
-                            }
-                            KeyCode::Char('?') => {
-                                app.show_help = true;
-                            }
-                            _ => {}
-                        }
-                    } else {
-                        // Searchカードにフォーカス中: 文字入力
-                        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
-                        match key.code {
-                            KeyCode::Esc => {
-                                app.cancel_search();
-                            }
-                            KeyCode::Enter => {
-                                app.confirm_search();
-                            }
-                            KeyCode::Backspace => {
-                                app.search_backspace();
-                            }
-                            KeyCode::Char('a') if ctrl => {
-                                app.search_cursor_start();
-                            }
-                            KeyCode::Char('e') if ctrl => {
-                                app.search_cursor_end();
-                            }
-                            KeyCode::Char('h') if ctrl => {
-                                app.search_backspace();
-                            }
-                            KeyCode::Char('k') if ctrl => {
-                                app.search_kill_line();
-                            }
-                            KeyCode::Char('f') if ctrl => {
-                                app.search_cursor_forward();
-                            }
-                            KeyCode::Char('b') if ctrl => {
-                                app.search_cursor_backward();
-                            }
-                            KeyCode::Left => {
-                                app.search_cursor_backward();
-                            }
-                            KeyCode::Right => {
-                                app.search_cursor_forward();
-                            }
-                            KeyCode::Char(c) => {
-                                app.search_input(c);
-                            }
-                            _ => {}
-                        }
-                    }
-                } else {
-                    match key.code {
-                        KeyCode::Char('q') => {
-                            app.should_quit = true;
-                        }
-                        KeyCode::Char('?') => {
-                            app.show_help = true;
-                        }
-                        KeyCode::Char('/') => {
-                            app.start_search();
-                        }
-                        KeyCode::Tab => {
-                            app.focus_next();
-                        }
-                        KeyCode::Char(' ') => {
-                            app.play_pause();
-                        }
-                        KeyCode::Char('n') => {
-                            app.next_track();
-                        }
-                        KeyCode::Char('p') => {
-                            app.previous_track();
-                        }
-                        KeyCode::Char('s') => {
-                            app.toggle_shuffle();
-                        }
-                        KeyCode::Char('r') => {
-                            app.cycle_repeat();
-                        }
-                        KeyCode::Char('c') => {
-                            app.cycle_highlight_color();
-                        }
-                        KeyCode::Char('R') => {
-                            app.refresh_current_playlist();
-                        }
-                        KeyCode::Left => {
-                            app.seek_backward();
-                        }
-                        KeyCode::Right => {
-                            app.seek_forward();
-                        }
-                        KeyCode::Up | KeyCode::Char('k') => {
-                            match app.focus {
-                                Focus::RecentlyAdded => app.recently_added_up(),
-                                Focus::Playlists => app.playlists_up(),
-                                Focus::Content => app.content_up(),
-                                _ => {}
-                            }
-                        }
-                        KeyCode::Down | KeyCode::Char('j') => {
-                            match app.focus {
-                                Focus::RecentlyAdded => app.recently_added_down(),
-                                Focus::Playlists => app.playlists_down(),
-                                Focus::Content => app.content_down(),
-                                _ => {}
-                            }
-                        }
-                        KeyCode::Char('g') => {
-                            match app.focus {
-                                Focus::RecentlyAdded => app.recently_added_top(),
-                                Focus::Playlists => app.playlists_top(),
-                                Focus::Content => app.content_top(),
-                                _ => {}
-                            }
-                        }
-                        KeyCode::Char('G') => {
-                            match app.focus {
-                                Focus::RecentlyAdded => app.recently_added_bottom(),
-                                Focus::Playlists => app.playlists_bottom(),
-                                Focus::Content => app.content_bottom(),
-                                _ => {}
-                            }
-                        }
-                        KeyCode::Char('h') => {
-                            app.focus_left();
-                        }
-                        KeyCode::Char('l') => {
-                            app.focus_right();
-                        }
-                        KeyCode::Char('a') => {
-                            app.start_add_to_playlist();
-                        }
-                        KeyCode::Enter => {
-                            match app.focus {
-                                Focus::RecentlyAdded => {
-                                    // アルバムを再生せず、詳細paneにフォーカス移動
-                                    app.focus = Focus::Content;
-                                    app.content_selected = 0;
-                                    app.content_scroll = 0;
-                                }
-                                Focus::Playlists => {
-                                    // プレイリストを再生せず、詳細paneにフォーカス移動
-                                    app.focus = Focus::Content;
-                                    app.content_selected = 0;
-                                    app.content_scroll = 0;
-                                }
-                                Focus::Content => app.play_selected(),
-                                _ => {}
-                            }
-                        }
-                        _ => {}
-                    }
-                }
+                    keys::handle_key(&mut app, key);
                 }
                 _ => {}
             }
@@ -0,0 +1,797 @@
+// Music Player Daemon backend
+// Speaks the MPD text protocol over TCP or a Unix socket
+
+use anyhow::{Context, Result};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::backend::{PlayerBackend, PlaylistRef, TrackRef};
+use crate::cache::days_from_civil;
+use crate::music::{ListItem, PlayerState, PlaylistFingerprint, PlaylistInfo, SimpleTrack, TrackBatch, TrackInfo, TrackStats};
+
+const DEFAULT_ADDRESS: &str = "localhost:6600";
+// 巨大なプレイリストの読み込みなどに備えて長めに取る
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+// ライブラリは find の window で分けて取得する
+// （listallinfo 1回では max_output_buffer_size を超えることがある）
+const LIBRARY_PAGE: usize = 1000;
+// すべての曲に一致するフィルタ
+const ALL_SONGS: &str = "(modified-since '0')";
+
+/// TCP と Unix ソケットの両方を扱うストリーム
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    fn connect(address: &str) -> Result<Self> {
+        #[cfg(unix)]
+        if address.starts_with('/') || address.starts_with('~') {
+            let path = match address.strip_prefix("~/") {
+                Some(rest) => dirs::home_dir().map(|h| h.join(rest)).unwrap_or_else(|| address.into()),
+                None => address.into(),
+            };
+            let stream = UnixStream::connect(&path)
+                .with_context(|| format!("Could not connect to MPD at {}", path.display()))?;
+            stream.set_read_timeout(Some(READ_TIMEOUT))?;
+            return Ok(Stream::Unix(stream));
+        }
+
+        let stream = TcpStream::connect(address)
+            .with_context(|| format!("Could not connect to MPD at {}", address))?;
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+        Ok(Stream::Tcp(stream))
+    }
+
+    fn try_clone(&self) -> Result<Self> {
+        Ok(match self {
+            Stream::Tcp(s) => Stream::Tcp(s.try_clone()?),
+            #[cfg(unix)]
+            Stream::Unix(s) => Stream::Unix(s.try_clone()?),
+        })
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Tcp(s) => s.flush(),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.flush(),
+        }
+    }
+}
+
+/// MPD への1接続
+struct Connection {
+    reader: BufReader<Stream>,
+    writer: Stream,
+}
+
+impl Connection {
+    fn open(address: &str, password: Option<&str>) -> Result<Self> {
+        let stream = Stream::connect(address)?;
+        let writer = stream.try_clone()?;
+        let mut conn = Connection { reader: BufReader::new(stream), writer };
+
+        // 接続直後のグリーティング "OK MPD 0.23.5"
+        let mut greeting = String::new();
+        conn.reader.read_line(&mut greeting)?;
+        if !greeting.starts_with("OK MPD") {
+            anyhow::bail!("Unexpected MPD greeting: {}", greeting.trim());
+        }
+
+        if let Some(password) = password {
+            conn.command(&format!("password {}", quote(password)?)).map_err(CommandError::into_inner)?;
+        }
+        Ok(conn)
+    }
+
+    /// コマンドを送り、OK までの "key: value" 行を返す
+    fn command(&mut self, command: &str) -> std::result::Result<Vec<(String, String)>, CommandError> {
+        let mut request = Vec::with_capacity(command.len() + 1);
+        request.extend_from_slice(command.as_bytes());
+        request.push(b'\n');
+        // 1バイトも送れなかったときだけ「届いていない」と判断できる
+        let mut sent = 0;
+        while sent < request.len() {
+            match self.writer.write(&request[sent..]) {
+                Ok(0) => return Err(CommandError::lost(sent, anyhow::anyhow!("MPD closed the connection"))),
+                Ok(n) => sent += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(CommandError::lost(sent, e.into())),
+            }
+        }
+        self.writer.flush().map_err(|e| CommandError::Lost(e.into()))?;
+
+        let mut pairs = Vec::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return Err(CommandError::Lost(anyhow::anyhow!("MPD closed the connection"))),
+                Ok(_) => {}
+                Err(e) => return Err(CommandError::Lost(e.into())),
+            }
+            let line = line.trim_end_matches('\n');
+            if line == "OK" {
+                return Ok(pairs);
+            }
+            if let Some(err) = line.strip_prefix("ACK ") {
+                return Err(CommandError::Ack(anyhow::anyhow!("MPD error: {}", err)));
+            }
+            if let Some((key, value)) = line.split_once(": ") {
+                pairs.push((key.to_string(), value.to_string()));
+            }
+        }
+    }
+}
+
+/// コマンド実行の失敗理由
+enum CommandError {
+    /// 送信できなかった（接続が既に切れていた。コマンドは MPD に届いていない）
+    NotSent(anyhow::Error),
+    /// MPD がエラーを返した（接続はそのまま使える）
+    Ack(anyhow::Error),
+    /// 送信後に切断した／タイムアウトした（コマンドが実行されたかは分からない）
+    Lost(anyhow::Error),
+}
+
+impl CommandError {
+    fn lost(sent: usize, e: anyhow::Error) -> Self {
+        if sent == 0 { CommandError::NotSent(e) } else { CommandError::Lost(e) }
+    }
+
+    fn into_inner(self) -> anyhow::Error {
+        match self {
+            CommandError::NotSent(e) | CommandError::Ack(e) | CommandError::Lost(e) => e,
+        }
+    }
+}
+
+/// 状態を変えないコマンド（送信後に失敗しても再送してよい）
+fn is_read_only(command: &str) -> bool {
+    let name = command.split_whitespace().next().unwrap_or("");
+    matches!(
+        name,
+        "status" | "currentsong" | "stats" | "listplaylists" | "listplaylistinfo" | "find"
+    )
+}
+
+/// 引数を MPD の引用符形式にする
+/// 改行などの制御文字はコマンドの区切りを壊すので受け付けない
+fn quote(arg: &str) -> Result<String> {
+    if arg.chars().any(char::is_control) {
+        anyhow::bail!("MPD arguments cannot contain control characters: {:?}", arg);
+    }
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    for c in arg.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    Ok(quoted)
+}
+
+/// ライブラリ内の1曲（再生・追加用に URI を保持）
+#[derive(Debug, Clone)]
+struct MpdSong {
+    uri: String,
+    track: SimpleTrack,
+    // データベースに追加された時刻（Unix timestamp）
+    added: u64,
+}
+
+/// MPD を操作するバックエンド
+pub struct MpdBackend {
+    address: String,
+    password: Option<String>,
+    conn: Mutex<Option<Connection>>,
+    // データベースの全曲（初回アクセス時に取得）
+    library: Mutex<Option<Vec<MpdSong>>>,
+}
+
+impl MpdBackend {
+    /// address: "host:port" または Unix ソケットのパス
+    /// None の場合は MPD_HOST / MPD_PORT 環境変数（mpc と同じ規則）、なければ localhost:6600
+    /// address に "password@" がなければ MPD_HOST のパスワードを使う
+    pub fn connect(address: Option<&str>) -> Result<Self> {
+        let (address, password) = match address {
+            Some(a) => match split_password(a) {
+                (Some(pw), host) => (host, Some(pw)),
+                (None, host) => (host, Self::address_from_env().1),
+            },
+            None => Self::address_from_env(),
+        };
+        let conn = Connection::open(&address, password.as_deref())?;
+        Ok(Self {
+            address,
+            password,
+            conn: Mutex::new(Some(conn)),
+            library: Mutex::new(None),
+        })
+    }
+
+    fn address_from_env() -> (String, Option<String>) {
+        let Ok(host) = std::env::var("MPD_HOST") else {
+            return (DEFAULT_ADDRESS.to_string(), None);
+        };
+        let (password, host) = split_password(&host);
+        if host.starts_with('/') || host.starts_with('~') {
+            return (host, password);
+        }
+        let port = std::env::var("MPD_PORT").unwrap_or_else(|_| "6600".to_string());
+        (format!("{}:{}", host, port), password)
+    }
+
+    /// コマンドを実行
+    /// 接続が切れていれば1回だけ再接続して再送する。ただし送信後に失敗した場合は、
+    /// 二重に実行されないよう状態を変えないコマンドに限る
+    fn run(&self, command: &str) -> Result<Vec<(String, String)>> {
+        let mut conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(c) = conn.as_mut() {
+            match c.command(command) {
+                Ok(pairs) => return Ok(pairs),
+                // MPD 自体のエラーは再接続しても変わらない
+                Err(CommandError::Ack(e)) => return Err(e),
+                Err(CommandError::Lost(e)) if !is_read_only(command) => {
+                    // 応答の途中かもしれないので、この接続は捨てて次回つなぎ直す
+                    *conn = None;
+                    return Err(e);
+                }
+                Err(CommandError::NotSent(_) | CommandError::Lost(_)) => {}
+            }
+        }
+        *conn = None;
+        let mut fresh = Connection::open(&self.address, self.password.as_deref())?;
+        let result = fresh.command(command);
+        // 送信後に失敗した接続は再利用しない
+        if !matches!(result, Err(CommandError::NotSent(_) | CommandError::Lost(_))) {
+            *conn = Some(fresh);
+        }
+        result.map_err(CommandError::into_inner)
+    }
+
+    /// 複数のコマンドを command_list でまとめて送る
+    fn run_list(&self, commands: &[String]) -> Result<()> {
+        if commands.is_empty() {
+            return Ok(());
+        }
+        let mut list = String::from("command_list_begin\n");
+        for command in commands {
+            list.push_str(command);
+            list.push('\n');
+        }
+        list.push_str("command_list_end");
+        self.run(&list)?;
+        Ok(())
+    }
+
+    fn status(&self) -> Result<Vec<(String, String)>> {
+        self.run("status")
+    }
+
+    /// ライブラリ全体を取得（キャッシュ済みならそれを使う）
+    fn with_library<T>(&self, f: impl FnOnce(&[MpdSong]) -> T) -> Result<T> {
+        let mut library = self.library.lock().unwrap_or_else(|e| e.into_inner());
+        if library.is_none() {
+            let mut songs = Vec::new();
+            loop {
+                let start = songs.len();
+                let command = format!("find {} window {}:{}", quote(ALL_SONGS)?, start, start + LIBRARY_PAGE);
+                let page = parse_songs(&self.run(&command)?);
+                let done = page.len() < LIBRARY_PAGE;
+                songs.extend(page);
+                if done {
+                    break;
+                }
+            }
+            *library = Some(songs);
+        }
+        Ok(f(library.as_deref().unwrap_or(&[])))
+    }
+
+    /// 曲の URI（persistent ID として URI を渡しているので、あればそのまま使う）
+    fn find_uri(&self, track: TrackRef, matches: impl Fn(&SimpleTrack) -> bool) -> Result<String> {
+        if !track.persistent_id.is_empty() {
+            return Ok(track.persistent_id.to_string());
+        }
+        self.with_library(|songs| songs.iter().find(|s| matches(&s.track)).map(|s| s.uri.clone()))?
+            .ok_or_else(|| anyhow::anyhow!("Track not found in MPD database"))
+    }
+}
+
+/// 先頭の "password@" を分ける（mpc と同じく最初の @ まで）
+/// パス（/, ~ で始まる）と抽象ソケット（@ で始まる）はそのまま。パスやホストの途中の @ では分けない
+fn split_password(host: &str) -> (Option<String>, String) {
+    if host.starts_with(['/', '~', '@']) {
+        return (None, host.to_string());
+    }
+    match host.split_once('@') {
+        Some((pw, h)) if !pw.contains('/') && !h.is_empty() => (Some(pw.to_string()), h.to_string()),
+        _ => (None, host.to_string()),
+    }
+}
+
+fn get<'a>(pairs: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    pairs.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+}
+
+/// "file:" で始まるブロックごとに曲へ分割する
+fn parse_songs(pairs: &[(String, String)]) -> Vec<MpdSong> {
+    let mut songs = Vec::new();
+    let mut start = None;
+    for (i, (key, _)) in pairs.iter().enumerate() {
+        // directory: / playlist: はブロックの区切りとして扱う
+        if key == "file" || key == "directory" || key == "playlist" {
+            if let Some(s) = start.take() {
+                songs.push(parse_song(&pairs[s..i]));
+            }
+            if key == "file" {
+                start = Some(i);
+            }
+        }
+    }
+    if let Some(s) = start {
+        songs.push(parse_song(&pairs[s..]));
+    }
+    songs
+}
+
+fn parse_song(pairs: &[(String, String)]) -> MpdSong {
+    let uri = get(pairs, "file").unwrap_or("").to_string();
+    // タイトルがなければファイル名を使う
+    let name = get(pairs, "Title")
+        .map(|t| t.to_string())
+        .unwrap_or_else(|| uri.rsplit('/').next().unwrap_or("").to_string());
+    let duration = get(pairs, "duration")
+        .or_else(|| get(pairs, "Time"))
+        .and_then(|d| d.parse::<f64>().ok())
+        .unwrap_or(0.0);
+    // 追加日は MPD 0.24 以降の Added。それより前の MPD にはないので、ファイルの更新日時で代用する
+    let added = get(pairs, "Added")
+        .or_else(|| get(pairs, "Last-Modified"))
+        .and_then(parse_iso8601)
+        .unwrap_or(0);
+
+    MpdSong {
+        uri: uri.clone(),
+        added,
+        track: SimpleTrack {
+            name,
+            artist: get(pairs, "Artist").unwrap_or("").to_string(),
+            album: get(pairs, "Album").unwrap_or("").to_string(),
+            album_artist: get(pairs, "AlbumArtist").unwrap_or("").to_string(),
+            date_added: added,
+            year: get(pairs, "Date").and_then(leading_number).unwrap_or(0),
+            track_number: get(pairs, "Track").and_then(leading_number).unwrap_or(0),
+            disc_number: get(pairs, "Disc").and_then(leading_number).unwrap_or(0),
+            time: format_duration(duration),
+            played_count: 0,
+            favorited: false,
+            rating: 0,
+            played_date: 0,
+            persistent_id: uri,
+        },
+    }
+}
+
+/// "3/12" や "1999-05-01" の先頭の数値
+fn leading_number(value: &str) -> Option<u32> {
+    let digits: String = value.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// 秒数を "3:08" 形式に
+fn format_duration(seconds: f64) -> String {
+    let total = seconds.round() as u64;
+    if total >= 3600 {
+        format!("{}:{:02}:{:02}", total / 3600, (total % 3600) / 60, total % 60)
+    } else {
+        format!("{}:{:02}", total / 60, total % 60)
+    }
+}
+
+/// "2024-03-01T12:34:56Z" を Unix timestamp に
+fn parse_iso8601(value: &str) -> Option<u64> {
+    let (date, time) = value.trim_end_matches('Z').split_once('T')?;
+    let mut d = date.split('-').map(|p| p.parse::<i64>().ok());
+    let (y, m, day) = (d.next()??, d.next()??, d.next()??);
+    let mut t = time.split(':').map(|p| p.parse::<i64>().ok());
+    let (h, min, s) = (t.next()??, t.next()??, t.next()??);
+    let secs = days_from_civil(y, m, day) * 86400 + h * 3600 + min * 60 + s;
+    u64::try_from(secs).ok()
+}
+
+fn song_to_list_item(song: &MpdSong) -> ListItem {
+    ListItem {
+        name: song.track.name.clone(),
+        artist: song.track.artist.clone(),
+        album: song.track.album.clone(),
+        time: song.track.time.clone(),
+        year: song.track.year,
+        track_number: song.track.track_number,
+        played_count: 0,
+        favorited: false,
+        persistent_id: song.uri.clone(),
+    }
+}
+
+impl PlayerBackend for MpdBackend {
+    fn play_pause(&self) -> Result<()> {
+        let status = self.status()?;
+        match get(&status, "state") {
+            Some("play") => self.run("pause 1")?,
+            Some("pause") => self.run("pause 0")?,
+            _ => self.run("play")?,
+        };
+        Ok(())
+    }
+
+    fn next_track(&self) -> Result<()> {
+        self.run("next")?;
+        Ok(())
+    }
+
+    fn previous_track(&self) -> Result<()> {
+        self.run("previous")?;
+        Ok(())
+    }
+
+    fn toggle_shuffle(&self) -> Result<bool> {
+        let status = self.status()?;
+        let enabled = get(&status, "random") != Some("1");
+        self.run(if enabled { "random 1" } else { "random 0" })?;
+        Ok(enabled)
+    }
+
+    fn cycle_repeat(&self) -> Result<String> {
+        // off: repeat 0 / all: repeat 1 single 0 / one: repeat 1 single 1
+        let status = self.status()?;
+        let repeat = get(&status, "repeat") == Some("1");
+        let single = get(&status, "single") == Some("1");
+        let next = match (repeat, single) {
+            (false, _) => {
+                self.run_list(&["repeat 1".to_string(), "single 0".to_string()])?;
+                "all"
+            }
+            (true, false) => {
+                self.run("single 1")?;
+                "one"
+            }
+            (true, true) => {
+                self.run_list(&["repeat 0".to_string(), "single 0".to_string()])?;
+                "off"
+            }
+        };
+        Ok(next.to_string())
+    }
+
+    fn seek_backward(&self) -> Result<()> {
+        self.run("seekcur -10")?;
+        Ok(())
+    }
+
+    fn seek_forward(&self) -> Result<()> {
+        self.run("seekcur +10")?;
+        Ok(())
+    }
+
+    fn get_position(&self) -> Result<(f64, bool)> {
+        let status = self.status()?;
+        let position = get(&status, "elapsed").and_then(|e| e.parse().ok()).unwrap_or(0.0);
+        Ok((position, get(&status, "state") == Some("play")))
+    }
+
+    fn get_all_state(&self) -> Result<PlayerState> {
+        let status = self.status()?;
+        let state = get(&status, "state").unwrap_or("stop");
+
+        let track = if state == "stop" {
+            TrackInfo::default()
+        } else {
+            let song = parse_song(&self.run("currentsong")?);
+            let duration = get(&status, "duration")
+                .and_then(|d| d.parse().ok())
+                .unwrap_or(0.0);
+            TrackInfo {
+                name: song.track.name,
+                artist: song.track.artist,
+                album: song.track.album,
+                duration,
+                position: get(&status, "elapsed").and_then(|e| e.parse().ok()).unwrap_or(0.0),
+                is_playing: state == "play",
+            }
+        };
+
+        let repeat = match (get(&status, "repeat"), get(&status, "single")) {
+            (Some("1"), Some("1")) => "one",
+            (Some("1"), _) => "all",
+            _ => "off",
+        };
+
+        Ok(PlayerState {
+            track,
+            // 音量制御のない出力では -1 が返る
+            volume: get(&status, "volume").and_then(|v| v.parse().ok()).unwrap_or(0).max(0),
+            shuffle: get(&status, "random") == Some("1"),
+            repeat: repeat.to_string(),
+        })
+    }
+
+    fn get_total_track_count(&self) -> Result<usize> {
+        let stats = self.run("stats")?;
+        Ok(get(&stats, "songs").and_then(|s| s.parse().ok()).unwrap_or(0))
+    }
+
+    fn get_tracks_batch(&self, start: usize, count: usize) -> Result<TrackBatch> {
+        self.with_library(|songs| TrackBatch {
+            tracks: songs
+                .iter()
+                .skip(start.saturating_sub(1))
+                .take(count)
+                .map(|s| s.track.clone())
+                .collect(),
+            malformed: Vec::new(),
+        })
+    }
+
+    fn get_all_tracks(&self) -> Result<TrackBatch> {
+        self.with_library(|songs| TrackBatch {
+            tracks: songs.iter().map(|s| s.track.clone()).collect(),
+            malformed: Vec::new(),
+        })
+    }
+
+    fn get_tracks_added_since(&self, unix_timestamp: u64) -> Result<TrackBatch> {
+        // データベースが更新されている可能性があるので取り直す
+        *self.library.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        self.with_library(|songs| TrackBatch {
+            tracks: songs
+                .iter()
+                .filter(|s| s.added > unix_timestamp)
+                .map(|s| s.track.clone())
+                .collect(),
+            malformed: Vec::new(),
+        })
+    }
+
+    fn get_all_persistent_ids(&self) -> Result<Vec<String>> {
+        // get_tracks_added_since の直後に呼ばれるので、取り直したライブラリを使う
+        self.with_library(|songs| songs.iter().map(|s| s.uri.clone()).collect())
+    }
+
+    fn get_track_stats(&self) -> Result<Vec<TrackStats>> {
+        // MPD のデータベースには再生回数・お気に入りがない
+        Ok(Vec::new())
+    }
+
+    /// MPD のプレイリストにフォルダはなく、名前で識別する
+    fn get_playlists(&self) -> Result<Vec<PlaylistInfo>> {
+        Ok(self
+            .run("listplaylists")?
+            .into_iter()
+            .filter(|(k, _)| k == "playlist")
+            .map(|(_, name)| PlaylistInfo::top_level(name.clone(), name))
+            .collect())
+    }
+
+    /// listplaylists の Last-Modified を変更検出に使う
+    fn get_playlist_fingerprints(&self, ids: Option<&[String]>) -> Result<Vec<PlaylistFingerprint>> {
+        let mut fingerprints: Vec<PlaylistFingerprint> = Vec::new();
+        for (key, value) in self.run("listplaylists")? {
+            match key.as_str() {
+                "playlist" => fingerprints.push(PlaylistFingerprint {
+                    persistent_id: value.clone(),
+                    name: value,
+                    fingerprint: String::new(),
+                }),
+                "Last-Modified" => {
+                    if let Some(last) = fingerprints.last_mut() {
+                        last.fingerprint = value;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(ids) = ids {
+            fingerprints.retain(|p| ids.contains(&p.persistent_id));
+        }
+        Ok(fingerprints)
+    }
+
+    fn get_playlist_tracks(&self, playlist: PlaylistRef) -> Result<Vec<ListItem>> {
+        let pairs = self.run(&format!("listplaylistinfo {}", quote(playlist.name)?))?;
+        Ok(parse_songs(&pairs).iter().map(song_to_list_item).collect())
+    }
+
+    fn play_track(&self, track: TrackRef) -> Result<()> {
+        let uri = self.find_uri(track, |t| t.name == track.name && t.artist == track.artist)?;
+        self.run_list(&[
+            "clear".to_string(),
+            format!("add {}", quote(&uri)?),
+            "play 0".to_string(),
+        ])
+    }
+
+    fn play_playlist_with_context(&self, playlist: PlaylistRef, track_index: usize) -> Result<()> {
+        // MPD はキューをそのまま扱えるので、プレイリストを読み込んで途中から再生する
+        self.run_list(&[
+            "clear".to_string(),
+            format!("load {}", quote(playlist.name)?),
+            format!("play {}", track_index),
+        ])
+    }
+
+    fn play_album_with_context(&self, album_name: &str, album_artist: &str, track_index: usize) -> Result<()> {
+        // UI と同じ順（ディスク番号 → トラック番号）でキューに入れる
+        let mut uris: Vec<(u32, u32, String)> = self.with_library(|songs| {
+            songs
+                .iter()
+                .filter(|s| {
+                    let artist = if s.track.album_artist.is_empty() { &s.track.artist } else { &s.track.album_artist };
+                    s.track.album == album_name && artist == album_artist
+                })
+                .map(|s| (s.track.disc_number, s.track.track_number, s.uri.clone()))
+                .collect()
+        })?;
+        if uris.is_empty() {
+            anyhow::bail!("Album not found: {}", album_name);
+        }
+        uris.sort();
+
+        let mut commands = vec!["clear".to_string()];
+        for (_, _, uri) in &uris {
+            commands.push(format!("add {}", quote(uri)?));
+        }
+        commands.push(format!("play {}", track_index.min(uris.len() - 1)));
+        self.run_list(&commands)
+    }
+
+    fn play_tracks_with_context(&self, tracks: &[TrackRef], track_index: usize) -> Result<()> {
+        if tracks.is_empty() {
+            anyhow::bail!("No tracks to play");
+        }
+        let mut commands = vec!["clear".to_string()];
+        for &track in tracks {
+            let uri = self.find_uri(track, |t| t.name == track.name && t.album == track.album)?;
+            commands.push(format!("add {}", quote(&uri)?));
+        }
+        commands.push(format!("play {}", track_index.min(tracks.len() - 1)));
+        self.run_list(&commands)
+    }
+
+    fn add_track_to_playlist(&self, track: TrackRef, playlist: PlaylistRef) -> Result<()> {
+        let uri = self.find_uri(track, |t| t.name == track.name && t.album == track.album)?;
+        self.run(&format!("playlistadd {} {}", quote(playlist.name)?, quote(&uri)?))?;
+        Ok(())
+    }
+
+    fn create_playlist_and_add_track(&self, playlist_name: &str, track: TrackRef) -> Result<String> {
+        // playlistadd は既存のプレイリストにも追加してしまうので、先に確かめる
+        let exists = self.run("listplaylists")?.iter().any(|(k, v)| k == "playlist" && v == playlist_name);
+        if exists {
+            anyhow::bail!("Playlist '{}' already exists", playlist_name);
+        }
+        // playlistadd は存在しないプレイリストを新規作成する
+        let playlist = PlaylistRef { persistent_id: playlist_name, name: playlist_name };
+        self.add_track_to_playlist(track, playlist)?;
+        Ok(playlist_name.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(lines: &[(&str, &str)]) -> Vec<(String, String)> {
+        lines.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn quote_escapes_and_rejects_control_characters() {
+        assert_eq!(quote("plain").unwrap(), "\"plain\"");
+        assert_eq!(quote(r#"say "hi" \o/"#).unwrap(), r#""say \"hi\" \\o/""#);
+        assert_eq!(quote("日本語").unwrap(), "\"日本語\"");
+        assert!(quote("two\nlines").is_err());
+        assert!(quote("tab\there").is_err());
+    }
+
+    #[test]
+    fn songs_are_split_on_file_blocks() {
+        let songs = parse_songs(&pairs(&[
+            ("directory", "music"),
+            ("file", "music/a.flac"),
+            ("Title", "Song A"),
+            ("Artist", "Artist"),
+            ("AlbumArtist", "Various Artists"),
+            ("Album", "Album"),
+            ("Track", "3/12"),
+            ("Disc", "1/2"),
+            ("Date", "1999-05-01"),
+            ("duration", "188.4"),
+            ("Last-Modified", "2024-03-01T12:34:56Z"),
+            ("playlist", "music/list.m3u"),
+            ("file", "music/untitled.mp3"),
+            ("Time", "3725"),
+            ("Last-Modified", "2024-03-01T12:34:56Z"),
+            ("Added", "2023-01-02T00:00:00Z"),
+        ]));
+        assert_eq!(songs.len(), 2);
+
+        let a = &songs[0].track;
+        assert_eq!(songs[0].uri, "music/a.flac");
+        assert_eq!((a.name.as_str(), a.artist.as_str(), a.album.as_str()), ("Song A", "Artist", "Album"));
+        assert_eq!(a.album_artist, "Various Artists");
+        assert_eq!((a.track_number, a.disc_number, a.year), (3, 1, 1999));
+        assert_eq!(a.time, "3:08");
+        assert_eq!(a.persistent_id, "music/a.flac");
+        // Added がなければファイルの更新日時
+        assert_eq!(a.date_added, 1709296496);
+
+        // タイトルがなければファイル名、Added があればそちら
+        let b = &songs[1].track;
+        assert_eq!(b.name, "untitled.mp3");
+        assert_eq!(b.time, "1:02:05");
+        assert_eq!(b.date_added, 1672617600);
+    }
+
+    #[test]
+    fn iso8601_timestamps() {
+        assert_eq!(parse_iso8601("2024-03-01T12:34:56Z"), Some(1709296496));
+        assert_eq!(parse_iso8601("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_iso8601("1969-12-31T23:59:59Z"), None);
+        assert_eq!(parse_iso8601("2024-03-01"), None);
+        assert_eq!(parse_iso8601("garbage"), None);
+    }
+
+    #[test]
+    fn password_prefix_is_split() {
+        assert_eq!(split_password("secret@localhost"), (Some("secret".to_string()), "localhost".to_string()));
+        assert_eq!(split_password("secret@/run/mpd/socket"), (Some("secret".to_string()), "/run/mpd/socket".to_string()));
+        assert_eq!(split_password("localhost"), (None, "localhost".to_string()));
+        // パスや抽象ソケットの @ では分けない
+        assert_eq!(split_password("/run/user@1000/mpd.sock"), (None, "/run/user@1000/mpd.sock".to_string()));
+        assert_eq!(split_password("~/mpd@home/socket"), (None, "~/mpd@home/socket".to_string()));
+        assert_eq!(split_password("@mpd"), (None, "@mpd".to_string()));
+        assert_eq!(split_password("run/mpd@1/socket"), (None, "run/mpd@1/socket".to_string()));
+        assert_eq!(split_password("secret@"), (None, "secret@".to_string()));
+    }
+
+    #[test]
+    fn leading_numbers() {
+        assert_eq!(leading_number("3/12"), Some(3));
+        assert_eq!(leading_number("1999-05-01"), Some(1999));
+        assert_eq!(leading_number("07"), Some(7));
+        assert_eq!(leading_number("A1"), None);
+        assert_eq!(leading_number(""), None);
+    }
+}
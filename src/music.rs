@@ -1,55 +1,275 @@
 use anyhow::Result;
-use std::process::Command;
-
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[cfg(target_os = "macos")]
+use crate::accessibility;
+use crate::backend::{PlayerBackend, PlaylistRef, TrackRef};
+use crate::error::{self, MusicError, ERR_PLAYLIST_NOT_FOUND, ERR_TRACK_NOT_FOUND};
+use crate::osascript;
+use crate::script::{self, AppleScript};
+
+/// Music.app を AppleScript / JXA (osascript) で操作するバックエンド
 pub struct MusicController;
 
+/// ライブラリ全体の列取得のタイムアウト（数万曲でも1回で取り切る）
+const LIBRARY_FETCH_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// 一覧に出すプレイリスト（ユーザ・スマート・フォルダと、取得できれば Apple Music のプレイリスト）
+/// kind は PlaylistKind のシリアライズ名
+macro_rules! jxa_listed_playlists {
+    () => {
+        r#"
+function listedPlaylists(music) {
+    const listed = music.userPlaylists().map(p => {
+        let kind = 'user';
+        if (p.specialKind() === 'folder') {
+            kind = 'folder';
+        } else if (p.smart()) {
+            kind = 'smart';
+        }
+        return { playlist: p, kind };
+    });
+    // Apple Music から追加したプレイリスト（古い Music.app にはない）
+    try {
+        const seen = new Set(music.userPlaylists.persistentID());
+        for (const p of music.subscriptionPlaylists()) {
+            if (!seen.has(p.persistentID())) {
+                listed.push({ playlist: p, kind: 'subscription' });
+            }
+        }
+    } catch (e) {}
+    return listed;
+}
+"#
+    };
+}
+
+/// JXA の日付を Unix timestamp（秒）にする関数
+macro_rules! jxa_epoch_seconds {
+    () => {
+        r#"
+// Unix timestamp（秒）。未設定は 0
+// 日付は数値で受け取るので、macOS のロケールや時刻表記に左右されない
+function epochSeconds(d) {
+    return d ? Math.floor(d.getTime() / 1000) : 0;
+}
+"#
+    };
+}
+
+/// JXA から返すトラック1行分の組み立て
+/// 取得に失敗した行は {error} として返し、Rust 側で報告する
+macro_rules! jxa_track_row {
+    () => {
+        concat!(jxa_epoch_seconds!(), r#"
+function trackRow(t) {
+    try {
+        return {
+            name: t.name(),
+            artist: t.artist(),
+            album: t.album(),
+            album_artist: t.albumArtist(),
+            date_added: epochSeconds(t.dateAdded()),
+            year: t.year(),
+            track_number: t.trackNumber(),
+            disc_number: t.discNumber(),
+            time: t.time(),
+            played_count: t.playedCount(),
+            favorited: t.favorited(),
+            rating: t.rating(),
+            played_date: epochSeconds(t.playedDate()),
+            persistent_id: t.persistentID(),
+        };
+    } catch (e) {
+        return { error: String(e.message || e) };
+    }
+}
+"#)
+    };
+}
+
 impl MusicController {
     fn run_script(script: &str) -> Result<String> {
-        let output = Command::new("osascript")
-            .arg("-e")
-            .arg(script)
-            .output()?;
-
-        if output.status.success() {
-            Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
-        } else {
-            let err = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!("AppleScript error: {}", err)
+        osascript::run(script)
+    }
+
+    /// 引数を JSON で渡して JXA を実行する
+    /// スクリプト側では `args` として参照できる
+    fn run_jxa(body: &str, args: serde_json::Value) -> Result<String> {
+        osascript::run_javascript(&script::jxa_with_args(body, &args))
+    }
+
+    /// JXA の JSON 出力をそのまま型に変換
+    fn run_jxa_json<T: DeserializeOwned>(script: &str, args: serde_json::Value) -> Result<T> {
+        let result = Self::run_jxa(script, args)?;
+        serde_json::from_str(&result).map_err(|e| MusicError::Malformed(e.to_string()).into())
+    }
+
+    /// JXA の JSON 配列を行ごとに変換し、変換できなかった行は理由を返す
+    fn run_jxa_rows<T: DeserializeOwned>(script: &str, args: serde_json::Value) -> Result<(Vec<T>, Vec<String>)> {
+        let rows: Vec<serde_json::Value> = Self::run_jxa_json(script, args)?;
+        let mut items = Vec::with_capacity(rows.len());
+        let mut malformed = Vec::new();
+        for (i, row) in rows.into_iter().enumerate() {
+            let name = row.get("name").and_then(|n| n.as_str()).unwrap_or("").to_string();
+            if let Some(err) = row.get("error").and_then(|e| e.as_str()) {
+                malformed.push(format!("row {}: {}", i + 1, err));
+                continue;
+            }
+            match serde_json::from_value::<T>(row) {
+                Ok(item) => items.push(item),
+                Err(e) if name.is_empty() => malformed.push(format!("row {}: {}", i + 1, e)),
+                Err(e) => malformed.push(format!("row {} ({}): {}", i + 1, name, e)),
+            }
+        }
+        Ok((items, malformed))
+    }
+}
+
+/// get_all_tracks の JXA 出力（プロパティごとの列）
+/// 取得できなかった値（missing value）は null になる
+#[derive(Deserialize)]
+struct TrackColumns {
+    name: Vec<Option<String>>,
+    artist: Vec<Option<String>>,
+    album: Vec<Option<String>>,
+    album_artist: Vec<Option<String>>,
+    date_added: Vec<Option<u64>>,
+    year: Vec<Option<u32>>,
+    track_number: Vec<Option<u32>>,
+    disc_number: Vec<Option<u32>>,
+    time: Vec<Option<String>>,
+    played_count: Vec<Option<u32>>,
+    favorited: Vec<Option<bool>>,
+    rating: Vec<Option<u32>>,
+    played_date: Vec<Option<u64>>,
+    persistent_id: Vec<Option<String>>,
+}
+
+impl TrackColumns {
+    /// 列を行にまとめる
+    /// 列の長さが揃わない場合（取得中にライブラリが変わった）は全体をエラーにする
+    fn into_batch(mut self) -> Result<TrackBatch> {
+        let len = self.persistent_id.len();
+        let lengths = [
+            self.name.len(), self.artist.len(), self.album.len(), self.album_artist.len(), self.date_added.len(),
+            self.year.len(), self.track_number.len(), self.disc_number.len(), self.time.len(),
+            self.played_count.len(), self.favorited.len(), self.rating.len(), self.played_date.len(),
+        ];
+        if lengths.iter().any(|&l| l != len) {
+            return Err(MusicError::Malformed("library changed while reading track columns".to_string()).into());
+        }
+
+        let mut batch = TrackBatch::default();
+        batch.tracks.reserve(len);
+        for i in 0..len {
+            let name = self.name[i].take().unwrap_or_default();
+            // persistent ID のない行は取得中に削除された曲
+            let Some(persistent_id) = self.persistent_id[i].take() else {
+                batch.malformed.push(format!("row {} ({}): missing persistent ID", i + 1, name));
+                continue;
+            };
+            batch.tracks.push(SimpleTrack {
+                name,
+                artist: self.artist[i].take().unwrap_or_default(),
+                album: self.album[i].take().unwrap_or_default(),
+                album_artist: self.album_artist[i].take().unwrap_or_default(),
+                date_added: self.date_added[i].unwrap_or_default(),
+                year: self.year[i].unwrap_or_default(),
+                track_number: self.track_number[i].unwrap_or_default(),
+                disc_number: self.disc_number[i].unwrap_or_default(),
+                time: self.time[i].take().unwrap_or_default(),
+                played_count: self.played_count[i].unwrap_or_default(),
+                favorited: self.favorited[i].unwrap_or_default(),
+                rating: self.rating[i].unwrap_or_default(),
+                played_date: self.played_date[i].unwrap_or_default(),
+                persistent_id,
+            });
         }
+        Ok(batch)
     }
+}
+
+/// get_track_stats の JXA 出力（再生で変わるプロパティの列）
+#[derive(Deserialize)]
+struct StatsColumns {
+    persistent_id: Vec<Option<String>>,
+    played_count: Vec<Option<u32>>,
+    favorited: Vec<Option<bool>>,
+    rating: Vec<Option<u32>>,
+    played_date: Vec<Option<u64>>,
+}
+
+/// get_position の JXA 出力
+#[derive(Deserialize)]
+struct PositionRow {
+    position: f64,
+    is_playing: bool,
+}
 
-    pub fn play_pause() -> Result<()> {
+/// get_playlist_fingerprints の JXA 出力
+#[derive(Deserialize)]
+struct PlaylistFingerprintRow {
+    name: String,
+    persistent_id: String,
+    fingerprint: String,
+}
+
+/// 一覧に出さない特殊なプレイリスト
+const EXCLUDED_PLAYLISTS: [&str; 3] = ["Music", "Music Videos", "Favorite Songs"];
+
+/// get_playlist_tracks の JXA 出力
+#[derive(Deserialize)]
+struct PlaylistTrackRow {
+    name: String,
+    artist: String,
+    album: String,
+    year: u32,
+    time: String,
+    played_count: u32,
+    favorited: bool,
+    persistent_id: String,
+}
+
+impl PlayerBackend for MusicController {
+    fn launch(&self) {
+        #[cfg(target_os = "macos")]
+        accessibility::init_music_window_offscreen();
+    }
+
+    fn play_pause(&self) -> Result<()> {
         Self::run_script("tell application \"Music\" to playpause")?;
         Ok(())
     }
 
-    pub fn get_position() -> Result<(f64, bool)> {
-        let result = Self::run_script(
-            "tell application \"Music\"
-                if player state is stopped then
-                    return \"0|||false\"
-                else
-                    return (player position as string) & \"|||\" & (player state is playing)
-                end if
-            end tell"
+    fn get_position(&self) -> Result<(f64, bool)> {
+        let row: PositionRow = Self::run_jxa_json(
+            r#"
+            const music = Application('Music');
+            const state = music.playerState();
+            if (state === 'stopped') {
+                return JSON.stringify({ position: 0, is_playing: false });
+            }
+            return JSON.stringify({ position: music.playerPosition() || 0, is_playing: state === 'playing' });
+            "#,
+            serde_json::Value::Null,
         )?;
-        let parts: Vec<&str> = result.split("|||").collect();
-        let position = parts.get(0).unwrap_or(&"0").parse().unwrap_or(0.0);
-        let is_playing = parts.get(1).unwrap_or(&"false") == &"true";
-        Ok((position, is_playing))
+        Ok((row.position, row.is_playing))
     }
 
-    pub fn next_track() -> Result<()> {
+    fn next_track(&self) -> Result<()> {
         Self::run_script("tell application \"Music\" to next track")?;
         Ok(())
     }
 
-    pub fn previous_track() -> Result<()> {
+    fn previous_track(&self) -> Result<()> {
         Self::run_script("tell application \"Music\" to previous track")?;
         Ok(())
     }
 
-    pub fn toggle_shuffle() -> Result<bool> {
+    fn toggle_shuffle(&self) -> Result<bool> {
         let result = Self::run_script(
             "tell application \"Music\"
                 set shuffle enabled to not shuffle enabled
@@ -59,7 +279,7 @@ impl MusicController {
         Ok(result == "true")
     }
 
-    pub fn cycle_repeat() -> Result<String> {
+    fn cycle_repeat(&self) -> Result<String> {
         let result = Self::run_script(
             "tell application \"Music\"
                 if song repeat is off then
@@ -77,176 +297,433 @@ impl MusicController {
         Ok(result)
     }
 
-    pub fn get_all_state() -> Result<PlayerState> {
+    fn get_all_state(&self) -> Result<PlayerState> {
         let script = r#"
-            tell application "Music"
-                set vol to sound volume
-                set shuf to shuffle enabled
-                set rep to song repeat as string
-
-                if player state is not stopped then
-                    try
-                        set trackName to name of current track
-                        set trackArtist to artist of current track
-                        set trackAlbum to album of current track
-                        set trackDuration to duration of current track
-                        set currentPos to player position
-                        set isPlaying to (player state is playing)
-                        return trackName & "|||" & trackArtist & "|||" & trackAlbum & "|||" & trackDuration & "|||" & currentPos & "|||" & isPlaying & "|||" & vol & "|||" & shuf & "|||" & rep
-                    on error
-                        -- current track にアクセスできない場合（ラジオ等）は空を返す
-                        try
-                            set currentPos to player position
-                        on error
-                            set currentPos to 0
-                        end try
-                        set isPlaying to (player state is playing)
-                        return "" & "|||" & "" & "|||" & "" & "|||" & "0" & "|||" & currentPos & "|||" & isPlaying & "|||" & vol & "|||" & shuf & "|||" & rep
-                    end try
-                else
-                    return "||||||false|||" & vol & "|||" & shuf & "|||" & rep
-                end if
-            end tell
+            const music = Application('Music');
+            const state = {
+                track: { name: '', artist: '', album: '', duration: 0, position: 0, is_playing: false },
+                volume: music.soundVolume(),
+                shuffle: music.shuffleEnabled(),
+                repeat: music.songRepeat(),
+            };
+
+            const playerState = music.playerState();
+            if (playerState !== 'stopped') {
+                state.track.is_playing = playerState === 'playing';
+                try {
+                    state.track.position = music.playerPosition() || 0;
+                } catch (e) {}
+                // current track にアクセスできない場合（ラジオ等）は空のまま
+                try {
+                    const t = music.currentTrack;
+                    state.track.name = t.name();
+                    state.track.artist = t.artist();
+                    state.track.album = t.album();
+                    state.track.duration = t.duration() || 0;
+                } catch (e) {}
+            }
+            return JSON.stringify(state);
         "#;
 
-        let result = Self::run_script(script)?;
-        let parts: Vec<&str> = result.split("|||").collect();
-
-        if parts.len() >= 9 {
-            Ok(PlayerState {
-                track: TrackInfo {
-                    name: parts[0].to_string(),
-                    artist: parts[1].to_string(),
-                    album: parts[2].to_string(),
-                    duration: parts[3].parse().unwrap_or(0.0),
-                    position: parts[4].parse().unwrap_or(0.0),
-                    is_playing: parts[5] == "true",
-                },
-                volume: parts[6].parse().unwrap_or(50),
-                shuffle: parts[7] == "true",
-                repeat: parts[8].to_string(),
-            })
-        } else {
-            Ok(PlayerState::default())
-        }
+        Self::run_jxa_json(script, serde_json::Value::Null)
     }
 
-    pub fn seek_backward() -> Result<()> {
+    fn seek_backward(&self) -> Result<()> {
         Self::run_script(
             "tell application \"Music\" to set player position to (player position - 10)"
         )?;
         Ok(())
     }
 
-    pub fn seek_forward() -> Result<()> {
+    fn seek_forward(&self) -> Result<()> {
         Self::run_script(
             "tell application \"Music\" to set player position to (player position + 10)"
         )?;
         Ok(())
     }
 
-    pub fn get_playlists() -> Result<Vec<ListItem>> {
-        let script = r#"
-            tell application "Music"
-                set output to ""
-                set allPlaylists to user playlists
-                repeat with p in allPlaylists
-                    set pName to name of p
-                    set pCount to count of tracks of p
-                    set output to output & pName & ":::" & pCount & "|||"
-                end repeat
-                return output
-            end tell
-        "#;
-        let result = Self::run_script(script)?;
+    /// フォルダとプレイリストを Music.app のサイドバーの順で取得
+    fn get_playlists(&self) -> Result<Vec<PlaylistInfo>> {
+        let script = concat!(jxa_listed_playlists!(), r#"
+            const music = Application('Music');
+            return JSON.stringify(listedPlaylists(music).map(({ playlist: p, kind }) => {
+                // 最上位のプレイリストには parent がない
+                let parentId = '';
+                try {
+                    parentId = p.parent.persistentID();
+                } catch (e) {}
+                return {
+                    name: p.name(),
+                    persistent_id: p.persistentID(),
+                    parent_id: parentId,
+                    kind,
+                };
+            }));
+        "#);
+        let (rows, malformed): (Vec<PlaylistInfo>, _) = Self::run_jxa_rows(script, serde_json::Value::Null)?;
+        if !malformed.is_empty() {
+            return Err(MusicError::Malformed(malformed.join("; ")).into());
+        }
+
+        Ok(rows
+            .into_iter()
+            .filter(|p| !EXCLUDED_PLAYLISTS.contains(&p.name.as_str()))
+            .collect())
+    }
 
-        let excluded = ["Music", "Music Videos", "Favorite Songs"];
-        let playlists: Vec<ListItem> = result
-            .split("|||")
-            .filter(|s| !s.is_empty())
-            .filter_map(|s| {
-                let parts: Vec<&str> = s.split(":::").collect();
-                let name = parts.get(0).unwrap_or(&"").to_string();
-                if excluded.contains(&name.as_str()) {
-                    None
-                } else {
-                    Some(ListItem {
-                        name,
-                        artist: format!("{} tracks", parts.get(1).unwrap_or(&"0")),
-                        album: String::new(),
-                        time: String::new(),
-                        year: 0,
-                        track_number: 0,
-                        played_count: 0,
-                        favorited: false,
-                    })
+    /// プレイリストごとに曲数と曲の並び（persistent ID 列のハッシュ）を取得
+    /// Music.app のプレイリストには更新日時がないので、中身から変更を検出する
+    fn get_playlist_fingerprints(&self, ids: Option<&[String]>) -> Result<Vec<PlaylistFingerprint>> {
+        let script = concat!(jxa_listed_playlists!(), r#"
+            const music = Application('Music');
+            // FNV-1a（32bit）
+            function hashIds(ids) {
+                let h = 0x811c9dc5;
+                for (const id of ids) {
+                    for (let i = 0; i < id.length; i++) {
+                        h = Math.imul(h ^ id.charCodeAt(i), 0x01000193) >>> 0;
+                    }
                 }
+                return h.toString(16);
+            }
+            // ID の指定があればそのプレイリストだけ（見つからないものは飛ばす）
+            const playlists = args.ids
+                ? args.ids
+                    .map(id => music.playlists.whose({ persistentID: id }))
+                    .filter(found => found.length > 0)
+                    .map(found => found[0])
+                : listedPlaylists(music).map(({ playlist }) => playlist);
+            return JSON.stringify(playlists.map(p => {
+                try {
+                    const ids = p.tracks.persistentID();
+                    return {
+                        name: p.name(),
+                        persistent_id: p.persistentID(),
+                        fingerprint: ids.length + ':' + hashIds(ids),
+                    };
+                } catch (e) {
+                    return { error: String(e) };
+                }
+            }));
+        "#);
+        let args = serde_json::json!({ "ids": ids });
+        let (rows, malformed): (Vec<PlaylistFingerprintRow>, _) = Self::run_jxa_rows(script, args)?;
+        // 読めなかったプレイリストを削除扱いにしないよう、全体をエラーにする
+        if !malformed.is_empty() {
+            return Err(MusicError::Malformed(malformed.join("; ")).into());
+        }
+        Ok(rows
+            .into_iter()
+            .filter(|p| !EXCLUDED_PLAYLISTS.contains(&p.name.as_str()))
+            .map(|p| PlaylistFingerprint {
+                persistent_id: p.persistent_id,
+                name: p.name,
+                fingerprint: p.fingerprint,
             })
-            .collect();
-
-        Ok(playlists)
+            .collect())
     }
 
     /// プレイリストのトラックを取得
-    pub fn get_playlist_tracks(name: &str) -> Result<Vec<ListItem>> {
-        let script = format!(
-            r#"tell application "Music"
-                set output to ""
-                set trackList to every track of playlist "{}"
-                repeat with t in trackList
-                    set trackName to name of t
-                    set trackArtist to artist of t
-                    set trackAlbum to album of t
-                    set trackYear to year of t
-                    set trackTime to time of t
-                    set trackPlays to played count of t
-                    set trackFav to favorited of t
-                    set output to output & trackName & ":::" & trackArtist & ":::" & trackAlbum & ":::" & trackYear & ":::" & trackTime & ":::" & trackPlays & ":::" & trackFav & "|||"
-                end repeat
-                return output
-            end tell"#,
-            name.replace("\"", "\\\"")
-        );
-        let result = Self::run_script(&script)?;
-        let tracks: Vec<ListItem> = result
-            .split("|||")
-            .filter(|s| !s.is_empty())
-            .map(|item| {
-                let parts: Vec<&str> = item.split(":::").collect();
-                ListItem {
-                    name: parts.get(0).unwrap_or(&"").to_string(),
-                    artist: parts.get(1).unwrap_or(&"").to_string(),
-                    album: parts.get(2).unwrap_or(&"").to_string(),
-                    year: parts.get(3).unwrap_or(&"0").parse().unwrap_or(0),
-                    time: parts.get(4).unwrap_or(&"").to_string(),
-                    played_count: parts.get(5).unwrap_or(&"0").parse().unwrap_or(0),
-                    favorited: *parts.get(6).unwrap_or(&"false") == "true",
-                    track_number: 0,
+    /// 順番がコンテキスト再生の位置に使われるため、壊れた行があればエラーにする
+    fn get_playlist_tracks(&self, playlist: PlaylistRef) -> Result<Vec<ListItem>> {
+        let script = r#"
+            const music = Application('Music');
+            // persistent ID で特定し、古いキャッシュ（ID なし）の場合のみ名前で探す
+            const playlist = args.id
+                ? music.playlists.whose({ persistentID: args.id })[0]
+                : music.playlists.byName(args.name);
+            if (!playlist.exists()) {
+                throw Object.assign(new Error(args.name), { errorNumber: args.not_found });
+            }
+            return JSON.stringify(playlist.tracks().map(t => {
+                try {
+                    return {
+                        name: t.name(),
+                        artist: t.artist(),
+                        album: t.album(),
+                        year: t.year(),
+                        time: t.time(),
+                        played_count: t.playedCount(),
+                        favorited: t.favorited(),
+                        persistent_id: t.persistentID(),
+                    };
+                } catch (e) {
+                    return { error: String(e.message || e) };
                 }
+            }));
+        "#;
+        let (rows, malformed): (Vec<PlaylistTrackRow>, _) =
+            Self::run_jxa_rows(script, serde_json::json!({
+                "id": playlist.persistent_id,
+                "name": playlist.name,
+                "not_found": ERR_PLAYLIST_NOT_FOUND,
+            }))
+            .map_err(|e| error::with_playlist_id(e, playlist.persistent_id))?;
+        if !malformed.is_empty() {
+            return Err(MusicError::Malformed(format!("{}: {}", playlist.name, malformed.join("; "))).into());
+        }
+
+        let tracks: Vec<ListItem> = rows
+            .into_iter()
+            .map(|t| ListItem {
+                name: t.name,
+                artist: t.artist,
+                album: t.album,
+                year: t.year,
+                time: t.time,
+                played_count: t.played_count,
+                favorited: t.favorited,
+                track_number: 0,
+                persistent_id: t.persistent_id,
             })
             .collect();
         Ok(tracks)
     }
 
     /// 曲を再生
-    pub fn play_track(name: &str, artist: &str) -> Result<()> {
-        let escaped_name = name.replace("\"", "\\\"");
-        let escaped_artist = artist.replace("\"", "\\\"");
-        let script = format!(
+    fn play_track(&self, track: TrackRef) -> Result<()> {
+        let script = AppleScript::new(
             r#"tell application "Music"
-                set matchingTracks to (every track of library playlist 1 whose name is "{}" and artist is "{}")
-                if (count of matchingTracks) > 0 then
-                    play item 1 of matchingTracks
+                if $id is not "" then
+                    set matchingTracks to (every track of library playlist 1 whose persistent ID is $id)
+                else
+                    set matchingTracks to (every track of library playlist 1 whose name is $name and artist is $artist)
+                end if
+                if (count of matchingTracks) = 0 then
+                    error $name number $not_found
                 end if
+                play item 1 of matchingTracks
             end tell"#,
-            escaped_name, escaped_artist
-        );
+        )
+        .text("id", track.persistent_id)
+        .text("name", track.name)
+        .text("artist", track.artist)
+        .int("not_found", ERR_TRACK_NOT_FOUND.into())
+        .build()?;
         Self::run_script(&script)?;
         Ok(())
     }
+
+    /// ライブラリの総曲数を取得
+    fn get_total_track_count(&self) -> Result<usize> {
+        let script = r#"
+            tell application "Music"
+                return count of tracks of library playlist 1
+            end tell
+        "#;
+        let result = Self::run_script(script)?;
+        result
+            .parse()
+            .map_err(|_| MusicError::Malformed(format!("track count: {:?}", result)).into())
+    }
+
+    /// 指定範囲のトラックを取得（1-indexed）
+    fn get_tracks_batch(&self, start: usize, count: usize) -> Result<TrackBatch> {
+        let script = concat!(jxa_track_row!(), r#"
+            const tracks = Application('Music').libraryPlaylists[0].tracks;
+            const end = Math.min(args.start - 1 + args.count, tracks.length);
+            const rows = [];
+            for (let i = args.start - 1; i < end; i++) {
+                rows.push(trackRow(tracks[i]));
+            }
+            return JSON.stringify(rows);
+        "#);
+        let (tracks, malformed) =
+            Self::run_jxa_rows(script, serde_json::json!({ "start": start, "count": count }))?;
+        Ok(TrackBatch { tracks, malformed })
+    }
+
+    /// ライブラリ全体をプロパティの列（`name of every track` など）で取得
+    /// 1曲ずつ参照するより Apple Event の回数が桁違いに少ない
+    fn get_all_tracks(&self) -> Result<TrackBatch> {
+        let script = concat!(jxa_epoch_seconds!(), r#"
+            const tracks = Application('Music').libraryPlaylists[0].tracks;
+            return JSON.stringify({
+                name: tracks.name(),
+                artist: tracks.artist(),
+                album: tracks.album(),
+                album_artist: tracks.albumArtist(),
+                date_added: tracks.dateAdded().map(epochSeconds),
+                year: tracks.year(),
+                track_number: tracks.trackNumber(),
+                disc_number: tracks.discNumber(),
+                time: tracks.time(),
+                played_count: tracks.playedCount(),
+                favorited: tracks.favorited(),
+                rating: tracks.rating(),
+                played_date: tracks.playedDate().map(epochSeconds),
+                persistent_id: tracks.persistentID(),
+            });
+        "#);
+        let script = script::jxa_with_args(script, &serde_json::Value::Null);
+        let result = osascript::run_javascript_with_timeout(&script, LIBRARY_FETCH_TIMEOUT)?;
+        let columns: TrackColumns =
+            serde_json::from_str(&result).map_err(|e| MusicError::Malformed(e.to_string()))?;
+        columns.into_batch()
+    }
+
+    /// 指定日時以降に追加されたトラックを取得
+    fn get_tracks_added_since(&self, unix_timestamp: u64) -> Result<TrackBatch> {
+        // JS の Date は絶対時刻なので、タイムゾーンのオフセット計算は不要
+        let script = concat!(jxa_track_row!(), r#"
+            const cutoff = new Date(args.since * 1000);
+            const tracks = Application('Music').libraryPlaylists[0].tracks;
+            const recent = tracks.whose({ dateAdded: { _greaterThan: cutoff } })();
+            return JSON.stringify(recent.map(trackRow));
+        "#);
+        let (tracks, malformed) = Self::run_jxa_rows(script, serde_json::json!({ "since": unix_timestamp }))?;
+        Ok(TrackBatch { tracks, malformed })
+    }
+
+    /// 再生回数・お気に入り・レーティング・最終再生日を列でまとめて取得
+    fn get_track_stats(&self) -> Result<Vec<TrackStats>> {
+        let script = concat!(jxa_epoch_seconds!(), r#"
+            const tracks = Application('Music').libraryPlaylists[0].tracks;
+            return JSON.stringify({
+                persistent_id: tracks.persistentID(),
+                played_count: tracks.playedCount(),
+                favorited: tracks.favorited(),
+                rating: tracks.rating(),
+                played_date: tracks.playedDate().map(epochSeconds),
+            });
+        "#);
+        let script = script::jxa_with_args(script, &serde_json::Value::Null);
+        let result = osascript::run_javascript_with_timeout(&script, LIBRARY_FETCH_TIMEOUT)?;
+        let columns: StatsColumns =
+            serde_json::from_str(&result).map_err(|e| MusicError::Malformed(e.to_string()))?;
+
+        // 列の長さが揃わない場合（取得中にライブラリが変わった）は次の同期に回す
+        let len = columns.persistent_id.len();
+        if [columns.played_count.len(), columns.favorited.len(), columns.rating.len(), columns.played_date.len()]
+            .iter()
+            .any(|&l| l != len)
+        {
+            return Err(MusicError::Malformed("library changed while reading track stats".to_string()).into());
+        }
+        Ok((0..len)
+            .filter_map(|i| {
+                Some(TrackStats {
+                    persistent_id: columns.persistent_id[i].clone()?,
+                    played_count: columns.played_count[i].unwrap_or_default(),
+                    favorited: columns.favorited[i].unwrap_or_default(),
+                    rating: columns.rating[i].unwrap_or_default(),
+                    played_date: columns.played_date[i].unwrap_or_default(),
+                })
+            })
+            .collect())
+    }
+
+    /// 全曲の persistent ID を1回の Apple Event で取得
+    fn get_all_persistent_ids(&self) -> Result<Vec<String>> {
+        let script = r#"
+            return JSON.stringify(Application('Music').libraryPlaylists[0].tracks.persistentID());
+        "#;
+        Self::run_jxa_json(script, serde_json::Value::Null)
+    }
+
+    /// プレイリストを track_index 番目から巡回再生
+    fn play_playlist_with_context(&self, playlist: PlaylistRef, track_index: usize) -> Result<()> {
+        #[cfg(target_os = "macos")]
+        {
+            accessibility::play_playlist_with_context(playlist, track_index)
+        }
+        #[cfg(not(target_os = "macos"))]
+        {
+            let _ = (playlist, track_index);
+            anyhow::bail!("Playback with context requires macOS")
+        }
+    }
+
+    /// アルバムを track_index 番目から巡回再生
+    fn play_album_with_context(&self, album_name: &str, album_artist: &str, track_index: usize) -> Result<()> {
+        #[cfg(target_os = "macos")]
+        {
+            accessibility::play_album_with_context(album_name, album_artist, track_index)
+        }
+        #[cfg(not(target_os = "macos"))]
+        {
+            let _ = (album_name, album_artist, track_index);
+            anyhow::bail!("Playback with context requires macOS")
+        }
+    }
+
+    /// 曲の並びを track_index 番目から巡回再生
+    fn play_tracks_with_context(&self, tracks: &[TrackRef], track_index: usize) -> Result<()> {
+        #[cfg(target_os = "macos")]
+        {
+            accessibility::play_tracks_with_context(tracks, track_index)
+        }
+        #[cfg(not(target_os = "macos"))]
+        {
+            let _ = (tracks, track_index);
+            anyhow::bail!("Playback with context requires macOS")
+        }
+    }
+
+    /// プレイリストに曲を追加
+    fn add_track_to_playlist(&self, track: TrackRef, playlist: PlaylistRef) -> Result<()> {
+        let script = AppleScript::new(
+            r#"tell application "Music"
+                if $track_id is not "" then
+                    set matchingTracks to (every track of library playlist 1 whose persistent ID is $track_id)
+                else
+                    set matchingTracks to (every track of library playlist 1 whose name is $track and album is $album)
+                end if
+                if (count of matchingTracks) = 0 then
+                    error $track number $track_not_found
+                end if
+                if $playlist_id is not "" then
+                    set matchingPlaylists to (every playlist whose persistent ID is $playlist_id)
+                else
+                    set matchingPlaylists to (every playlist whose name is $playlist)
+                end if
+                if (count of matchingPlaylists) = 0 then
+                    error $playlist number $playlist_not_found
+                end if
+                duplicate (item 1 of matchingTracks) to (item 1 of matchingPlaylists)
+            end tell"#,
+        )
+        .text("track_id", track.persistent_id)
+        .text("track", track.name)
+        .text("album", track.album)
+        .text("playlist_id", playlist.persistent_id)
+        .text("playlist", playlist.name)
+        .int("track_not_found", ERR_TRACK_NOT_FOUND.into())
+        .int("playlist_not_found", ERR_PLAYLIST_NOT_FOUND.into())
+        .build()?;
+        Self::run_script(&script).map_err(|e| error::with_playlist_id(e, playlist.persistent_id))?;
+        Ok(())
+    }
+
+    /// 新規プレイリストを作成して曲を追加
+    fn create_playlist_and_add_track(&self, playlist_name: &str, track: TrackRef) -> Result<String> {
+        let script = AppleScript::new(
+            r#"tell application "Music"
+                -- 曲が見つからない場合は空のプレイリストを作らない
+                if $track_id is not "" then
+                    set matchingTracks to (every track of library playlist 1 whose persistent ID is $track_id)
+                else
+                    set matchingTracks to (every track of library playlist 1 whose name is $track and album is $album)
+                end if
+                if (count of matchingTracks) = 0 then
+                    error $track number $track_not_found
+                end if
+                set newPlaylist to make new playlist with properties {name:$playlist}
+                duplicate (item 1 of matchingTracks) to newPlaylist
+                return persistent ID of newPlaylist
+            end tell"#,
+        )
+        .text("playlist", playlist_name)
+        .text("track_id", track.persistent_id)
+        .text("track", track.name)
+        .text("album", track.album)
+        .int("track_not_found", ERR_TRACK_NOT_FOUND.into())
+        .build()?;
+        Self::run_script(&script)
+    }
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Deserialize)]
 pub struct TrackInfo {
     pub name: String,
     pub artist: String,
@@ -256,7 +733,7 @@ pub struct TrackInfo {
     pub is_playing: bool,
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Deserialize)]
 pub struct PlayerState {
     pub track: TrackInfo,
     pub volume: i32,
@@ -275,6 +752,8 @@ pub struct ListItem {
     pub track_number: u32,
     pub played_count: u32,
     pub favorited: bool,
+    // 曲・プレイリストの persistent ID（アルバム一覧や古いキャッシュでは空）
+    pub persistent_id: String,
 }
 
 impl TrackInfo {
@@ -290,140 +769,93 @@ impl TrackInfo {
 }
 
 /// キャッシュ用のシンプルなトラック情報
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct SimpleTrack {
     pub name: String,
     pub artist: String,
     pub album: String,
-    pub date_added: String,
+    #[serde(default)]
+    pub album_artist: String,
+    pub date_added: u64,  // 追加日（Unix timestamp）
     pub year: u32,
     pub track_number: u32,
     pub disc_number: u32,
     pub time: String,
     pub played_count: u32,
     pub favorited: bool,
+    #[serde(default)]
+    pub rating: u32,  // 0〜100
+    #[serde(default)]
+    pub played_date: u64,  // 最終再生日（Unix timestamp、未再生は 0）
+    pub persistent_id: String,
 }
 
-impl MusicController {
-    /// ライブラリの総曲数を取得
-    pub fn get_total_track_count() -> Result<usize> {
-        let script = r#"
-            tell application "Music"
-                return count of tracks of library playlist 1
-            end tell
-        "#;
-        let result = Self::run_script(script)?;
-        Ok(result.parse().unwrap_or(0))
+/// 再生で変わるプロパティ（定期同期用）
+#[derive(Debug, Clone)]
+pub struct TrackStats {
+    pub persistent_id: String,
+    pub played_count: u32,
+    pub favorited: bool,
+    pub rating: u32,
+    pub played_date: u64,
+}
+
+/// プレイリストの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistKind {
+    #[default]
+    User,
+    Smart,
+    Folder,
+    Subscription,  // Apple Music のプレイリスト
+}
+
+impl PlaylistKind {
+    /// 曲を追加できるか（スマート・フォルダ・Apple Music のプレイリストは読み取り専用）
+    pub fn is_editable(self) -> bool {
+        self == PlaylistKind::User
     }
+}
 
-    /// 指定範囲のトラックを取得（1-indexed）
-    pub fn get_tracks_batch(start: usize, count: usize) -> Result<Vec<SimpleTrack>> {
-        let script = format!(
-            r#"tell application "Music"
-                set output to ""
-                set trackList to every track of library playlist 1
-                set totalCount to count of trackList
-                set endIndex to {} + {} - 1
-                if endIndex > totalCount then
-                    set endIndex to totalCount
-                end if
-                if {} > totalCount then
-                    return ""
-                end if
-                repeat with i from {} to endIndex
-                    set t to item i of trackList
-                    set dateStr to (date added of t) as string
-                    set yr to year of t
-                    set tn to track number of t
-                    set dn to disc number of t
-                    set tm to time of t
-                    set pc to played count of t
-                    set fav to favorited of t
-                    set output to output & name of t & ":::" & artist of t & ":::" & album of t & ":::" & dateStr & ":::" & yr & ":::" & tn & ":::" & dn & ":::" & tm & ":::" & pc & ":::" & fav & "|||"
-                end repeat
-                return output
-            end tell"#,
-            start, count, start, start
-        );
-        let result = Self::run_script(&script)?;
-
-        let tracks: Vec<SimpleTrack> = result
-            .split("|||")
-            .filter(|s| !s.is_empty())
-            .map(|s| {
-                let parts: Vec<&str> = s.split(":::").collect();
-                SimpleTrack {
-                    name: parts.get(0).unwrap_or(&"").to_string(),
-                    artist: parts.get(1).unwrap_or(&"").to_string(),
-                    album: parts.get(2).unwrap_or(&"").to_string(),
-                    date_added: parts.get(3).unwrap_or(&"").to_string(),
-                    year: parts.get(4).unwrap_or(&"0").parse().unwrap_or(0),
-                    track_number: parts.get(5).unwrap_or(&"0").parse().unwrap_or(0),
-                    disc_number: parts.get(6).unwrap_or(&"0").parse().unwrap_or(0),
-                    time: parts.get(7).unwrap_or(&"").to_string(),
-                    played_count: parts.get(8).unwrap_or(&"0").parse().unwrap_or(0),
-                    favorited: *parts.get(9).unwrap_or(&"false") == "true",
-                }
-            })
-            .collect();
+/// プレイリスト・フォルダの一覧の1件
+/// parent_id は親フォルダの persistent ID（最上位なら空）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaylistInfo {
+    pub name: String,
+    pub persistent_id: String,
+    #[serde(default)]
+    pub parent_id: String,
+    #[serde(default)]
+    pub kind: PlaylistKind,
+}
 
-        Ok(tracks)
+impl PlaylistInfo {
+    /// 最上位のユーザプレイリスト
+    pub fn top_level(name: String, persistent_id: String) -> Self {
+        Self { name, persistent_id, parent_id: String::new(), kind: PlaylistKind::User }
     }
+}
 
-    /// 指定日時以降に追加されたトラックを取得
-    pub fn get_tracks_added_since(unix_timestamp: u64) -> Result<Vec<SimpleTrack>> {
-        // Unix timestamp を AppleScript の日付形式に変換
-        // AppleScriptは現在時刻と基準日の差分を使って正確なオフセットを計算
-        let script = format!(
-            r#"tell application "Music"
-                set output to ""
-                set unixTs to {}
-                set baseDate to date "Monday, January 1, 2001 at 12:00:00 AM"
-                -- 現在の Unix timestamp と AppleScript の (current date - baseDate) の差分からオフセットを計算
-                set currentUnix to (do shell script "date +%s") as integer
-                set currentAppleSeconds to (current date) - baseDate
-                set offsetVal to currentAppleSeconds - (currentUnix - 978307200)
-                -- 正しい cutoffDate を計算
-                set cutoffDate to baseDate + (unixTs - 978307200) + offsetVal
-                set recentTracks to (every track of library playlist 1 whose date added > cutoffDate)
-                repeat with t in recentTracks
-                    try
-                        set dateStr to (date added of t) as string
-                        set yr to year of t
-                        set tn to track number of t
-                        set dn to disc number of t
-                        set tm to time of t
-                        set pc to played count of t
-                        set fav to favorited of t
-                        set output to output & name of t & ":::" & artist of t & ":::" & album of t & ":::" & dateStr & ":::" & yr & ":::" & tn & ":::" & dn & ":::" & tm & ":::" & pc & ":::" & fav & "|||"
-                    end try
-                end repeat
-                return output
-            end tell"#,
-            unix_timestamp
-        );
-        let result = Self::run_script(&script)?;
-
-        let tracks: Vec<SimpleTrack> = result
-            .split("|||")
-            .filter(|s| !s.is_empty())
-            .map(|s| {
-                let parts: Vec<&str> = s.split(":::").collect();
-                SimpleTrack {
-                    name: parts.get(0).unwrap_or(&"").to_string(),
-                    artist: parts.get(1).unwrap_or(&"").to_string(),
-                    album: parts.get(2).unwrap_or(&"").to_string(),
-                    date_added: parts.get(3).unwrap_or(&"").to_string(),
-                    year: parts.get(4).unwrap_or(&"0").parse().unwrap_or(0),
-                    track_number: parts.get(5).unwrap_or(&"0").parse().unwrap_or(0),
-                    disc_number: parts.get(6).unwrap_or(&"0").parse().unwrap_or(0),
-                    time: parts.get(7).unwrap_or(&"").to_string(),
-                    played_count: parts.get(8).unwrap_or(&"0").parse().unwrap_or(0),
-                    favorited: *parts.get(9).unwrap_or(&"false") == "true",
-                }
-            })
-            .collect();
+/// プレイリストの変更検出用の値（キャッシュとの比較で再取得を判断する）
+#[derive(Debug, Clone)]
+pub struct PlaylistFingerprint {
+    pub persistent_id: String,
+    pub name: String,
+    pub fingerprint: String,
+}
 
-        Ok(tracks)
+/// ライブラリから取得したトラックの一群
+/// 解釈できなかった行は tracks に含めず、理由を malformed に残す
+#[derive(Debug, Default)]
+pub struct TrackBatch {
+    pub tracks: Vec<SimpleTrack>,
+    pub malformed: Vec<String>,
+}
+
+impl TrackBatch {
+    /// 取得した行数（壊れた行も含む）
+    pub fn row_count(&self) -> usize {
+        self.tracks.len() + self.malformed.len()
     }
 }
@@ -0,0 +1,191 @@
+// Text normalization for search: NFKC, diacritic stripping, hiragana → katakana and case folding
+// The index keys and the query terms go through the same functions; quoted (exact) terms skip them
+
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// 大文字小文字は残して正規化する
+/// NFKC（半角カナ・全角英数をそろえる）、アクセント記号の除去（é → e）、ひらがな → カタカナ
+/// 仮名の濁点・半濁点は残す（ガ と カ は別の文字）
+pub fn normalize(text: &str) -> String {
+    if text.is_ascii() {
+        return text.to_string();
+    }
+    text.nfkd()
+        .filter(|&c| !is_stripped_mark(c))
+        .nfc()
+        .map(hiragana_to_katakana)
+        .collect()
+}
+
+/// 1文字ずつ小文字にそろえる
+/// 文字単位の変換なので「a が b を含む → fold_case(a) が fold_case(b) を含む」が常に成り立つ
+/// （検索キーとクエリのほか、search_index の trigram もこれでそろえる）
+pub fn fold_case(text: &str) -> String {
+    fold_chars(text).collect()
+}
+
+/// fold_case の文字列を作らない版
+/// str::to_lowercase は語末のシグマだけ ς にするため、σ にそろえる
+pub fn fold_chars(text: &str) -> impl Iterator<Item = char> + '_ {
+    text.chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| if c == 'ς' { 'σ' } else { c })
+}
+
+/// normalize(text) の各文字が text の何文字目から来たか（あいまい検索の一致位置を元の文字列に戻すため）
+/// 結合文字（半角の濁点やアクセント記号など）は直前の文字とまとめて正規化する
+pub fn source_positions(text: &str) -> Vec<usize> {
+    if text.is_ascii() {
+        return (0..text.len()).collect();
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let mut positions = Vec::with_capacity(chars.len());
+    let mut start = 0;
+    while start < chars.len() {
+        let mut end = start + 1;
+        while end < chars.len() && starts_with_mark(chars[end]) {
+            end += 1;
+        }
+        let cluster: String = chars[start..end].iter().collect();
+        positions.extend(std::iter::repeat_n(start, normalize(&cluster).chars().count()));
+        start = end;
+    }
+    positions
+}
+
+/// 分解すると結合文字で始まるか（ﾞ は U+3099 になる）
+fn starts_with_mark(c: char) -> bool {
+    std::iter::once(c).nfkd().next().is_some_and(is_combining_mark)
+}
+
+fn is_stripped_mark(c: char) -> bool {
+    is_combining_mark(c) && c != '\u{3099}' && c != '\u{309A}'
+}
+
+/// ぁ〜ゖ と ゝゞ をカタカナに
+fn hiragana_to_katakana(c: char) -> char {
+    match c {
+        '\u{3041}'..='\u{3096}' | '\u{309D}' | '\u{309E}' => char::from_u32(c as u32 + 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+/// トラックの検索キー: name / artist / album を正規化して空白でつないだもの
+#[derive(Debug, Clone, Default)]
+pub struct SearchKey {
+    /// 大文字小文字もそろえたもの
+    pub folded: String,
+    /// 大文字小文字は残したもの（大文字を含む検索語用）
+    pub cased: String,
+    // name と artist の終わりのバイト位置（folded, cased それぞれ）
+    folded_ends: [usize; 2],
+    cased_ends: [usize; 2],
+}
+
+impl SearchKey {
+    pub fn new(fields: [&str; 3]) -> Self {
+        let cased_fields = fields.map(normalize);
+        let folded_fields = cased_fields.each_ref().map(|f| fold_case(f));
+        let (cased, cased_ends) = join(&cased_fields);
+        let (folded, folded_ends) = join(&folded_fields);
+        Self { folded, cased, folded_ends, cased_ends }
+    }
+
+    /// 0: name, 1: artist, 2: album
+    pub fn field(&self, field: usize, cased: bool) -> &str {
+        let (key, ends) = if cased { (&self.cased, &self.cased_ends) } else { (&self.folded, &self.folded_ends) };
+        match field {
+            0 => &key[..ends[0]],
+            1 => &key[ends[0] + 1..ends[1]],
+            _ => &key[ends[1] + 1..],
+        }
+    }
+}
+
+fn join(fields: &[String; 3]) -> (String, [usize; 2]) {
+    let name_end = fields[0].len();
+    let artist_end = name_end + 1 + fields[1].len();
+    (fields.join(" "), [name_end, artist_end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CachedTrack;
+    use crate::query::Query;
+
+    /// 検索語が検索キーに含まれるか（query.rs と同じスマートケース）
+    fn finds(word: &str, text: &str) -> bool {
+        let key = SearchKey::new([text, "", ""]);
+        let word = normalize(word);
+        if word.chars().any(char::is_uppercase) {
+            key.field(0, true).contains(word.as_str())
+        } else {
+            key.field(0, false).contains(fold_case(&word).as_str())
+        }
+    }
+
+    #[test]
+    fn half_width_and_full_width_are_unified() {
+        assert_eq!(normalize("ｱｲ"), "アイ");
+        assert_eq!(normalize("ｶﾞｰﾙ"), "ガール");
+        assert_eq!(normalize("ＡＢＣ１２３"), "ABC123");
+        assert!(finds("ｱｲ", "アイのうた"));
+        assert!(finds("アイ", "ｱｲ"));
+    }
+
+    #[test]
+    fn hiragana_and_katakana_match_each_other() {
+        assert_eq!(normalize("あいうゞ"), "アイウヾ");
+        assert!(finds("さくら", "サクラ"));
+        assert!(finds("サクラ", "さくら"));
+        // 濁点は残す
+        assert!(!finds("か", "ガ"));
+    }
+
+    #[test]
+    fn diacritics_are_stripped_and_case_folded() {
+        assert_eq!(normalize("Beyoncé"), "Beyonce");
+        assert_eq!(normalize("Beyonce\u{301}"), "Beyonce");
+        assert!(finds("beyonce", "Beyoncé"));
+        assert!(finds("Beyonce", "Beyoncé"));
+        assert!(!finds("BEYONCE", "Beyoncé"));
+        assert_eq!(fold_case("ΣΟΦΟΣ"), "σοφοσ");
+        assert_eq!(fold_case("Straße"), "straße");
+    }
+
+    #[test]
+    fn fields_are_sliced_from_the_key() {
+        let key = SearchKey::new(["Ｌｏｖｅ Song", "Beyoncé", "さくら"]);
+        assert_eq!(key.folded, "love song beyonce サクラ");
+        assert_eq!(key.field(0, true), "Love Song");
+        assert_eq!(key.field(1, true), "Beyonce");
+        assert_eq!(key.field(1, false), "beyonce");
+        assert_eq!(key.field(2, false), "サクラ");
+
+        let empty = SearchKey::new(["", "", ""]);
+        assert_eq!((empty.field(0, false), empty.field(1, false), empty.field(2, false)), ("", "", ""));
+    }
+
+    #[test]
+    fn source_positions_point_into_the_raw_text() {
+        assert_eq!(source_positions("abc"), vec![0, 1, 2]);
+        assert_eq!(source_positions("ｶﾞｰﾙ"), vec![0, 2, 3]);
+        assert_eq!(source_positions("e\u{301}x"), vec![0, 2]);
+    }
+
+    #[test]
+    fn exact_mode_bypasses_folding() {
+        let track = |name: &str| {
+            CachedTrack::new(name.into(), "Artist".into(), "Album".into(), String::new(), 0, 0, 0, 0, String::new(), 0, false, 0, 0, String::new())
+        };
+        let matches = |query: &str, name: &str| Query::parse(query).unwrap().matches(&track(name));
+        assert!(matches("beyonce", "Beyoncé"));
+        assert!(matches("\"Beyoncé\"", "Beyoncé"));
+        assert!(!matches("\"beyonce\"", "Beyoncé"));
+        assert!(!matches("\"アイ\"", "ｱｲ"));
+        assert!(matches("name:\"ｱｲ\"", "ｱｲ"));
+        assert!(!matches("name:\"ｱｲ\"", "アイ"));
+    }
+}
@@ -0,0 +1,386 @@
+// Long-lived osascript sessions
+// Spawning osascript for every poll is expensive, so each session keeps one
+// interpreter alive and runs AppleScript sent over stdin (one JSON line per request/response)
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::MusicError;
+
+/// 通常のスクリプトのタイムアウト
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// セッション内で動く JXA
+/// 1行ずつ {id, language, script} を読み、実行して {id, ok, result|error} を返す
+/// AppleScript は NSAppleScript で実行し、結果の文字列化は `osascript -e` の出力に合わせる
+/// JavaScript は関数本体として評価し、戻り値を文字列で返す
+const SESSION_SCRIPT: &str = r#"
+ObjC.import('Foundation');
+
+const stdin = $.NSFileHandle.fileHandleWithStandardInput;
+const stdout = $.NSFileHandle.fileHandleWithStandardOutput;
+
+function fourcc(s) {
+    return (s.charCodeAt(0) << 24) | (s.charCodeAt(1) << 16) | (s.charCodeAt(2) << 8) | s.charCodeAt(3);
+}
+
+function describe(desc) {
+    switch (desc.descriptorType) {
+        case fourcc('null'): return '';
+        case fourcc('true'): return 'true';
+        case fourcc('fals'): return 'false';
+        case fourcc('bool'): return desc.booleanValue ? 'true' : 'false';
+        case fourcc('long'): return String(desc.int32Value);
+        case fourcc('doub'): return String(desc.doubleValue);
+        case fourcc('list'): {
+            const items = [];
+            for (let i = 1; i <= desc.numberOfItems; i++) {
+                items.push(describe(desc.descriptorAtIndex(i)));
+            }
+            return items.join(', ');
+        }
+    }
+    const s = desc.stringValue;
+    return s.isNil() ? '' : s.js;
+}
+
+function execute(source) {
+    const error = Ref();
+    const script = $.NSAppleScript.alloc.initWithSource(source);
+    const desc = script.executeAndReturnError(error);
+    if (desc.isNil()) {
+        const info = error[0];
+        const message = ObjC.unwrap(info.objectForKey('NSAppleScriptErrorMessage')) || 'unknown error';
+        const number = ObjC.unwrap(info.objectForKey('NSAppleScriptErrorNumber'));
+        return { ok: false, error: 'execution error: ' + message + (number === undefined ? '' : ' (' + number + ')') };
+    }
+    return { ok: true, result: describe(desc) };
+}
+
+function executeJavaScript(source) {
+    try {
+        const value = (0, eval)('(function() {\n' + source + '\n})()');
+        return { ok: true, result: value === undefined || value === null ? '' : String(value) };
+    } catch (e) {
+        const message = (e && e.message) || String(e);
+        const number = e && e.errorNumber;
+        return { ok: false, error: 'execution error: ' + message + (number === undefined ? '' : ' (' + number + ')') };
+    }
+}
+
+function write(obj) {
+    const line = $(JSON.stringify(obj) + '\n');
+    stdout.writeData(line.dataUsingEncoding($.NSUTF8StringEncoding));
+}
+
+function run() {
+    // 受け取ったバイト列のまま溜め、改行（0x0A）までそろった行だけを文字列にする
+    // （読み取りの区切りが UTF-8 の途中に来ても壊れない）
+    const buffer = $.NSMutableData.data;
+    const newline = $('\n').dataUsingEncoding($.NSUTF8StringEncoding);
+    while (true) {
+        const data = stdin.availableData;
+        if (data.length === 0) return;
+        buffer.appendData(data);
+
+        while (true) {
+            const found = buffer.rangeOfDataOptionsRange(newline, 0, $.NSMakeRange(0, buffer.length));
+            if (found.length === 0) break;
+            const end = found.location;
+            const bytes = buffer.subdataWithRange($.NSMakeRange(0, end));
+            buffer.setData(buffer.subdataWithRange($.NSMakeRange(end + 1, buffer.length - end - 1)));
+            if (end === 0) continue;
+
+            const line = $.NSString.alloc.initWithDataEncoding(bytes, $.NSUTF8StringEncoding).js;
+            const request = JSON.parse(line);
+            const response = request.language === 'JavaScript'
+                ? executeJavaScript(request.script)
+                : execute(request.script);
+            response.id = request.id;
+            write(response);
+        }
+    }
+}
+"#;
+
+/// スクリプト言語
+#[derive(Debug, Clone, Copy, Serialize)]
+pub enum Language {
+    AppleScript,
+    JavaScript,
+}
+
+#[derive(Serialize)]
+struct Request<'a> {
+    id: u64,
+    language: Language,
+    script: &'a str,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    id: u64,
+    ok: bool,
+    #[serde(default)]
+    result: String,
+    #[serde(default)]
+    error: String,
+}
+
+/// セッション実行の失敗理由
+enum SessionError {
+    /// インタプリタが既に終了していた（スクリプトは実行されていない）
+    Dead,
+    /// 実行中にインタプリタが終了した／タイムアウトした
+    Lost(anyhow::Error),
+}
+
+/// 1つの osascript プロセス
+struct Session {
+    child: Child,
+    stdin: ChildStdin,
+    responses: Receiver<Response>,
+    next_id: u64,
+}
+
+impl Session {
+    fn spawn() -> Result<Self> {
+        let mut command = Command::new("osascript");
+        command.args(["-l", "JavaScript", "-e", SESSION_SCRIPT]);
+        Self::start(command)
+    }
+
+    /// インタプリタを起動し、標準入出力で1行1つの JSON をやり取りする（テストでは代わりのコマンドを渡す）
+    fn start(mut command: Command) -> Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("Failed to start osascript")?;
+
+        let stdin = child.stdin.take().context("osascript stdin unavailable")?;
+        let stdout = child.stdout.take().context("osascript stdout unavailable")?;
+
+        // 応答の読み取りスレッド（EOF で終了し、チャネルが閉じる）
+        let (tx, responses) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if let Ok(response) = serde_json::from_str::<Response>(&line) {
+                    if tx.send(response).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Self { child, stdin, responses, next_id: 0 })
+    }
+
+    fn is_alive(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    fn run(&mut self, language: Language, script: &str, timeout: Duration) -> std::result::Result<Response, SessionError> {
+        self.next_id += 1;
+        let id = self.next_id;
+
+        let line = encode_request(id, language, script).map_err(SessionError::Lost)?;
+        if self.stdin.write_all(line.as_bytes()).and_then(|_| self.stdin.flush()).is_err() {
+            return Err(SessionError::Dead);
+        }
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.responses.recv_timeout(remaining) {
+                Ok(response) if response.id == id => return Ok(response),
+                // タイムアウト後に届いた古い応答は捨てる
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(SessionError::Lost(MusicError::Timeout.into()));
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(SessionError::Lost(anyhow::anyhow!("osascript exited unexpectedly")));
+                }
+            }
+        }
+    }
+}
+
+/// 要求を1行の JSON にする（スクリプト中の改行はエスケープされ、行末の改行だけが区切りになる）
+fn encode_request(id: u64, language: Language, script: &str) -> Result<String> {
+    let mut line = serde_json::to_string(&Request { id, language, script })?;
+    line.push('\n');
+    Ok(line)
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// 待機中のセッション
+/// ワーカースレッドが同時に呼んでも互いを待たないよう、呼び出しごとに1つ借りて返す
+static IDLE_SESSIONS: Mutex<Vec<Session>> = Mutex::new(Vec::new());
+
+fn take_session() -> Result<Session> {
+    let mut idle = IDLE_SESSIONS.lock().unwrap_or_else(|e| e.into_inner());
+    while let Some(mut session) = idle.pop() {
+        if session.is_alive() {
+            return Ok(session);
+        }
+    }
+    drop(idle);
+    Session::spawn()
+}
+
+/// 待機させておくセッションの上限（超えた分は返却時に終了させる）
+const MAX_IDLE_SESSIONS: usize = 4;
+
+fn return_session(session: Session) {
+    let mut idle = IDLE_SESSIONS.lock().unwrap_or_else(|e| e.into_inner());
+    if idle.len() < MAX_IDLE_SESSIONS {
+        idle.push(session);
+    }
+}
+
+/// AppleScript を実行して結果を文字列で返す
+pub fn run(script: &str) -> Result<String> {
+    run_with_timeout(script, DEFAULT_TIMEOUT)
+}
+
+/// タイムアウトを指定して AppleScript を実行
+pub fn run_with_timeout(script: &str, timeout: Duration) -> Result<String> {
+    execute(Language::AppleScript, script, timeout)
+}
+
+/// JXA を実行して戻り値を文字列で返す（script は関数本体として評価される）
+pub fn run_javascript(script: &str) -> Result<String> {
+    run_javascript_with_timeout(script, DEFAULT_TIMEOUT)
+}
+
+/// タイムアウトを指定して JXA を実行
+pub fn run_javascript_with_timeout(script: &str, timeout: Duration) -> Result<String> {
+    execute(Language::JavaScript, script, timeout)
+}
+
+/// スクリプトのエラーは MusicError に分類して返す
+/// タイムアウトしたセッションは終了させ、次回の呼び出しで作り直す
+fn execute(language: Language, script: &str, timeout: Duration) -> Result<String> {
+    let mut session = take_session()?;
+    let response = match session.run(language, script, timeout) {
+        Ok(response) => response,
+        // 送信前に死んでいた場合は作り直して1回だけ再送
+        Err(SessionError::Dead) => {
+            session = Session::spawn()?;
+            match session.run(language, script, timeout) {
+                Ok(response) => response,
+                Err(SessionError::Dead) => anyhow::bail!("osascript exited unexpectedly"),
+                Err(SessionError::Lost(e)) => return Err(e),
+            }
+        }
+        Err(SessionError::Lost(e)) => return Err(e),
+    };
+    return_session(session);
+
+    if response.ok {
+        Ok(response.result.trim().to_string())
+    } else {
+        Err(MusicError::from_script_error(&response.error).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// osascript の代わりに sh で動くセッション
+    fn fake_session(script: &str) -> Session {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        Session::start(command).unwrap()
+    }
+
+    fn run(session: &mut Session, timeout_ms: u64) -> std::result::Result<Response, SessionError> {
+        session.run(Language::AppleScript, "return 1", Duration::from_millis(timeout_ms))
+    }
+
+    #[test]
+    fn requests_are_one_json_line() {
+        let line = encode_request(7, Language::JavaScript, "const a = \"é\";\nreturn a;\r\n").unwrap();
+        assert!(line.ends_with('\n'));
+        assert_eq!(line.matches('\n').count(), 1);
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["id"], 7);
+        assert_eq!(value["language"], "JavaScript");
+        assert_eq!(value["script"], "const a = \"é\";\nreturn a;\r\n");
+    }
+
+    #[test]
+    fn responses_are_matched_by_id() {
+        // 解釈できない行と他の要求への応答は読み飛ばす
+        let mut session = fake_session(
+            r#"read line
+            echo 'not json'
+            echo '{"id":99,"ok":true,"result":"other"}'
+            echo '{"id":1,"ok":true,"result":"a, b"}'
+            read line
+            echo '{"id":2,"ok":false,"error":"execution error: Nope (-1728)"}'
+            sleep 5"#,
+        );
+        let Ok(response) = run(&mut session, 5000) else { panic!("no response") };
+        assert!(response.ok);
+        assert_eq!((response.id, response.result.as_str()), (1, "a, b"));
+        let Ok(response) = run(&mut session, 5000) else { panic!("no response") };
+        assert!(!response.ok);
+        assert_eq!(response.error, "execution error: Nope (-1728)");
+    }
+
+    #[test]
+    fn timeout_drops_the_late_response() {
+        let mut session = fake_session(
+            r#"read line
+            sleep 1
+            echo '{"id":1,"ok":true,"result":"late"}'
+            read line
+            echo '{"id":2,"ok":true,"result":"fresh"}'
+            sleep 5"#,
+        );
+        let started = Instant::now();
+        match run(&mut session, 100) {
+            Err(SessionError::Lost(e)) => assert_eq!(e.downcast_ref::<MusicError>(), Some(&MusicError::Timeout)),
+            _ => panic!("expected a timeout"),
+        }
+        assert!(started.elapsed() < Duration::from_millis(900));
+
+        // 遅れて届いた1回目の応答は捨て、2回目の応答を返す
+        let Ok(response) = run(&mut session, 5000) else { panic!("no response") };
+        assert_eq!(response.result, "fresh");
+    }
+
+    #[test]
+    fn exited_interpreter_is_reported() {
+        // 実行中に終了した
+        let mut session = fake_session("read line");
+        match run(&mut session, 5000) {
+            Err(SessionError::Lost(e)) => assert!(e.to_string().contains("exited unexpectedly")),
+            _ => panic!("expected the session to be lost"),
+        }
+
+        // 送る前に終了していた（スクリプトは実行されていない）
+        let mut session = fake_session("exit 0");
+        while session.is_alive() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(matches!(run(&mut session, 5000), Err(SessionError::Dead)));
+    }
+}
@@ -0,0 +1,238 @@
+// Playlists pane tree: folder playlists and their children, flattened into visible rows
+// Folding only changes which rows are visible; the full list stays in App::playlist_tree
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::backend::PlaylistRef;
+use crate::cache::SavedSearch;
+use crate::music::{PlaylistInfo, PlaylistKind};
+
+/// 保存した検索の見出し行の ID（フォルダと同じく開閉し、collapsed_playlist_folders に入る）
+pub const SAVED_SEARCHES_ID: &str = "saved-searches";
+const SAVED_SEARCH_ID_PREFIX: &str = "saved-search:";
+
+/// Playlists ペインの行の種類（保存した検索は Music.app にないので、プレイリストの種類とは分ける）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowKind {
+    Playlist(PlaylistKind),
+    SavedSearch,
+}
+
+impl RowKind {
+    /// 曲を追加できるか（保存した検索には追加できない）
+    pub fn is_editable(self) -> bool {
+        matches!(self, RowKind::Playlist(kind) if kind.is_editable())
+    }
+}
+
+/// Playlists ペインの1行
+#[derive(Debug, Clone)]
+pub struct PlaylistTreeRow {
+    pub name: String,
+    pub persistent_id: String,
+    pub parent_id: String,
+    pub depth: usize,
+    pub kind: RowKind,
+    pub expanded: bool,
+}
+
+impl PlaylistTreeRow {
+    pub fn is_folder(&self) -> bool {
+        self.kind == RowKind::Playlist(PlaylistKind::Folder)
+    }
+
+    /// 保存した検索の行なら、その名前
+    pub fn saved_search_name(&self) -> Option<&str> {
+        (self.kind == RowKind::SavedSearch).then(|| &self.persistent_id[SAVED_SEARCH_ID_PREFIX.len()..])
+    }
+
+    /// プレイリストとして参照する
+    pub fn playlist_ref(&self) -> PlaylistRef<'_> {
+        PlaylistRef {
+            persistent_id: &self.persistent_id,
+            name: &self.name,
+        }
+    }
+}
+
+/// 表示する行を親の直後に深さ付きで並べる（折りたたまれたフォルダの中身は出さない）
+/// 兄弟の順は一覧の順のまま。親が一覧にないプレイリストは最上位に置く
+/// 親をたどると循環する（最上位に届かない）ものは、循環に戻ってきた項目を最上位に置く
+/// include が false のプレイリストは出さない（フォルダは常に出す）
+pub fn visible_rows(
+    playlists: &[PlaylistInfo],
+    collapsed: &BTreeSet<String>,
+    include: impl Fn(&PlaylistInfo) -> bool,
+) -> Vec<PlaylistTreeRow> {
+    let included: Vec<&PlaylistInfo> =
+        playlists.iter().filter(|p| p.kind == PlaylistKind::Folder || include(p)).collect();
+    let ids: HashSet<&str> = included.iter().map(|p| p.persistent_id.as_str()).collect();
+
+    let mut children: HashMap<&str, Vec<&PlaylistInfo>> = HashMap::new();
+    let mut parents: HashMap<&str, &str> = HashMap::new();
+    let mut roots: HashSet<&str> = HashSet::new();
+    for &playlist in &included {
+        let parent = playlist.parent_id.as_str();
+        if !parent.is_empty() && parent != playlist.persistent_id && ids.contains(parent) {
+            children.entry(parent).or_default().push(playlist);
+            parents.insert(&playlist.persistent_id, parent);
+        } else {
+            roots.insert(&playlist.persistent_id);
+        }
+    }
+
+    // 最上位から届かない項目は親をたどり、2度目に出てきた項目を最上位にする
+    let mut reachable = HashSet::new();
+    for &root in &roots {
+        mark_reachable(root, &children, &mut reachable);
+    }
+    for playlist in &included {
+        if reachable.contains(playlist.persistent_id.as_str()) {
+            continue;
+        }
+        let mut seen = HashSet::new();
+        let mut id = playlist.persistent_id.as_str();
+        while seen.insert(id) {
+            id = parents[id];
+        }
+        roots.insert(id);
+        mark_reachable(id, &children, &mut reachable);
+    }
+
+    let roots: Vec<&PlaylistInfo> =
+        included.iter().copied().filter(|p| roots.contains(p.persistent_id.as_str())).collect();
+    let mut rows = Vec::with_capacity(included.len());
+    let mut visited = HashSet::new();
+    push_rows(&roots, 0, &children, collapsed, &mut visited, &mut rows);
+    rows
+}
+
+/// id とその子孫を（折りたたみに関係なく）reachable に入れる
+fn mark_reachable<'a>(id: &'a str, children: &HashMap<&str, Vec<&'a PlaylistInfo>>, reachable: &mut HashSet<&'a str>) {
+    if !reachable.insert(id) {
+        return;
+    }
+    for child in children.get(id).into_iter().flatten() {
+        mark_reachable(&child.persistent_id, children, reachable);
+    }
+}
+
+/// 保存した検索の見出しと各検索の行（保存した検索がなければ空）
+pub fn saved_search_rows(searches: &[SavedSearch], collapsed: &BTreeSet<String>) -> Vec<PlaylistTreeRow> {
+    if searches.is_empty() {
+        return Vec::new();
+    }
+    let expanded = !collapsed.contains(SAVED_SEARCHES_ID);
+    let mut rows = vec![PlaylistTreeRow {
+        name: "Saved Searches".to_string(),
+        persistent_id: SAVED_SEARCHES_ID.to_string(),
+        parent_id: String::new(),
+        depth: 0,
+        kind: RowKind::Playlist(PlaylistKind::Folder),
+        expanded,
+    }];
+    if expanded {
+        rows.extend(searches.iter().map(|search| PlaylistTreeRow {
+            name: search.name.clone(),
+            persistent_id: format!("{}{}", SAVED_SEARCH_ID_PREFIX, search.name),
+            parent_id: SAVED_SEARCHES_ID.to_string(),
+            depth: 1,
+            kind: RowKind::SavedSearch,
+            expanded: false,
+        }));
+    }
+    rows
+}
+
+fn push_rows<'a>(
+    nodes: &[&'a PlaylistInfo],
+    depth: usize,
+    children: &HashMap<&str, Vec<&'a PlaylistInfo>>,
+    collapsed: &BTreeSet<String>,
+    visited: &mut HashSet<&'a str>,
+    rows: &mut Vec<PlaylistTreeRow>,
+) {
+    for node in nodes {
+        // 壊れた親子関係（循環）でも同じ項目を二度出さない
+        if !visited.insert(node.persistent_id.as_str()) {
+            continue;
+        }
+        let is_folder = node.kind == PlaylistKind::Folder;
+        let expanded = is_folder && !collapsed.contains(&node.persistent_id);
+        rows.push(PlaylistTreeRow {
+            name: node.name.clone(),
+            persistent_id: node.persistent_id.clone(),
+            parent_id: node.parent_id.clone(),
+            depth,
+            kind: RowKind::Playlist(node.kind),
+            expanded,
+        });
+        if expanded {
+            if let Some(kids) = children.get(node.persistent_id.as_str()) {
+                push_rows(kids, depth + 1, children, collapsed, visited, rows);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(id: &str, parent: &str, kind: PlaylistKind) -> PlaylistInfo {
+        PlaylistInfo { name: format!("{} name", id), persistent_id: id.to_string(), parent_id: parent.to_string(), kind }
+    }
+
+    fn folder(id: &str, parent: &str) -> PlaylistInfo {
+        info(id, parent, PlaylistKind::Folder)
+    }
+
+    fn user(id: &str, parent: &str) -> PlaylistInfo {
+        info(id, parent, PlaylistKind::User)
+    }
+
+    /// (ID, 深さ) の並び
+    fn layout(playlists: &[PlaylistInfo], collapsed: &[&str]) -> Vec<(String, usize)> {
+        let collapsed = collapsed.iter().map(|s| s.to_string()).collect();
+        visible_rows(playlists, &collapsed, |_| true).into_iter().map(|r| (r.persistent_id, r.depth)).collect()
+    }
+
+    fn rows(expected: &[(&str, usize)]) -> Vec<(String, usize)> {
+        expected.iter().map(|&(id, depth)| (id.to_string(), depth)).collect()
+    }
+
+    #[test]
+    fn children_follow_their_folder() {
+        let playlists = [folder("F", ""), user("A", ""), folder("G", "F"), user("B", "G"), user("C", "F")];
+        assert_eq!(layout(&playlists, &[]), rows(&[("F", 0), ("G", 1), ("B", 2), ("C", 1), ("A", 0)]));
+        assert_eq!(layout(&playlists, &["G"]), rows(&[("F", 0), ("G", 1), ("C", 1), ("A", 0)]));
+        assert_eq!(layout(&playlists, &["F"]), rows(&[("F", 0), ("A", 0)]));
+    }
+
+    #[test]
+    fn orphans_are_top_level() {
+        let playlists = [user("A", "missing"), folder("F", ""), user("B", "F"), user("S", "S")];
+        assert_eq!(layout(&playlists, &[]), rows(&[("A", 0), ("F", 0), ("B", 1), ("S", 0)]));
+
+        // 除外したプレイリストの子も最上位に出す
+        let playlists = [info("P", "", PlaylistKind::Smart), user("A", "P")];
+        let visible: Vec<_> = visible_rows(&playlists, &BTreeSet::new(), |p| p.kind != PlaylistKind::Smart)
+            .into_iter()
+            .map(|r| (r.persistent_id, r.depth))
+            .collect();
+        assert_eq!(visible, rows(&[("A", 0)]));
+    }
+
+    #[test]
+    fn cycles_are_broken_at_the_repeated_node() {
+        // F -> G -> F の循環と、その中のプレイリスト
+        let playlists = [user("A", ""), folder("F", "G"), folder("G", "F"), user("B", "G")];
+        assert_eq!(layout(&playlists, &[]), rows(&[("A", 0), ("F", 0), ("G", 1), ("B", 2)]));
+        // 折りたたんでいても出てくる
+        assert_eq!(layout(&playlists, &["F"]), rows(&[("A", 0), ("F", 0)]));
+
+        // 循環の外から循環にぶら下がる項目から見つけても、循環上の項目を最上位にする
+        let playlists = [user("B", "G"), folder("F", "G"), folder("G", "F")];
+        assert_eq!(layout(&playlists, &[]), rows(&[("G", 0), ("B", 1), ("F", 1)]));
+    }
+}
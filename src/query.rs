@@ -0,0 +1,735 @@
+// Search query language: the search box text is parsed into an expression tree and evaluated per track
+// Terms are ANDed, `OR` (uppercase) separates alternatives, `( )` groups and a leading `-` negates
+
+use std::fmt;
+use std::ops::{Bound, RangeBounds};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cache::{days_from_civil, local_utc_offset, CachedTrack};
+use crate::fuzzy::{self, FieldHighlights, Pattern};
+use crate::normalize;
+
+/// 日付で指定できる最後の年
+const MAX_YEAR: i64 = 9999;
+
+/// クエリの構文エラー（検索ボックスに表示する）
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryError(pub String);
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+type Range = (Bound<i64>, Bound<i64>);
+
+#[derive(Clone, Copy)]
+enum TextField {
+    Name,
+    Artist,
+    Album,
+}
+
+#[derive(Clone, Copy)]
+enum NumberField {
+    Year,
+    Plays,
+    Rating,
+    Time,    // 秒
+    Added,   // Unix time
+    Played,  // Unix time（未再生は一致しない）
+}
+
+enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    /// 一般検索語（name / artist / album のどれかに含まれる）
+    Word(Word),
+    /// name: / artist: / album:（引用符で囲んだ値は正規化せずに完全一致）
+    Text { field: TextField, value: Word },
+    Number { field: NumberField, range: Range },
+    Fav(bool),
+}
+
+struct Word {
+    /// 入力のまま（引用符は除く）
+    text: String,
+    /// 正規化して大文字小文字もそろえたもの
+    folded: String,
+    /// 正規化だけしたもの
+    cased: String,
+    has_uppercase: bool,
+    /// 引用符で囲まれていた（正規化せずにそのまま比べる）
+    exact: bool,
+    pattern: Pattern,
+}
+
+/// 解析済みの検索クエリ
+pub struct Query {
+    expr: Expr,
+}
+
+/// 一致したときのスコアと強調位置（あいまい検索のみ意味を持つ）
+#[derive(Default)]
+struct Hit {
+    score: i32,
+    highlights: FieldHighlights,
+}
+
+impl Query {
+    /// 検索ボックスの文字列を解析する。空なら全曲に一致する
+    pub fn parse(text: &str) -> Result<Self, QueryError> {
+        let tokens = tokenize(text)?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+        let mut parser = Parser { tokens, pos: 0, now };
+        let expr = parser.parse_or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(QueryError("unexpected )".to_string()));
+        }
+        Ok(Self { expr })
+    }
+
+    pub fn matches(&self, track: &CachedTrack) -> bool {
+        self.expr.eval(track, false).is_some()
+    }
+
+    /// あいまい検索: 一般検索語を部分列として一致させたスコアと強調位置
+    pub fn fuzzy_match(&self, track: &CachedTrack) -> Option<(i32, FieldHighlights)> {
+        let mut hit = self.expr.eval(track, true)?;
+        for positions in &mut hit.highlights {
+            positions.sort_unstable();
+            positions.dedup();
+        }
+        Some((hit.score, hit.highlights))
+    }
+
+    /// 一致するトラックが必ず含んでいる文字列（インデックスでの絞り込み用）
+    /// OR や否定の中の語は必須ではないので含めない。あいまい検索では一般検索語も含めない
+    pub fn needles(&self, fuzzy: bool) -> Vec<&str> {
+        let mut needles = Vec::new();
+        self.expr.collect_needles(fuzzy, &mut needles);
+        needles
+    }
+}
+
+impl Expr {
+    fn eval(&self, track: &CachedTrack, fuzzy: bool) -> Option<Hit> {
+        match self {
+            Expr::And(items) => {
+                let mut hit = Hit::default();
+                for item in items {
+                    let h = item.eval(track, fuzzy)?;
+                    hit.score += h.score;
+                    for (all, field) in hit.highlights.iter_mut().zip(h.highlights) {
+                        all.extend(field);
+                    }
+                }
+                Some(hit)
+            }
+            Expr::Or(branches) => branches
+                .iter()
+                .filter_map(|b| b.eval(track, fuzzy))
+                .max_by_key(|hit| hit.score),
+            // 否定は部分一致で判定する（あいまい一致で除外すると広すぎる）
+            Expr::Not(inner) => match inner.eval(track, false) {
+                Some(_) => None,
+                None => Some(Hit::default()),
+            },
+            Expr::Word(word) if fuzzy => {
+                let (score, highlights) = fuzzy::match_fields(std::slice::from_ref(&word.pattern), &track.search_key, track.search_fields())?;
+                Some(Hit { score, highlights })
+            }
+            Expr::Word(word) => word.matches(track).then(Hit::default),
+            Expr::Text { field, value } => value.matches_field(track, *field).then(Hit::default),
+            Expr::Number { field, range } => {
+                let value = match field {
+                    NumberField::Year => Some(track.year as i64),
+                    NumberField::Plays => Some(track.played_count as i64),
+                    NumberField::Rating => Some(track.rating as i64),
+                    NumberField::Time => parse_clock(&track.time),
+                    NumberField::Added => Some(track.date_added as i64),
+                    NumberField::Played => (track.played_date > 0).then_some(track.played_date as i64),
+                };
+                value.filter(|v| range.contains(v)).map(|_| Hit::default())
+            }
+            Expr::Fav(fav) => (track.favorited == *fav).then(Hit::default),
+        }
+    }
+
+    fn collect_needles<'a>(&'a self, fuzzy: bool, out: &mut Vec<&'a str>) {
+        match self {
+            Expr::And(items) => items.iter().for_each(|item| item.collect_needles(fuzzy, out)),
+            Expr::Word(word) if !fuzzy => out.extend(word.needle()),
+            Expr::Text { value, .. } => out.extend(value.needle()),
+            _ => {}
+        }
+    }
+}
+
+impl Word {
+    fn new(text: String, exact: bool) -> Self {
+        let cased = normalize::normalize(&text);
+        Self {
+            folded: normalize::fold_case(&cased),
+            cased,
+            has_uppercase: text.chars().any(|c| c.is_uppercase()),
+            exact,
+            pattern: Pattern::new(&text),
+            text,
+        }
+    }
+
+    /// 全て小文字の場合は case insensitive、大文字が含まれる場合は case sensitive
+    /// どちらも正規化したキーで比べる。引用符で囲んだ語は元の文字列にそのまま含まれるか調べる
+    fn matches(&self, track: &CachedTrack) -> bool {
+        if !self.exact {
+            let key = &track.search_key;
+            if self.has_uppercase {
+                key.cased.contains(self.cased.as_str())
+            } else {
+                key.folded.contains(self.folded.as_str())
+            }
+        } else if self.text.contains(' ') {
+            // フィールドの境目をまたぎうる語だけ連結して調べる
+            format!("{} {} {}", track.name, track.artist, track.album).contains(self.text.as_str())
+        } else {
+            track.name.contains(self.text.as_str())
+                || track.artist.contains(self.text.as_str())
+                || track.album.contains(self.text.as_str())
+        }
+    }
+
+    /// フィールド指定: 引用符で囲んだ値は完全一致、それ以外はスマートケース部分一致
+    fn matches_field(&self, track: &CachedTrack, field: TextField) -> bool {
+        let (raw, index) = match field {
+            TextField::Name => (&track.name, 0),
+            TextField::Artist => (&track.artist, 1),
+            TextField::Album => (&track.album, 2),
+        };
+        if self.exact {
+            *raw == self.text
+        } else if self.has_uppercase {
+            track.search_key.field(index, true).contains(self.cased.as_str())
+        } else {
+            track.search_key.field(index, false).contains(self.folded.as_str())
+        }
+    }
+
+    /// インデックスでの絞り込みに使う文字列
+    /// 引用符で囲んだ語は正規化したキーに含まれるとは限らないので、英数字と空白だけのときに限る
+    fn needle(&self) -> Option<&str> {
+        let plain = self.text.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ');
+        (!self.exact || plain).then_some(self.folded.as_str())
+    }
+}
+
+#[derive(Clone)]
+enum Token {
+    Open,
+    Close,
+    Or,
+    Not,
+    Term(String),
+}
+
+/// クエリを引用符を尊重してトークン化
+/// 例: `-artist:"James Blake" (love OR night)` -> [Not, "artist:\"James Blake\"", Open, "love", Or, "night", Close]
+/// 引用符は語の先頭か `field:` の直後でだけ始まる（"Don't" の ' はただの文字）
+/// 語の先頭の引用符は閉じる引用符がなければただの文字（'90s）。`field:` の直後で閉じていなければエラー
+/// 閉じていない ( もただの文字（"Song (feat" はそのまま検索する）
+fn tokenize(query: &str) -> Result<Vec<Token>, QueryError> {
+    let (tokens, unclosed) = tokenize_with(query, &[])?;
+    if unclosed.is_empty() {
+        return Ok(tokens);
+    }
+    Ok(tokenize_with(query, &unclosed)?.0)
+}
+
+/// literal_opens に挙げた位置（文字数）の ( は文字として扱う
+/// 閉じなかった ( の位置も返す
+fn tokenize_with(query: &str, literal_opens: &[usize]) -> Result<(Vec<Token>, Vec<usize>), QueryError> {
+    fn flush(current: &mut String, tokens: &mut Vec<Token>) {
+        if !current.is_empty() {
+            let text = std::mem::take(current);
+            tokens.push(if text == "OR" { Token::Or } else { Token::Term(text) });
+        }
+    }
+
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut opens = Vec::new();
+    let mut chars = query.chars().enumerate().peekable();
+
+    while let Some((i, ch)) = chars.next() {
+        if let Some(q) = quote {
+            current.push(ch);
+            if ch == q {
+                quote = None;
+            }
+            continue;
+        }
+        match ch {
+            '"' | '\'' if current.ends_with(':') || (current.is_empty() && chars.clone().any(|(_, c)| c == ch)) => {
+                quote = Some(ch);
+                current.push(ch);
+            }
+            ' ' | '\t' => flush(&mut current, &mut tokens),
+            '(' if current.is_empty() && !literal_opens.contains(&i) => {
+                tokens.push(Token::Open);
+                opens.push(i);
+            }
+            ')' if !opens.is_empty() => {
+                flush(&mut current, &mut tokens);
+                tokens.push(Token::Close);
+                opens.pop();
+            }
+            '-' if current.is_empty() && chars.peek().is_some_and(|(_, c)| !c.is_whitespace()) => {
+                tokens.push(Token::Not);
+            }
+            _ => current.push(ch),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(QueryError("unclosed quote".to_string()));
+    }
+    flush(&mut current, &mut tokens);
+    Ok((tokens, opens))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    now: i64,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    /// or := and ("OR" and)*
+    fn parse_or(&mut self) -> Result<Expr, QueryError> {
+        let mut branches = vec![self.parse_and()?];
+        while matches!(self.peek(), Some(Token::Or)) {
+            self.pos += 1;
+            if matches!(self.peek(), None | Some(Token::Close) | Some(Token::Or)) {
+                return Err(QueryError("OR needs a term on both sides".to_string()));
+            }
+            branches.push(self.parse_and()?);
+        }
+        Ok(if branches.len() == 1 { branches.remove(0) } else { Expr::Or(branches) })
+    }
+
+    /// and := unary*（OR か ) まで）
+    fn parse_and(&mut self) -> Result<Expr, QueryError> {
+        let mut items = Vec::new();
+        while !matches!(self.peek(), None | Some(Token::Or) | Some(Token::Close)) {
+            items.push(self.parse_unary()?);
+        }
+        if items.is_empty() && matches!(self.peek(), Some(Token::Or)) {
+            return Err(QueryError("OR needs a term on both sides".to_string()));
+        }
+        Ok(if items.len() == 1 { items.remove(0) } else { Expr::And(items) })
+    }
+
+    /// unary := "-" unary | "(" or ")" | term
+    fn parse_unary(&mut self) -> Result<Expr, QueryError> {
+        let token = self.tokens[self.pos].clone();
+        self.pos += 1;
+        match token {
+            Token::Not => {
+                if matches!(self.peek(), None | Some(Token::Or) | Some(Token::Close)) {
+                    return Err(QueryError("- needs a term after it".to_string()));
+                }
+                Ok(Expr::Not(Box::new(self.parse_unary()?)))
+            }
+            Token::Open => {
+                let inner = self.parse_or()?;
+                if !matches!(self.peek(), Some(Token::Close)) {
+                    return Err(QueryError("missing )".to_string()));
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Token::Term(text) => parse_term(&text, self.now),
+            // parse_and が OR と ) の手前で止まるので来ない
+            Token::Or | Token::Close => Err(QueryError("unexpected token".to_string())),
+        }
+    }
+}
+
+/// フィールド指定（大文字小文字を区別しない）か一般検索語
+fn parse_term(token: &str, now: i64) -> Result<Expr, QueryError> {
+    if let Some((name, value)) = token.split_once(':') {
+        let field = name.to_lowercase();
+        let text_field = match field.as_str() {
+            "name" => Some(TextField::Name),
+            "artist" => Some(TextField::Artist),
+            "album" => Some(TextField::Album),
+            _ => None,
+        };
+        if let Some(field) = text_field {
+            let Some((value, exact)) = parse_filter_value(value) else {
+                return Err(QueryError(format!("{}: needs a value", name)));
+            };
+            return Ok(Expr::Text { field, value: Word::new(value, exact) });
+        }
+
+        let number_field = match field.as_str() {
+            "year" => Some(NumberField::Year),
+            "plays" => Some(NumberField::Plays),
+            "rating" => Some(NumberField::Rating),
+            "time" => Some(NumberField::Time),
+            "added" => Some(NumberField::Added),
+            "played" => Some(NumberField::Played),
+            _ => None,
+        };
+        if let Some(field) = number_field {
+            if value.is_empty() {
+                return Err(QueryError(format!("{}: needs a value", name)));
+            }
+            let range = match field {
+                NumberField::Year | NumberField::Plays | NumberField::Rating => {
+                    parse_range(value, |v| v.parse().ok()).ok_or_else(|| {
+                        QueryError(format!("{}: expects a number, >N, <N or A..B", name))
+                    })?
+                }
+                NumberField::Time => parse_range(value, parse_duration)
+                    .ok_or_else(|| QueryError(format!("{}: expects a duration like 3:00 or 5m", name)))?,
+                NumberField::Added | NumberField::Played => parse_date_range(value, now).ok_or_else(|| {
+                    QueryError(format!("{}: expects an age like 30d or a date like 2024-01-31", name))
+                })?,
+            };
+            return Ok(Expr::Number { field, range });
+        }
+
+        if field == "fav" {
+            return match value.to_lowercase().as_str() {
+                "true" | "yes" | "1" => Ok(Expr::Fav(true)),
+                "false" | "no" | "0" => Ok(Expr::Fav(false)),
+                _ => Err(QueryError(format!("{}: expects true or false", name))),
+            };
+        }
+    }
+
+    // 知らないフィールド名は "Mission:" のような語として扱う。引用符で囲んだ語はそのままのフレーズ
+    let word = match parse_filter_value(token) {
+        Some((inner, true)) => Word::new(inner, true),
+        _ => Word::new(token.to_string(), false),
+    };
+    Ok(Expr::Word(word))
+}
+
+/// フィルタ値をパース: 引用符で囲まれていれば (値, true)、そうでなければ (値, false)
+fn parse_filter_value(value: &str) -> Option<(String, bool)> {
+    if value.is_empty() {
+        return None;
+    }
+
+    // "" または '' で囲まれている場合は完全一致
+    if value.len() >= 2 &&
+       ((value.starts_with('"') && value.ends_with('"')) ||
+        (value.starts_with('\'') && value.ends_with('\''))) {
+        let inner = &value[1..value.len()-1];
+        if inner.is_empty() {
+            return None;
+        }
+        Some((inner.to_string(), true))
+    } else {
+        Some((value.to_string(), false))
+    }
+}
+
+/// "N"（等しい）、">N" ">=N" "<N" "<=N" "=N"、"A..B" "A.." "..B"（両端を含む）
+fn parse_range(value: &str, parse: impl Fn(&str) -> Option<i64>) -> Option<Range> {
+    if let Some((lo, hi)) = value.split_once("..") {
+        if lo.is_empty() && hi.is_empty() {
+            return None;
+        }
+        let lo = if lo.is_empty() { Bound::Unbounded } else { Bound::Included(parse(lo)?) };
+        let hi = if hi.is_empty() { Bound::Unbounded } else { Bound::Included(parse(hi)?) };
+        return Some((lo, hi));
+    }
+    let range = if let Some(v) = value.strip_prefix(">=") {
+        (Bound::Included(parse(v)?), Bound::Unbounded)
+    } else if let Some(v) = value.strip_prefix("<=") {
+        (Bound::Unbounded, Bound::Included(parse(v)?))
+    } else if let Some(v) = value.strip_prefix('>') {
+        (Bound::Excluded(parse(v)?), Bound::Unbounded)
+    } else if let Some(v) = value.strip_prefix('<') {
+        (Bound::Unbounded, Bound::Excluded(parse(v)?))
+    } else {
+        let v = parse(value.strip_prefix('=').unwrap_or(value))?;
+        (Bound::Included(v), Bound::Included(v))
+    };
+    Some(range)
+}
+
+/// "3:08" / "1:02:03" を秒にする
+pub fn parse_clock(text: &str) -> Option<i64> {
+    let mut seconds: i64 = 0;
+    let mut parts = 0;
+    for part in text.split(':') {
+        seconds = seconds.checked_mul(60)?.checked_add(part.trim().parse::<i64>().ok()?)?;
+        parts += 1;
+    }
+    (parts >= 2).then_some(seconds)
+}
+
+/// "3:00" のような時刻形式か "5m" / "90s"
+fn parse_duration(text: &str) -> Option<i64> {
+    if text.contains(':') {
+        return parse_clock(text);
+    }
+    let (n, unit) = split_unit(text)?;
+    match unit {
+        's' => Some(n),
+        'm' => n.checked_mul(60),
+        'h' => n.checked_mul(3600),
+        _ => None,
+    }
+}
+
+/// "30d" を (30, 'd') に分ける（単位は最後の1文字。マルチバイトでもよい）
+fn split_unit(text: &str) -> Option<(i64, char)> {
+    let (i, unit) = text.char_indices().last()?;
+    Some((text[..i].parse().ok()?, unit))
+}
+
+/// 日付の値 1つ分を Unix time の区間 [start, end) にする
+/// "30d" などの経過時間は現在からさかのぼった一点、"2024" / "2024-03" / "2024-03-15" はその期間（ローカル時刻）
+fn parse_moment(text: &str, now: i64) -> Option<(i64, i64)> {
+    if let Some(age) = parse_age(text) {
+        let at = now.checked_sub(age)?;
+        return Some((at, at.checked_add(1)?));
+    }
+    let parts: Vec<i64> = text.split(['-', '/']).map(|p| p.parse().ok()).collect::<Option<_>>()?;
+    // 年は 1〜9999 に限る（days_from_civil が桁あふれしない範囲）
+    let year = parts.first().copied().filter(|y| (1..=MAX_YEAR).contains(y))?;
+    let local = |y: i64, m: i64, d: i64| {
+        days_from_civil(y, m, d).checked_mul(86400)?.checked_sub(local_utc_offset())
+    };
+    match parts[1..] {
+        [] => Some((local(year, 1, 1)?, local(year.checked_add(1)?, 1, 1)?)),
+        [m] if (1..=12).contains(&m) => {
+            let (ny, nm) = if m == 12 { (year.checked_add(1)?, 1) } else { (year, m + 1) };
+            Some((local(year, m, 1)?, local(ny, nm, 1)?))
+        }
+        [m, d] if (1..=12).contains(&m) && (1..=31).contains(&d) => {
+            let start = local(year, m, d)?;
+            Some((start, start.checked_add(86400)?))
+        }
+        _ => None,
+    }
+}
+
+/// "12h" "30d" "2w" "6m" "1y" を秒にする（月は30日、年は365日）
+fn parse_age(text: &str) -> Option<i64> {
+    let (n, unit) = split_unit(text)?;
+    let day = 86400;
+    let unit_seconds = match unit {
+        'h' => 3600,
+        'd' => day,
+        'w' => 7 * day,
+        'm' => 30 * day,
+        'y' => 365 * day,
+        _ => return None,
+    };
+    n.checked_mul(unit_seconds)
+}
+
+/// added: / played: の値
+/// 経過時間は "<30d"（30日以内）">1y"（1年より前）、日付は ">2024-01-01"（その日より後）のように比べる
+/// 範囲 "A..B" は両端を含む期間。単独の経過時間 "30d" と "=30d" は "<=30d" と同じ
+fn parse_date_range(value: &str, now: i64) -> Option<Range> {
+    if let Some((lo, hi)) = value.split_once("..") {
+        // 経過時間と日付が混ざっても、両端を含む区間にする
+        let lo = if lo.is_empty() { None } else { Some(parse_moment(lo, now)?) };
+        let hi = if hi.is_empty() { None } else { Some(parse_moment(hi, now)?) };
+        return match (lo, hi) {
+            (Some(a), Some(b)) => Some((Bound::Included(a.0.min(b.0)), Bound::Excluded(a.1.max(b.1)))),
+            (Some(a), None) => Some((Bound::Included(a.0), Bound::Unbounded)),
+            (None, Some(b)) => Some((Bound::Unbounded, Bound::Excluded(b.1))),
+            (None, None) => None,
+        };
+    }
+
+    let (op, text) = [">=", "<=", ">", "<", "="]
+        .iter()
+        .find_map(|op| value.strip_prefix(op).map(|rest| (*op, rest)))
+        .unwrap_or(("", value));
+    if let Some(age) = parse_age(text) {
+        // 経過時間は大小が逆になる（<30d は 30日前より後）
+        let at = now.checked_sub(age)?;
+        return Some(match op {
+            "<" => (Bound::Excluded(at), Bound::Unbounded),
+            ">" => (Bound::Unbounded, Bound::Excluded(at)),
+            ">=" => (Bound::Unbounded, Bound::Included(at)),
+            _ => (Bound::Included(at), Bound::Unbounded),
+        });
+    }
+    let (start, end) = parse_moment(text, now)?;
+    Some(match op {
+        ">" => (Bound::Included(end), Bound::Unbounded),
+        ">=" => (Bound::Included(start), Bound::Unbounded),
+        "<" => (Bound::Unbounded, Bound::Excluded(start)),
+        "<=" => (Bound::Unbounded, Bound::Excluded(end)),
+        _ => (Bound::Included(start), Bound::Excluded(end)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multibyte_unit_is_rejected_without_panicking() {
+        assert_eq!(parse_duration("3分"), None);
+        assert_eq!(parse_age("30日"), None);
+        assert!(Query::parse("added:30日").is_err());
+        assert!(Query::parse("time:3分").is_err());
+        assert!(Query::parse("played:<1ヶ").is_err());
+    }
+
+    #[test]
+    fn units_are_parsed() {
+        assert_eq!(parse_duration("90s"), Some(90));
+        assert_eq!(parse_duration("5m"), Some(300));
+        assert_eq!(parse_duration("3:08"), Some(188));
+        assert_eq!(parse_age("2w"), Some(14 * 86400));
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_age(""), None);
+    }
+
+    #[test]
+    fn huge_values_are_errors() {
+        assert_eq!(parse_age("999999999999y"), None);
+        assert_eq!(parse_duration("9999999999999999h"), None);
+        assert_eq!(parse_clock("9999999999999999999:00"), None);
+        assert_eq!(parse_clock("999999999999999:00:00:00"), None);
+        assert!(Query::parse("added:<999999999999y").is_err());
+        assert!(Query::parse("time:<99999999999999999h").is_err());
+        assert!(Query::parse("added:99999999999").is_err());
+        assert!(Query::parse("added:99999999999-01-01").is_err());
+        assert!(Query::parse("added:>9223372036854775807d").is_err());
+    }
+
+    struct Fixture {
+        name: &'static str,
+        artist: &'static str,
+        album: &'static str,
+        year: u32,
+        plays: u32,
+        time: &'static str,
+        fav: bool,
+        days_ago: u64,
+    }
+
+    fn tracks() -> Vec<CachedTrack> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        [
+            Fixture { name: "Love Song", artist: "James Blake", album: "Overgrown", year: 2013, plays: 30, time: "4:05", fav: true, days_ago: 10 },
+            Fixture { name: "Night Drive", artist: "Chromatics", album: "Kill for Love", year: 2012, plays: 5, time: "2:50", fav: false, days_ago: 100 },
+            Fixture { name: "Live Forever", artist: "Oasis", album: "Definitely Maybe", year: 1994, plays: 21, time: "4:36", fav: false, days_ago: 400 },
+            Fixture { name: "Song (feat. Someone)", artist: "Various", album: "Collabs", year: 1999, plays: 0, time: "2:59", fav: true, days_ago: 5 },
+        ]
+        .into_iter()
+        .map(|t| CachedTrack::new(
+            t.name.to_string(), t.artist.to_string(), t.album.to_string(), String::new(),
+            now - t.days_ago * 86400, t.year, 1, 1, t.time.to_string(), t.plays, t.fav, 0, 0, String::new(),
+        ))
+        .collect()
+    }
+
+    /// 一致した曲の名前
+    fn search(query: &str) -> Vec<&'static str> {
+        let query = Query::parse(query).unwrap();
+        let names = ["Love Song", "Night Drive", "Live Forever", "Song (feat. Someone)"];
+        tracks().iter().zip(names).filter(|(t, _)| query.matches(t)).map(|(_, n)| n).collect()
+    }
+
+    fn error(query: &str) -> String {
+        Query::parse(query).err().map(|e| e.to_string()).unwrap_or_default()
+    }
+
+    #[test]
+    fn terms_are_anded_and_or_separates() {
+        assert_eq!(search("love blake"), vec!["Love Song"]);
+        assert_eq!(search("blake OR oasis"), vec!["Love Song", "Live Forever"]);
+        // 小文字の or はただの語
+        assert!(search("blake or oasis").is_empty());
+    }
+
+    #[test]
+    fn negation_and_parentheses() {
+        assert_eq!(search("love -blake"), vec!["Night Drive"]);
+        assert_eq!(search("(night OR live) -oasis"), vec!["Night Drive"]);
+        assert_eq!(search("-(love OR song)"), vec!["Live Forever"]);
+        // 語の途中の - はただの文字
+        assert!(search("a-ha").is_empty());
+    }
+
+    #[test]
+    fn unbalanced_parenthesis_is_text() {
+        assert_eq!(search("Song (feat"), vec!["Song (feat. Someone)"]);
+        assert_eq!(search("(feat"), vec!["Song (feat. Someone)"]);
+        assert_eq!(search("(love OR night) (feat"), Vec::<&str>::new());
+        assert_eq!(search("someone)"), vec!["Song (feat. Someone)"]);
+    }
+
+    #[test]
+    fn number_filters() {
+        assert_eq!(search("year:1990..1999"), vec!["Live Forever", "Song (feat. Someone)"]);
+        assert_eq!(search("year:2012"), vec!["Night Drive"]);
+        assert_eq!(search("plays:>20"), vec!["Love Song", "Live Forever"]);
+        assert_eq!(search("plays:<=5"), vec!["Night Drive", "Song (feat. Someone)"]);
+        assert_eq!(search("time:<3:00"), vec!["Night Drive", "Song (feat. Someone)"]);
+        assert_eq!(search("time:>4m"), vec!["Love Song", "Live Forever"]);
+    }
+
+    #[test]
+    fn fav_and_added_filters() {
+        assert_eq!(search("fav:true"), vec!["Love Song", "Song (feat. Someone)"]);
+        assert_eq!(search("fav:no"), vec!["Night Drive", "Live Forever"]);
+        assert_eq!(search("added:<30d"), vec!["Love Song", "Song (feat. Someone)"]);
+        assert_eq!(search("added:>1y"), vec!["Live Forever"]);
+        assert_eq!(search("fav:true added:<7d"), vec!["Song (feat. Someone)"]);
+    }
+
+    #[test]
+    fn field_filters() {
+        assert_eq!(search("artist:blake"), vec!["Love Song"]);
+        assert_eq!(search("album:love"), vec!["Night Drive"]);
+        assert_eq!(search("album:\"Kill for Love\""), vec!["Night Drive"]);
+        assert!(search("album:\"kill for love\"").is_empty());
+        // 知らないフィールド名はただの語
+        assert!(search("mission:impossible").is_empty());
+    }
+
+    #[test]
+    fn parse_errors_are_reported() {
+        assert_eq!(error("artist:\"James"), "unclosed quote");
+        assert_eq!(error("love OR"), "OR needs a term on both sides");
+        assert_eq!(error("OR love"), "OR needs a term on both sides");
+        assert_eq!(error("-OR love"), "- needs a term after it");
+        assert_eq!(error("artist:"), "artist: needs a value");
+        assert_eq!(error("year:abc"), "year: expects a number, >N, <N or A..B");
+        assert_eq!(error("time:soon"), "time: expects a duration like 3:00 or 5m");
+        assert_eq!(error("added:yesterday"), "added: expects an age like 30d or a date like 2024-01-31");
+        assert_eq!(error("fav:maybe"), "fav: expects true or false");
+    }
+
+    #[test]
+    fn dates_in_range_still_parse() {
+        assert!(Query::parse("added:2024").is_ok());
+        assert!(Query::parse("added:>2024-06-01").is_ok());
+        assert!(Query::parse("added:9999-12").is_ok());
+        assert!(Query::parse("played:>1y").is_ok());
+    }
+}
@@ -0,0 +1,181 @@
+// AppleScript / JXA source builders
+// Values are never spliced into script text by hand: strings are always quoted as literals
+
+use std::fmt::Write;
+
+/// AppleScript の文字列リテラルにする
+/// `\` と `"` をエスケープし、改行・タブはエスケープシーケンスにする
+pub fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// テンプレートに埋め込む値
+enum Arg {
+    Text(String),
+    Integer(i64),
+}
+
+/// テンプレートから AppleScript を組み立てる
+/// テンプレート中の `$name` を型付きの値で置き換える（文字列は必ず quote される）
+/// テンプレートは 'static なので、実行時の値がスクリプトの構造に入り込むことはない
+///
+/// ```ignore
+/// let script = AppleScript::new(r#"play (first track whose name is $name)"#)
+///     .text("name", title)
+///     .build()?;
+/// ```
+pub struct AppleScript {
+    template: &'static str,
+    args: Vec<(&'static str, Arg)>,
+}
+
+impl AppleScript {
+    pub fn new(template: &'static str) -> Self {
+        Self { template, args: Vec::new() }
+    }
+
+    /// 文字列を `$name` に埋め込む
+    pub fn text(mut self, name: &'static str, value: &str) -> Self {
+        self.args.push((name, Arg::Text(value.to_string())));
+        self
+    }
+
+    /// 整数を `$name` に埋め込む
+    pub fn int(mut self, name: &'static str, value: i64) -> Self {
+        self.args.push((name, Arg::Integer(value)));
+        self
+    }
+
+    /// 置換は1回の走査で行い、埋め込んだ値の中の `$` は再解釈しない
+    /// 未定義の `$name` はテンプレートの誤り（デバッグビルドでは assert、リリースではエラー）
+    pub fn build(&self) -> anyhow::Result<String> {
+        let mut out = String::with_capacity(self.template.len());
+        let mut rest = self.template;
+        while let Some(pos) = rest.find('$') {
+            out.push_str(&rest[..pos]);
+            let after = &rest[pos + 1..];
+            let len = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            let name = &after[..len];
+            if name.is_empty() {
+                out.push('$');
+            } else {
+                match self.args.iter().find(|(n, _)| *n == name) {
+                    Some((_, Arg::Text(value))) => out.push_str(&quote(value)),
+                    Some((_, Arg::Integer(value))) => {
+                        let _ = write!(out, "{}", value);
+                    }
+                    None => {
+                        debug_assert!(false, "AppleScript template has no value for ${}", name);
+                        anyhow::bail!("AppleScript template has no value for ${}", name);
+                    }
+                }
+            }
+            rest = &after[len..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+/// JXA の関数本体の前に `const args = <JSON>;` を置く
+/// JSON は JS の式としてそのまま評価できる（U+2028/2029 は古いエンジン向けにエスケープする）
+pub fn jxa_with_args(body: &str, args: &serde_json::Value) -> String {
+    let json = args.to_string().replace('\u{2028}', "\\u2028").replace('\u{2029}', "\\u2029");
+    format!("const args = {};\n{}", json, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // 曲名・プレイリスト名として入りうる悪意のある文字列
+    const INJECTION: &str = r#"" & do shell script "rm -rf ~" & ""#;
+
+    #[test]
+    fn quote_escapes_quotes_and_backslashes() {
+        assert_eq!(quote(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote(r"C:\path\"), r#""C:\\path\\""#);
+        assert_eq!(quote(r#"\""#), r#""\\\"""#);
+    }
+
+    #[test]
+    fn quote_escapes_control_characters() {
+        assert_eq!(quote("a\r\nb\tc"), r#""a\r\nb\tc""#);
+    }
+
+    #[test]
+    fn quote_keeps_shell_and_unicode_characters() {
+        assert_eq!(quote("$HOME `id` $(ls)"), r#""$HOME `id` $(ls)""#);
+        assert_eq!(quote("夜に駆ける 🎵 Ünïcödé"), "\"夜に駆ける 🎵 Ünïcödé\"");
+        assert_eq!(quote(""), r#""""#);
+    }
+
+    #[test]
+    fn quote_neutralizes_injection() {
+        assert_eq!(quote(INJECTION), r#""\" & do shell script \"rm -rf ~\" & \"""#);
+    }
+
+    #[test]
+    fn build_quotes_every_value() {
+        let script = AppleScript::new(r#"play (first track whose name is $name) -- $n"#)
+            .text("name", INJECTION)
+            .int("n", -3)
+            .build()
+            .unwrap();
+        assert_eq!(
+            script,
+            r#"play (first track whose name is "\" & do shell script \"rm -rf ~\" & \"") -- -3"#
+        );
+    }
+
+    #[test]
+    fn build_does_not_reinterpret_dollar_in_values() {
+        let script = AppleScript::new("$a $b $")
+            .text("a", "$b")
+            .text("b", "x")
+            .build()
+            .unwrap();
+        assert_eq!(script, r#""$b" "x" $"#);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "no value for $missing")]
+    fn build_asserts_on_undefined_name() {
+        let _ = AppleScript::new("play $missing").build();
+    }
+
+    #[test]
+    fn jxa_args_are_json_literals() {
+        let script = jxa_with_args("run(args);", &json!({ "name": INJECTION }));
+        assert_eq!(
+            script,
+            "const args = {\"name\":\"\\\" & do shell script \\\"rm -rf ~\\\" & \\\"\"};\nrun(args);"
+        );
+    }
+
+    #[test]
+    fn jxa_args_escape_control_and_line_separators() {
+        let script = jxa_with_args("", &json!(["a\\b\r\n\t`${x}`", "夜🎵\u{2028}\u{2029}"]));
+        assert_eq!(
+            script,
+            "const args = [\"a\\\\b\\r\\n\\t`${x}`\",\"夜🎵\\u2028\\u2029\"];\n"
+        );
+    }
+}
@@ -0,0 +1,163 @@
+// Grouped search results: one header row per album or artist, collapsible like playlist folders
+// Groups appear in the order of their first result; inside a group tracks keep the result order
+
+use std::collections::{BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cache::CachedTrack;
+
+/// 検索結果のまとめ方（v キーで切り替え、settings.json に保存する）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchGrouping {
+    #[default]
+    Off,
+    Album,
+    Artist,
+}
+
+impl SearchGrouping {
+    /// Off → Album → Artist → Off
+    pub fn next(self) -> Self {
+        match self {
+            SearchGrouping::Off => SearchGrouping::Album,
+            SearchGrouping::Album => SearchGrouping::Artist,
+            SearchGrouping::Artist => SearchGrouping::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SearchGrouping::Off => "ungrouped",
+            SearchGrouping::Album => "by album",
+            SearchGrouping::Artist => "by artist",
+        }
+    }
+
+    /// グループの名前（見出しに出す）
+    fn name(self, item: &CachedTrack) -> &str {
+        match self {
+            SearchGrouping::Off => "",
+            SearchGrouping::Album => &item.album,
+            SearchGrouping::Artist => &item.artist,
+        }
+    }
+
+    /// 名前と組にするアーティスト
+    /// アルバムはアルバムアーティスト（コンピレーションを曲のアーティストごとに分けない）
+    /// アルバムアーティストが空なら曲のアーティストを使う
+    fn artist(self, item: &CachedTrack) -> &str {
+        match self {
+            SearchGrouping::Album => item.album_artist_or_artist(),
+            _ => &item.artist,
+        }
+    }
+}
+
+/// グループの識別: (名前, アーティスト)
+/// 同じ名前の別のアルバム（"Greatest Hits" など）を1つにまとめないよう、アルバムはアーティストと組にする
+pub type GroupKey = (String, String);
+
+/// 見出し1行ぶんのグループ
+#[derive(Debug, Clone)]
+pub struct SearchGroup {
+    pub name: String,  // アルバム名またはアーティスト名
+    pub artist: String,  // アルバムアーティスト（アーティストでまとめたときは name と同じ）
+    pub tracks: Vec<usize>,  // search_results の添字（結果の順）
+}
+
+impl SearchGroup {
+    /// 折りたたみの状態もこのキーで覚える
+    pub fn key(&self) -> GroupKey {
+        (self.name.clone(), self.artist.clone())
+    }
+}
+
+/// 検索結果の表示行
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchRow {
+    Header(usize),  // search_groups の添字
+    Track(usize),  // search_results の添字
+}
+
+/// 結果（tracks の添字）をグループに分ける（Off なら空）
+pub fn build_groups(tracks: &[CachedTrack], results: &[usize], grouping: SearchGrouping) -> Vec<SearchGroup> {
+    if grouping == SearchGrouping::Off {
+        return Vec::new();
+    }
+    let mut groups: Vec<SearchGroup> = Vec::new();
+    let mut positions: HashMap<(&str, &str), usize> = HashMap::new();
+    for (i, &t) in results.iter().enumerate() {
+        let Some(item) = tracks.get(t) else { continue };
+        let g = *positions.entry((grouping.name(item), grouping.artist(item))).or_insert_with(|| {
+            groups.push(SearchGroup {
+                name: grouping.name(item).to_string(),
+                artist: grouping.artist(item).to_string(),
+                tracks: Vec::new(),
+            });
+            groups.len() - 1
+        });
+        groups[g].tracks.push(i);
+    }
+    groups
+}
+
+/// 表示する行（折りたたんだグループは見出しだけ）。グループがなければ結果をそのまま並べる
+pub fn visible_rows(item_count: usize, groups: &[SearchGroup], collapsed: &BTreeSet<GroupKey>) -> Vec<SearchRow> {
+    if groups.is_empty() {
+        return (0..item_count).map(SearchRow::Track).collect();
+    }
+    let mut rows = Vec::with_capacity(item_count + groups.len());
+    for (g, group) in groups.iter().enumerate() {
+        rows.push(SearchRow::Header(g));
+        if !collapsed.contains(&group.key()) {
+            rows.extend(group.tracks.iter().map(|&i| SearchRow::Track(i)));
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(name: &str, artist: &str, album: &str, album_artist: &str) -> CachedTrack {
+        CachedTrack::new(
+            name.to_string(), artist.to_string(), album.to_string(), album_artist.to_string(),
+            0, 0, 0, 0, String::new(), 0, false, 0, 0, String::new(),
+        )
+    }
+
+    #[test]
+    fn compilation_is_one_album_group() {
+        let tracks = vec![
+            track("One", "Artist A", "Hits 2020", "Various Artists"),
+            track("Two", "Artist B", "Hits 2020", "Various Artists"),
+            track("Three", "Artist C", "Hits 2020", "Various Artists"),
+        ];
+        let groups = build_groups(&tracks, &[0, 1, 2], SearchGrouping::Album);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].key(), ("Hits 2020".to_string(), "Various Artists".to_string()));
+        assert_eq!(groups[0].tracks, vec![0, 1, 2]);
+
+        // アーティストでまとめると曲のアーティストごと
+        assert_eq!(build_groups(&tracks, &[0, 1, 2], SearchGrouping::Artist).len(), 3);
+    }
+
+    #[test]
+    fn same_title_without_album_artist_is_split_by_artist() {
+        let tracks = vec![
+            track("One", "Artist A", "Greatest Hits", ""),
+            track("Two", "Artist B", "Greatest Hits", ""),
+            track("Three", "Artist A", "Greatest Hits", ""),
+        ];
+        let groups = build_groups(&tracks, &[0, 1, 2], SearchGrouping::Album);
+        let keys: Vec<GroupKey> = groups.iter().map(SearchGroup::key).collect();
+        assert_eq!(keys, vec![
+            ("Greatest Hits".to_string(), "Artist A".to_string()),
+            ("Greatest Hits".to_string(), "Artist B".to_string()),
+        ]);
+        assert_eq!(groups[0].tracks, vec![0, 2]);
+    }
+}
@@ -0,0 +1,100 @@
+// Trigram postings over the normalized search keys, used by TrackCache::search to narrow candidates
+// The index never decides a match by itself; every candidate is still checked with the normal rules
+
+use std::collections::HashMap;
+
+use crate::normalize::fold_chars;
+
+type Trigram = [char; 3];
+
+/// トラック番号（tracks の添字）の trigram 転置インデックス
+#[derive(Debug, Clone, Default)]
+pub struct SearchIndex {
+    postings: HashMap<Trigram, Vec<u32>>,
+}
+
+impl SearchIndex {
+    /// (添字, 検索キー) の並びから作る
+    pub fn build<'a>(tracks: impl Iterator<Item = (usize, &'a str)>) -> Self {
+        let mut index = Self::default();
+        for (i, key) in tracks {
+            index.insert(i, key);
+        }
+        index
+    }
+
+    pub fn insert(&mut self, i: usize, key: &str) {
+        let i = i as u32;
+        for gram in key_trigrams(key) {
+            let list = self.postings.entry(gram).or_default();
+            // 末尾への追加がほとんどなので先に確認する
+            match list.last() {
+                Some(&last) if last >= i => {
+                    if let Err(pos) = list.binary_search(&i) {
+                        list.insert(pos, i);
+                    }
+                }
+                _ => list.push(i),
+            }
+        }
+    }
+
+    /// insert したときと同じ key を渡す
+    pub fn remove(&mut self, i: usize, key: &str) {
+        let i = i as u32;
+        for gram in key_trigrams(key) {
+            let Some(list) = self.postings.get_mut(&gram) else { continue };
+            if let Ok(pos) = list.binary_search(&i) {
+                list.remove(pos);
+            }
+            if list.is_empty() {
+                self.postings.remove(&gram);
+            }
+        }
+    }
+
+    /// needles（正規化済み）をすべて（大文字小文字を問わず）含みうるトラックの添字を昇順で返す
+    /// 3文字以上の needle がなく絞り込めないときは None（全件が候補）
+    pub fn candidates(&self, needles: &[&str]) -> Option<Vec<usize>> {
+        let mut grams: Vec<Trigram> = Vec::new();
+        for needle in needles {
+            grams.extend(trigrams(&fold_chars(needle).collect::<Vec<_>>()));
+        }
+        if grams.is_empty() {
+            return None;
+        }
+        grams.sort_unstable();
+        grams.dedup();
+
+        let mut lists = Vec::with_capacity(grams.len());
+        for gram in &grams {
+            match self.postings.get(gram) {
+                Some(list) => lists.push(list),
+                None => return Some(Vec::new()),
+            }
+        }
+        // 短いリストから順に積集合をとる
+        lists.sort_by_key(|l| l.len());
+        let mut result: Vec<u32> = lists[0].clone();
+        for list in &lists[1..] {
+            result.retain(|i| list.binary_search(i).is_ok());
+            if result.is_empty() {
+                break;
+            }
+        }
+        Some(result.into_iter().map(|i| i as usize).collect())
+    }
+}
+
+fn trigrams(chars: &[char]) -> impl Iterator<Item = Trigram> + '_ {
+    chars.windows(3).map(|w| [w[0], w[1], w[2]])
+}
+
+/// 検索キーの trigram（重複なし）
+fn key_trigrams(key: &str) -> Vec<Trigram> {
+    let chars: Vec<char> = fold_chars(key).collect();
+    let mut grams: Vec<Trigram> = trigrams(&chars).collect();
+    grams.sort_unstable();
+    grams.dedup();
+    grams
+}
@@ -0,0 +1,298 @@
+// Content list sorting: up to two keys, each with its own direction, picked in the sort menu
+// Sorting is stable, so ties keep the natural order (track order, playlist order, search ranking)
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cache::CachedTrack;
+use crate::music::ListItem;
+use crate::query::parse_clock;
+
+/// 同時に使えるキーの数
+pub const MAX_SORT_KEYS: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    Name,
+    Artist,
+    Album,
+    Year,
+    Duration,
+    Plays,
+    DateAdded,
+    Favorite,
+}
+
+impl SortField {
+    /// メニューに並べる順
+    pub const ALL: [SortField; 8] = [
+        SortField::Name,
+        SortField::Artist,
+        SortField::Album,
+        SortField::Year,
+        SortField::Duration,
+        SortField::Plays,
+        SortField::DateAdded,
+        SortField::Favorite,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortField::Name => "Name",
+            SortField::Artist => "Artist",
+            SortField::Album => "Album",
+            SortField::Year => "Year",
+            SortField::Duration => "Time",
+            SortField::Plays => "Plays",
+            SortField::DateAdded => "Added",
+            SortField::Favorite => "Favorite",
+        }
+    }
+
+    /// 初めて選んだときの向き（再生回数・追加日・お気に入りは多い・新しい方から）
+    fn default_descending(self) -> bool {
+        matches!(self, SortField::Plays | SortField::DateAdded | SortField::Favorite)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SortKey {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+/// 並べ替えを覚えておく表示の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortView {
+    Search,  // 検索結果と保存した検索
+    Album,
+    Playlist,
+}
+
+/// 表示の種類ごとのキー（空なら元の順）。settings.json に保存する
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ViewSorts {
+    #[serde(default)]
+    pub search: Vec<SortKey>,
+    #[serde(default)]
+    pub album: Vec<SortKey>,
+    #[serde(default)]
+    pub playlist: Vec<SortKey>,
+}
+
+impl ViewSorts {
+    pub fn get(&self, view: SortView) -> &[SortKey] {
+        match view {
+            SortView::Search => &self.search,
+            SortView::Album => &self.album,
+            SortView::Playlist => &self.playlist,
+        }
+    }
+
+    pub fn get_mut(&mut self, view: SortView) -> &mut Vec<SortKey> {
+        match view {
+            SortView::Search => &mut self.search,
+            SortView::Album => &mut self.album,
+            SortView::Playlist => &mut self.playlist,
+        }
+    }
+}
+
+/// 1番目のキーにする。すでに1番目なら向きを反転する。それまでの1番目は2番目になる
+pub fn set_primary(keys: &mut Vec<SortKey>, field: SortField) {
+    if let Some(first) = keys.first_mut().filter(|k| k.field == field) {
+        first.descending = !first.descending;
+        return;
+    }
+    keys.retain(|k| k.field != field);
+    keys.insert(0, SortKey { field, descending: field.default_descending() });
+    keys.truncate(MAX_SORT_KEYS);
+}
+
+/// 2番目のキーにする。すでに2番目なら向きを反転する。1番目がなければ1番目にする
+pub fn set_secondary(keys: &mut Vec<SortKey>, field: SortField) {
+    match keys.iter().position(|k| k.field == field) {
+        Some(1) => keys[1].descending = !keys[1].descending,
+        // 1番目のキーは2番目にできない（1番目がなくなる）
+        Some(_) => {}
+        None if keys.is_empty() => keys.push(SortKey { field, descending: field.default_descending() }),
+        None => {
+            keys.truncate(1);
+            keys.push(SortKey { field, descending: field.default_descending() });
+        }
+    }
+}
+
+/// "Plays ↓, Name ↑"（キーがなければ空）
+pub fn describe(keys: &[SortKey]) -> String {
+    keys.iter()
+        .map(|k| format!("{} {}", k.field.label(), if k.descending { "↓" } else { "↑" }))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// 比べる値（ListItem とキャッシュの曲の両方から作る）
+pub struct SortFields<'a> {
+    name: &'a str,
+    artist: &'a str,
+    album: &'a str,
+    year: u32,
+    time: &'a str,
+    played_count: u32,
+    date_added: u64,
+    favorited: bool,
+}
+
+impl<'a> SortFields<'a> {
+    /// 追加日は dates（persistent ID → 追加日）で引く
+    pub fn of_item(item: &'a ListItem, dates: &HashMap<String, u64>) -> Self {
+        SortFields {
+            name: &item.name,
+            artist: &item.artist,
+            album: &item.album,
+            year: item.year,
+            time: &item.time,
+            played_count: item.played_count,
+            date_added: dates.get(item.persistent_id.as_str()).copied().unwrap_or(0),
+            favorited: item.favorited,
+        }
+    }
+
+    pub fn of_track(track: &'a CachedTrack) -> Self {
+        SortFields {
+            name: &track.name,
+            artist: &track.artist,
+            album: &track.album,
+            year: track.year,
+            time: &track.time,
+            played_count: track.played_count,
+            date_added: track.date_added,
+            favorited: track.favorited,
+        }
+    }
+}
+
+/// キーの順に比べる（先のキーで差がつかなければ次のキー）
+pub fn compare_by_keys(a: &SortFields, b: &SortFields, keys: &[SortKey]) -> Ordering {
+    keys.iter()
+        .map(|key| {
+            let ordering = compare(a, b, key.field);
+            if key.descending { ordering.reverse() } else { ordering }
+        })
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// 追加日のキーがあるときだけ必要
+pub fn needs_dates(keys: &[SortKey]) -> bool {
+    keys.iter().any(|k| k.field == SortField::DateAdded)
+}
+
+fn compare(a: &SortFields, b: &SortFields, field: SortField) -> Ordering {
+    match field {
+        SortField::Name => compare_text(a.name, b.name),
+        SortField::Artist => compare_text(a.artist, b.artist),
+        SortField::Album => compare_text(a.album, b.album),
+        SortField::Year => a.year.cmp(&b.year),
+        SortField::Duration => parse_clock(a.time).cmp(&parse_clock(b.time)),
+        SortField::Plays => a.played_count.cmp(&b.played_count),
+        SortField::DateAdded => a.date_added.cmp(&b.date_added),
+        SortField::Favorite => a.favorited.cmp(&b.favorited),
+    }
+}
+
+/// 大文字小文字を区別せずに比べる（文字列を作らない）
+fn compare_text(a: &str, b: &str) -> Ordering {
+    a.chars()
+        .flat_map(char::to_lowercase)
+        .cmp(b.chars().flat_map(char::to_lowercase))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, artist: &str, time: &str, plays: u32, id: &str) -> ListItem {
+        ListItem {
+            name: name.to_string(),
+            artist: artist.to_string(),
+            album: String::new(),
+            time: time.to_string(),
+            year: 0,
+            track_number: 0,
+            played_count: plays,
+            favorited: plays > 10,
+            persistent_id: id.to_string(),
+        }
+    }
+
+    fn key(field: SortField, descending: bool) -> SortKey {
+        SortKey { field, descending }
+    }
+
+    /// 安定ソートで並べた名前
+    fn sorted(items: &[ListItem], keys: &[SortKey], dates: &HashMap<String, u64>) -> Vec<String> {
+        let mut items = items.to_vec();
+        items.sort_by(|a, b| compare_by_keys(&SortFields::of_item(a, dates), &SortFields::of_item(b, dates), keys));
+        items.into_iter().map(|i| i.name).collect()
+    }
+
+    fn items() -> Vec<ListItem> {
+        vec![
+            item("beta", "X", "10:00", 3, "B"),
+            item("Alpha", "Y", "9:59", 20, "A"),
+            item("gamma", "X", "0:30", 3, "G"),
+            item("Delta", "Y", "1:00:00", 0, "D"),
+        ]
+    }
+
+    #[test]
+    fn comparators_follow_each_field() {
+        let none = HashMap::new();
+        assert_eq!(sorted(&items(), &[key(SortField::Name, false)], &none), ["Alpha", "beta", "Delta", "gamma"]);
+        assert_eq!(sorted(&items(), &[key(SortField::Name, true)], &none), ["gamma", "Delta", "beta", "Alpha"]);
+        // 時間は文字列ではなく長さで比べる
+        assert_eq!(sorted(&items(), &[key(SortField::Duration, false)], &none), ["gamma", "Alpha", "beta", "Delta"]);
+        assert_eq!(sorted(&items(), &[key(SortField::Favorite, true)], &none), ["Alpha", "beta", "gamma", "Delta"]);
+
+        let dates = HashMap::from([("A".to_string(), 30), ("B".to_string(), 10), ("G".to_string(), 20)]);
+        assert!(needs_dates(&[key(SortField::DateAdded, true)]));
+        assert_eq!(sorted(&items(), &[key(SortField::DateAdded, true)], &dates), ["Alpha", "gamma", "beta", "Delta"]);
+
+        // 1番目のキーで並ばなければ2番目のキー
+        let keys = [key(SortField::Artist, false), key(SortField::Plays, true)];
+        assert_eq!(sorted(&items(), &keys, &none), ["beta", "gamma", "Alpha", "Delta"]);
+    }
+
+    #[test]
+    fn ties_keep_the_original_order() {
+        let none = HashMap::new();
+        // Plays が同じ beta と gamma は元の順のまま
+        assert_eq!(sorted(&items(), &[key(SortField::Plays, false)], &none), ["Delta", "beta", "gamma", "Alpha"]);
+        assert_eq!(sorted(&items(), &[key(SortField::Plays, true)], &none), ["Alpha", "beta", "gamma", "Delta"]);
+        let names: Vec<String> = items().into_iter().map(|i| i.name).collect();
+        assert_eq!(sorted(&items(), &[], &none), names);
+        assert_eq!(sorted(&items(), &[key(SortField::Year, true)], &none), names);
+    }
+
+    #[test]
+    fn menu_keys_toggle_and_shift() {
+        let mut keys = Vec::new();
+        set_primary(&mut keys, SortField::Name);
+        assert_eq!(keys, [key(SortField::Name, false)]);
+        set_primary(&mut keys, SortField::Name);
+        assert_eq!(keys, [key(SortField::Name, true)]);
+        set_primary(&mut keys, SortField::Plays);
+        assert_eq!(keys, [key(SortField::Plays, true), key(SortField::Name, true)]);
+        set_secondary(&mut keys, SortField::Name);
+        assert_eq!(keys, [key(SortField::Plays, true), key(SortField::Name, false)]);
+        set_secondary(&mut keys, SortField::Plays);
+        assert_eq!(keys, [key(SortField::Plays, true), key(SortField::Name, false)]);
+        set_secondary(&mut keys, SortField::Year);
+        assert_eq!(keys, [key(SortField::Plays, true), key(SortField::Year, false)]);
+        assert_eq!(describe(&keys), "Plays ↓, Year ↑");
+    }
+}
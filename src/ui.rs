@@ -2,13 +2,16 @@ use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, BorderType, Borders, Paragraph},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph},
     Frame,
 };
 use unicode_width::UnicodeWidthStr;
 
-use crate::app::{App, Focus};
-use crate::music::{ListItem, TrackInfo};
+use crate::app::{App, Focus, HistorySearch, SortMenu};
+use crate::search_groups::{SearchGrouping, SearchRow};
+use crate::music::{PlaylistKind, TrackInfo};
+use crate::playlist_tree::RowKind;
+use crate::sort::{self, SortField};
 
 const BG_ACCENT: Color = Color::Rgb(60, 60, 80);
 const BG_SELECTED: Color = Color::Rgb(50, 50, 60);
@@ -17,6 +20,7 @@ const TEXT_PRIMARY: Color = Color::Rgb(255, 255, 255);
 const TEXT_SECONDARY: Color = Color::Rgb(160, 160, 180);
 const TEXT_DIM: Color = Color::Rgb(100, 100, 120);
 const ACCENT_GREEN: Color = Color::Rgb(80, 220, 120);
+const TEXT_ERROR: Color = Color::Rgb(255, 110, 110);
 
 fn accent_color(app: &App) -> Color {
     let (r, g, b) = app.highlight_color.rgb();
@@ -62,6 +66,9 @@ pub fn draw(frame: &mut Frame, app: &App) {
 
     draw_left_column(frame, app, body_chunks[0]);
     draw_content(frame, app, body_chunks[1]);
+    if let Some(menu) = app.sort_menu {
+        draw_sort_menu(frame, app, menu, body_chunks[1]);
+    }
     draw_footer(frame, app, main_chunks[2]);
 }
 
@@ -155,7 +162,7 @@ fn draw_help(frame: &mut Frame, app: &App) {
 
     // カードサイズ
     let card_width = 60u16;
-    let card_height = 20u16;
+    let card_height = 41u16;
 
     // 中央に配置
     let card_x = area.x + (area.width.saturating_sub(card_width)) / 2;
@@ -206,7 +213,7 @@ fn draw_help(frame: &mut Frame, app: &App) {
         Line::from(""),
         Line::from(vec![
             Span::styled("Exact Match", Style::default().fg(accent_color(app))),
-            Span::styled(" (use quotes)", Style::default().fg(TEXT_DIM)),
+            Span::styled(" (use quotes, compared as typed)", Style::default().fg(TEXT_DIM)),
         ]),
         Line::from(vec![
             Span::styled("  artist:\"ABC\"", Style::default().fg(TEXT_PRIMARY)),
@@ -217,6 +224,52 @@ fn draw_help(frame: &mut Frame, app: &App) {
             Span::styled("     Name is exactly \"OK\"", Style::default().fg(TEXT_DIM)),
         ]),
         Line::from(""),
+        Line::from(vec![
+            Span::styled("Numbers & Dates", Style::default().fg(accent_color(app))),
+            Span::styled(" (also >=, <=, =)", Style::default().fg(TEXT_DIM)),
+        ]),
+        Line::from(vec![
+            Span::styled("  year:1990..1999", Style::default().fg(TEXT_PRIMARY)),
+            Span::styled("   Range, both ends included", Style::default().fg(TEXT_DIM)),
+        ]),
+        Line::from(vec![
+            Span::styled("  plays:>20", Style::default().fg(TEXT_PRIMARY)),
+            Span::styled("         Play count (also rating:)", Style::default().fg(TEXT_DIM)),
+        ]),
+        Line::from(vec![
+            Span::styled("  time:<3:00", Style::default().fg(TEXT_PRIMARY)),
+            Span::styled("        Duration (m:ss or 5m)", Style::default().fg(TEXT_DIM)),
+        ]),
+        Line::from(vec![
+            Span::styled("  added:<30d", Style::default().fg(TEXT_PRIMARY)),
+            Span::styled("        Added in the last 30 days", Style::default().fg(TEXT_DIM)),
+        ]),
+        Line::from(vec![
+            Span::styled("  played:>1y", Style::default().fg(TEXT_PRIMARY)),
+            Span::styled("        Not played for a year", Style::default().fg(TEXT_DIM)),
+        ]),
+        Line::from(vec![
+            Span::styled("  fav:true", Style::default().fg(TEXT_PRIMARY)),
+            Span::styled("          Favorites only", Style::default().fg(TEXT_DIM)),
+        ]),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("Logic", Style::default().fg(accent_color(app))),
+            Span::styled(" (terms are ANDed)", Style::default().fg(TEXT_DIM)),
+        ]),
+        Line::from(vec![
+            Span::styled("  -artist:foo", Style::default().fg(TEXT_PRIMARY)),
+            Span::styled("       Exclude", Style::default().fg(TEXT_DIM)),
+        ]),
+        Line::from(vec![
+            Span::styled("  love OR night", Style::default().fg(TEXT_PRIMARY)),
+            Span::styled("     Either one", Style::default().fg(TEXT_DIM)),
+        ]),
+        Line::from(vec![
+            Span::styled("  (a OR b) c", Style::default().fg(TEXT_PRIMARY)),
+            Span::styled("        Grouping", Style::default().fg(TEXT_DIM)),
+        ]),
+        Line::from(""),
         Line::from(vec![
             Span::styled("Smart Case", Style::default().fg(accent_color(app))),
             Span::styled(" (without quotes)", Style::default().fg(TEXT_DIM)),
@@ -229,6 +282,32 @@ fn draw_help(frame: &mut Frame, app: &App) {
             Span::styled("  hasUppercase", Style::default().fg(TEXT_PRIMARY)),
             Span::styled(" → case-sensitive", Style::default().fg(TEXT_DIM)),
         ]),
+        Line::from(vec![
+            Span::styled("  ｱｲ あい é", Style::default().fg(TEXT_PRIMARY)),
+            Span::styled(" → アイ アイ e", Style::default().fg(TEXT_DIM)),
+        ]),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("Fuzzy Mode", Style::default().fg(accent_color(app))),
+            Span::styled(" (Tab in the search box)", Style::default().fg(TEXT_DIM)),
+        ]),
+        Line::from(vec![
+            Span::styled("  jmsblk", Style::default().fg(TEXT_PRIMARY)),
+            Span::styled("      Letters in order, best matches first", Style::default().fg(TEXT_DIM)),
+        ]),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("History", Style::default().fg(accent_color(app))),
+            Span::styled(" (in the search box)", Style::default().fg(TEXT_DIM)),
+        ]),
+        Line::from(vec![
+            Span::styled("  ↑ ↓  Ctrl-R", Style::default().fg(TEXT_PRIMARY)),
+            Span::styled("      Previous searches / search them", Style::default().fg(TEXT_DIM)),
+        ]),
+        Line::from(vec![
+            Span::styled("  Ctrl-S", Style::default().fg(TEXT_PRIMARY)),
+            Span::styled("            Save to the Playlists pane (d deletes)", Style::default().fg(TEXT_DIM)),
+        ]),
         Line::from(""),
         Line::from(vec![
             Span::styled("Press any key to close", Style::default().fg(TEXT_DIM)),
@@ -374,6 +453,12 @@ fn draw_left_column(frame: &mut Frame, app: &App, area: Rect) {
     draw_playlists(frame, app, chunks[2]);
 }
 
+/// "(i-search)`love': " の部分（見つからなくなったら failing を付ける）
+fn history_search_prompt(search: &HistorySearch) -> String {
+    let failing = if search.failing { "failing " } else { "" };
+    format!("({}i-search)`{}': ", failing, search.pattern)
+}
+
 fn draw_search_box(frame: &mut Frame, app: &App, area: Rect) {
     let is_focused = app.focus == Focus::Search;
     let border_color = if is_focused { border_focus_color(app) } else { BORDER_DIM };
@@ -390,7 +475,13 @@ fn draw_search_box(frame: &mut Frame, app: &App, area: Rect) {
     let inner = inner_area(area, 2, 1);
 
     // 検索入力行
-    let search_line = if app.search_mode {
+    let search_line = if let Some(search) = app.history_search.as_ref().filter(|_| app.search_mode) {
+        // Ctrl+R: 検索語と一致した履歴
+        Line::from(vec![
+            Span::styled(history_search_prompt(search), Style::default().fg(accent_color(app))),
+            Span::styled(&app.search_query, Style::default().fg(TEXT_PRIMARY)),
+        ])
+    } else if app.search_mode {
         if app.search_query.is_empty() {
             Line::from(vec![
                 Span::styled("Type to search...", Style::default().fg(TEXT_DIM)),
@@ -409,20 +500,49 @@ fn draw_search_box(frame: &mut Frame, app: &App, area: Rect) {
     let search_area = Rect { height: 1, ..inner };
     frame.render_widget(Paragraph::new(search_line), search_area);
 
+    // 検索モード中は右端にあいまい検索の表示
+    if app.search_mode && app.fuzzy_search {
+        frame.render_widget(
+            Paragraph::new("fuzzy").style(Style::default().fg(accent_color(app))).alignment(ratatui::layout::Alignment::Right),
+            search_area,
+        );
+    }
+
     // 検索モード時はカーソルを検索入力位置に配置（IME対応）
     if app.search_mode && app.focus == Focus::Search {
-        // カーソル位置までの表示幅を計算
-        let cursor_width: usize = app.search_query.chars()
-            .take(app.search_cursor)
-            .map(|c| unicode_width::UnicodeWidthChar::width(c).unwrap_or(0))
-            .sum();
+        // カーソル位置までの表示幅を計算（履歴の検索中は検索語の後ろ）
+        let cursor_width: usize = match &app.history_search {
+            Some(search) => {
+                let prompt = history_search_prompt(search);
+                unicode_width::UnicodeWidthStr::width(prompt.as_str()) - 3
+            }
+            None => app.search_query.chars()
+                .take(app.search_cursor)
+                .map(|c| unicode_width::UnicodeWidthChar::width(c).unwrap_or(0))
+                .sum(),
+        };
         let cursor_x = search_area.x + cursor_width as u16;
         let cursor_y = search_area.y;
         frame.set_cursor_position((cursor_x, cursor_y));
     }
 
-    // キャッシュ状態表示
-    if is_caching {
+    // クエリの構文エラー（キャッシュ状態の代わりに表示）
+    if let Some(error) = app.search_error.as_ref().filter(|_| app.search_mode) {
+        if inner.height >= 2 {
+            let error_area = Rect {
+                y: inner.y + 1,
+                height: (inner.height - 1).min(2),
+                ..inner
+            };
+            frame.render_widget(
+                Paragraph::new(format!("⚠ {}", error))
+                    .style(Style::default().fg(TEXT_ERROR))
+                    .wrap(ratatui::widgets::Wrap { trim: true }),
+                error_area,
+            );
+        }
+    } else if is_caching {
+        // キャッシュ状態表示
         // キャッシュ中: 進捗と注意書き
         if inner.height >= 2 {
             let spinner_frames = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
@@ -613,7 +733,17 @@ fn draw_playlists(frame: &mut Frame, app: &App, area: Rect) {
 
     // タイトル
     let title_area = Rect { height: 1, ..inner };
-    if app.new_playlist_input_mode {
+    if app.save_search_input_mode {
+        // 保存する検索の名前入力モード
+        let prefix = "Save search as: ";
+        let title = Paragraph::new(Line::from(vec![
+            Span::styled(format!("{}{}", prefix, app.save_search_name), Style::default().fg(accent_color(app))),
+        ]));
+        frame.render_widget(title, title_area);
+        // カーソル位置を設定（IME対応）
+        let cursor_x = title_area.x + prefix.width() as u16 + app.save_search_name.width() as u16;
+        frame.set_cursor_position((cursor_x, title_area.y));
+    } else if app.new_playlist_input_mode {
         // 新規プレイリスト名入力モード
         let prefix = "New: ";
         let input_display = format!("{}{}", prefix, app.new_playlist_name);
@@ -633,7 +763,7 @@ fn draw_playlists(frame: &mut Frame, app: &App, area: Rect) {
         frame.render_widget(title, title_area);
     } else {
         // 通常モード
-        let playlist_count = app.playlists.len();
+        let playlist_count = app.playlist_tree.iter().filter(|p| p.kind != PlaylistKind::Folder).count();
         let title = Paragraph::new(Line::from(vec![
             Span::styled("Playlists", Style::default().fg(TEXT_PRIMARY).add_modifier(Modifier::BOLD)),
             Span::styled(format!(" ({})", playlist_count), Style::default().fg(TEXT_DIM)),
@@ -675,15 +805,26 @@ fn draw_playlists(frame: &mut Frame, app: &App, area: Rect) {
                     (Style::default().fg(TEXT_SECONDARY), " ", Color::Reset)
                 };
 
+                // フォルダの中身は深さに応じて字下げし、種類ごとの印を付ける（フォルダは開閉）
+                let indent = "  ".repeat(item.depth);
+                let marker = match item.kind {
+                    RowKind::Playlist(PlaylistKind::Folder) if item.expanded => "▾ ",
+                    RowKind::Playlist(PlaylistKind::Folder) => "▸ ",
+                    RowKind::Playlist(PlaylistKind::Smart) => "⚙ ",
+                    RowKind::Playlist(PlaylistKind::Subscription) => "☁ ",
+                    RowKind::Playlist(PlaylistKind::User) => "♪ ",
+                    RowKind::SavedSearch => "⌕ ",
+                };
                 let mut spans = vec![
                     Span::styled(prefix, Style::default().fg(accent_color(app)).bg(bg)),
+                    Span::styled(format!("{}{}", indent, marker), Style::default().fg(TEXT_DIM).bg(bg)),
                     Span::styled(&item.name, style),
                 ];
                 if is_refreshing {
                     spans.push(Span::styled(format!(" {}", spinner_char), Style::default().fg(accent_color(app)).bg(bg)));
                 }
                 // 行末まで背景色を埋める
-                let content_len = 1 + item.name.width() + if is_refreshing { 2 } else { 0 };
+                let content_len = 1 + indent.width() + marker.width() + item.name.width() + if is_refreshing { 2 } else { 0 };
                 let remaining = row_width.saturating_sub(content_len);
                 spans.push(Span::styled(" ".repeat(remaining), Style::default().bg(bg)));
 
@@ -729,16 +870,30 @@ fn draw_content(frame: &mut Frame, app: &App, area: Rect) {
     let title_area = Rect { height: 1, ..inner };
     let max_title_width = inner.width as usize - 2;
 
+    // 並べ替え中なら "· Plays ↓, Name ↑" を添える
+    let sort_keys = app.view_sorts.get(app.sort_view());
+    let sort_suffix = if sort_keys.is_empty() {
+        String::new()
+    } else {
+        format!(" · {}", sort::describe(sort_keys))
+    };
+
     if app.search_mode {
-        let title_text = format!("{} results", app.search_results.len());
+        let mode_suffix = if app.fuzzy_search { " (fuzzy)" } else { "" };
+        let group_suffix = match app.search_grouping {
+            SearchGrouping::Off => String::new(),
+            grouping => format!(" {} ({})", grouping.label(), app.search_groups.len()),
+        };
+        let title_text = format!("{} results{}{}", app.search_results.len(), mode_suffix, group_suffix);
         let title = Paragraph::new(Line::from(vec![
-            Span::styled(truncate(&title_text, max_title_width), Style::default().fg(TEXT_PRIMARY).add_modifier(Modifier::BOLD)),
+            Span::styled(truncate(&title_text, max_title_width.saturating_sub(sort_suffix.width())), Style::default().fg(TEXT_PRIMARY).add_modifier(Modifier::BOLD)),
+            Span::styled(&sort_suffix, Style::default().fg(TEXT_DIM)),
         ]));
         frame.render_widget(title, title_area);
     } else if is_album_detail {
         // アルバム詳細: "Album - Artist Year" の形式をパースして別スタイルで表示
-        let total_time = calculate_total_time(&app.content_items);
-        let time_suffix = format!(" [{}]", total_time);
+        let total_time = calculate_total_time(app.content_items.iter().map(|item| item.time.as_str()));
+        let time_suffix = format!(" [{}]{}", total_time, sort_suffix);
         let parts: Vec<&str> = app.content_title.splitn(2, " - ").collect();
         if parts.len() == 2 {
             let album = parts[0];
@@ -764,8 +919,8 @@ fn draw_content(frame: &mut Frame, app: &App, area: Rect) {
         }
     } else if is_playlist_detail {
         // プレイリスト詳細: プレイリスト名 + 合計時間を表示
-        let total_time = calculate_total_time(&app.content_items);
-        let time_suffix = format!(" [{}]", total_time);
+        let total_time = calculate_total_time(app.content_items.iter().map(|item| item.time.as_str()));
+        let time_suffix = format!(" [{}]{}", total_time, sort_suffix);
         let title = Paragraph::new(Line::from(vec![
             Span::styled(truncate(&app.content_title, max_title_width.saturating_sub(time_suffix.len())), Style::default().fg(TEXT_PRIMARY).add_modifier(Modifier::BOLD)),
             Span::styled(&time_suffix, Style::default().fg(TEXT_DIM)),
@@ -784,7 +939,7 @@ fn draw_content(frame: &mut Frame, app: &App, area: Rect) {
     }
 
     // Content list
-    let items = if app.search_mode { &app.search_results } else { &app.content_items };
+    let items = &app.content_items;
     let list_area = Rect {
         y: inner.y + 2,
         height: inner.height.saturating_sub(2),
@@ -801,7 +956,7 @@ fn draw_content(frame: &mut Frame, app: &App, area: Rect) {
         let loading = Paragraph::new(format!("{} Loading...", spinner_char))
             .style(Style::default().fg(accent_color(app)));
         frame.render_widget(loading, list_area);
-    } else if items.is_empty() {
+    } else if (app.search_mode && app.search_results.is_empty()) || (!app.search_mode && items.is_empty()) {
         let empty_msg = if app.search_mode {
             "No results found"
         } else {
@@ -859,14 +1014,23 @@ fn draw_content(frame: &mut Frame, app: &App, area: Rect) {
         };
         let data_visible = data_area.height as usize;
 
-        for (i, item) in items.iter().enumerate().skip(app.content_scroll).take(data_visible) {
-            let y = data_area.y + (i - app.content_scroll) as u16;
+        for (row, &search_row) in app.search_rows.iter().enumerate().skip(app.content_scroll).take(data_visible) {
+            let y = data_area.y + (row - app.content_scroll) as u16;
             if y >= data_area.y + data_area.height {
                 break;
             }
 
             let line_area = Rect { x: data_area.x, y, width: data_area.width, height: 1 };
-            let is_selected = i == app.content_selected;
+            let is_selected = row == app.content_selected;
+            let i = match search_row {
+                SearchRow::Track(i) => i,
+                SearchRow::Header(g) => {
+                    let line = search_group_header(app, g, is_selected && is_focused, data_area.width as usize);
+                    frame.render_widget(Paragraph::new(line), line_area);
+                    continue;
+                }
+            };
+            let Some(item) = app.search_track(i) else { continue };
 
             let (name_style, sub_style, prefix, bg) = if is_selected && is_focused {
                 (Style::default().fg(TEXT_PRIMARY).bg(BG_SELECTED),
@@ -882,18 +1046,25 @@ fn draw_content(frame: &mut Frame, app: &App, area: Rect) {
             let year_str = if item.year > 0 { item.year.to_string() } else { String::new() };
             let plays_str = if item.played_count > 0 { item.played_count.to_string() } else { String::new() };
 
-            let line = Paragraph::new(Line::from(vec![
+            // あいまい検索で一致した文字を強調
+            let no_highlights = Default::default();
+            let highlights = app.search_highlights.get(&app.search_results[i]).unwrap_or(&no_highlights);
+            let match_style = Style::default().fg(accent_color(app)).add_modifier(Modifier::BOLD);
+
+            let mut spans = vec![
                 Span::styled(prefix, Style::default().fg(accent_color(app)).bg(bg)),
                 Span::styled(pad_right(&seq_num, col_track), sub_style),
                 Span::styled(" ".repeat(col_gap), Style::default().bg(bg)),
-                Span::styled(pad_left(&truncate(&item.name, col_name.saturating_sub(1)), col_name), name_style),
-                Span::styled(pad_left(&truncate(&item.artist, col_artist.saturating_sub(1)), col_artist), sub_style),
-                Span::styled(pad_left(&truncate(&item.album, col_album.saturating_sub(1)), col_album), sub_style),
+            ];
+            spans.extend(highlighted_cell(&item.name, &highlights[0], col_name, name_style, match_style));
+            spans.extend(highlighted_cell(&item.artist, &highlights[1], col_artist, sub_style, match_style));
+            spans.extend(highlighted_cell(&item.album, &highlights[2], col_album, sub_style, match_style));
+            spans.extend([
                 Span::styled(pad_right(&item.time, col_time), sub_style),
                 Span::styled(pad_right(&year_str, col_year), sub_style),
                 Span::styled(pad_right(&plays_str, col_plays), sub_style),
-            ]));
-            frame.render_widget(line, line_area);
+            ]);
+            frame.render_widget(Paragraph::new(Line::from(spans)), line_area);
         }
     } else if is_album_detail {
         // アルバム詳細モード: テーブル形式で表示 (#, Name, Time, Plays)
@@ -1129,6 +1300,14 @@ fn draw_footer(frame: &mut Frame, app: &App, area: Rect) {
     let key_style = Style::default().fg(accent_color(app));
     let sep_style = Style::default().fg(TEXT_DIM);
 
+    // メッセージがあればコマンドガイドの代わりに表示（次のキー入力で消える）
+    if let Some(message) = &app.message {
+        let line = Line::from(Span::styled(message.as_str(), Style::default().fg(TEXT_PRIMARY)));
+        let paragraph = Paragraph::new(line).wrap(ratatui::widgets::Wrap { trim: false });
+        frame.render_widget(paragraph, area);
+        return;
+    }
+
     // 共通コマンド (すべてのカードで表示)
     let common_commands: Vec<(&str, &str)> = vec![
         ("c", "color"),
@@ -1136,7 +1315,29 @@ fn draw_footer(frame: &mut Frame, app: &App, area: Rect) {
         ("?", "help"),
     ];
 
-    let commands: Vec<(&str, &str)> = if app.new_playlist_input_mode {
+    let commands: Vec<(&str, &str)> = if app.sort_menu.is_some() {
+        // 並べ替えメニュー表示中
+        vec![
+            ("Return/1", "first key"),
+            ("2", "second key"),
+            ("x", "remove"),
+            ("r", "reset"),
+            ("j/k", "nav"),
+            ("Esc", "close"),
+        ]
+    } else if app.delete_saved_search_pending.is_some() {
+        // 保存した検索の削除の確認
+        vec![
+            ("y", "delete"),
+            ("n/Esc", "cancel"),
+        ]
+    } else if app.save_search_input_mode {
+        // 保存する検索の名前入力モード
+        vec![
+            ("Return", "save"),
+            ("Esc", "cancel"),
+        ]
+    } else if app.new_playlist_input_mode {
         // 新規プレイリスト名入力モード
         let mut cmds = vec![
             ("Return", "create"),
@@ -1149,28 +1350,38 @@ fn draw_footer(frame: &mut Frame, app: &App, area: Rect) {
         let mut cmds = vec![
             ("Return", "add"),
             ("j/k/g/G", "nav"),
+            ("h/l", "fold"),
             ("Esc", "cancel"),
         ];
         cmds.extend(common_commands.iter().cloned());
         cmds
     } else if app.search_mode {
         if app.focus == Focus::Content {
-            // 検索結果にフォーカス中
+            // 検索結果にフォーカス中（まとめて表示中は折りたたみも）
             let mut cmds = vec![
                 ("Return", "play"),
                 ("j/k/g/G", "nav"),
                 ("h", "back"),
                 ("l", "album"),
                 ("s", "sort"),
+                ("v", "group"),
+            ];
+            if app.search_grouping != SearchGrouping::Off {
+                cmds.push(("z/Z", "fold"));
+            }
+            cmds.extend([
+                ("S", "save"),
                 ("a", "add"),
                 ("Esc", "cancel"),
-            ];
+            ]);
             cmds.extend(common_commands.iter().cloned());
             cmds
         } else {
             // Searchカードにフォーカス中 (文字入力モードなので c / ? は表示しない)
             vec![
                 ("Return", "search"),
+                ("Tab", if app.fuzzy_search { "substring" } else { "fuzzy" }),
+                ("^S", "save"),
                 ("Esc", "cancel"),
             ]
         }
@@ -1182,6 +1393,7 @@ fn draw_footer(frame: &mut Frame, app: &App, area: Rect) {
             ("j/k/g/G", "nav"),
             ("h", "back"),
             ("l", "album"),
+            ("o", "sort"),
             ("a", "add"),
             ("c", "color"),
             ("/", "search"),
@@ -1197,6 +1409,7 @@ fn draw_footer(frame: &mut Frame, app: &App, area: Rect) {
             ("←→", "seek"),
             ("j/k/g/G", "nav"),
             ("h/l", "column"),
+            ("o", "sort"),
             ("a", "add"),
             ("c", "color"),
             ("/", "search"),
@@ -1204,18 +1417,24 @@ fn draw_footer(frame: &mut Frame, app: &App, area: Rect) {
             ("q", "quit"),
         ]
     } else if app.focus == Focus::Playlists {
-        // Playlistsカードにフォーカス中
-        vec![
+        // Playlistsカードにフォーカス中（保存した検索を選択中なら削除も）
+        let mut cmds = vec![
             ("Space", "play/pause"),
-            ("Return", "select"),
+            ("Return", "select/fold"),
             ("j/k/g/G", "nav"),
-            ("h/l", "column"),
+            ("h/l", "fold/column"),
+        ];
+        if app.playlists.get(app.playlists_selected).is_some_and(|row| row.saved_search_name().is_some()) {
+            cmds.push(("d", "delete search"));
+        }
+        cmds.extend([
             ("Tab", "pane"),
             ("c", "color"),
             ("/", "search"),
             ("?", "help"),
             ("q", "quit"),
-        ]
+        ]);
+        cmds
     } else {
         vec![
             ("Space", "play/pause"),
@@ -1248,6 +1467,75 @@ fn draw_footer(frame: &mut Frame, app: &App, area: Rect) {
     frame.render_widget(paragraph, area);
 }
 
+/// 並べ替えメニュー: 詳細カードの右上に重ねて表示（"1↓" "2↑" は何番目のキーでどちら向きか）
+fn draw_sort_menu(frame: &mut Frame, app: &App, menu: SortMenu, area: Rect) {
+    let keys = app.view_sorts.get(menu.view);
+    let card_width = 24u16.min(area.width);
+    let card_height = (SortField::ALL.len() as u16 + 3).min(area.height);
+    let card_area = Rect {
+        x: area.x + area.width.saturating_sub(card_width + 1),
+        y: area.y + 1,
+        width: card_width,
+        height: card_height,
+    };
+
+    frame.render_widget(Clear, card_area);
+    let card = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(accent_color(app)))
+        .title(" Sort ")
+        .title_style(Style::default().fg(TEXT_PRIMARY).add_modifier(Modifier::BOLD));
+    frame.render_widget(card, card_area);
+
+    let inner = inner_area(card_area, 2, 1);
+    let lines: Vec<Line> = SortField::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, &field)| {
+            let marker = keys
+                .iter()
+                .position(|k| k.field == field)
+                .map(|pos| format!("{}{}", pos + 1, if keys[pos].descending { "↓" } else { "↑" }))
+                .unwrap_or_default();
+            let style = if i == menu.selected {
+                Style::default().fg(TEXT_PRIMARY).bg(BG_ACCENT).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(TEXT_SECONDARY)
+            };
+            let label_width = (inner.width as usize).saturating_sub(3);
+            Line::from(vec![
+                Span::styled(pad_left(field.label(), label_width), style),
+                Span::styled(pad_right(&marker, 3), style.fg(accent_color(app))),
+            ])
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// 検索結果のグループ見出し: "▾ Album - Artist  12 tracks · 48:10"（折りたたみ中は ▸）
+fn search_group_header(app: &App, g: usize, highlighted: bool, width: usize) -> Line<'static> {
+    let group = &app.search_groups[g];
+    let marker = if app.is_search_group_collapsed(g) { "▸ " } else { "▾ " };
+    let (bg, prefix) = if highlighted { (BG_SELECTED, "▎") } else { (Color::Reset, " ") };
+
+    let times = group.tracks.iter().filter_map(|&i| app.search_track(i)).map(|t| t.time.as_str());
+    let count = if group.tracks.len() == 1 { "1 track".to_string() } else { format!("{} tracks", group.tracks.len()) };
+    let summary = format!("  {} · {}", count, calculate_total_time(times));
+    let name = match app.search_grouping {
+        SearchGrouping::Album if !group.artist.is_empty() => format!("{} - {}", group.name, group.artist),
+        _ => group.name.clone(),
+    };
+    let name_width = width.saturating_sub(prefix.width() + marker.width() + summary.width());
+
+    Line::from(vec![
+        Span::styled(prefix, Style::default().fg(accent_color(app)).bg(bg)),
+        Span::styled(marker, Style::default().fg(accent_color(app)).bg(bg)),
+        Span::styled(truncate(&name, name_width), Style::default().fg(TEXT_PRIMARY).bg(bg).add_modifier(Modifier::BOLD)),
+        Span::styled(summary, Style::default().fg(TEXT_DIM).bg(bg)),
+    ])
+}
+
 fn inner_area(area: Rect, h_padding: u16, v_padding: u16) -> Rect {
     Rect {
         x: area.x + h_padding,
@@ -1258,11 +1546,11 @@ fn inner_area(area: Rect, h_padding: u16, v_padding: u16) -> Rect {
 }
 
 /// アイテムリストの合計時間を計算
-fn calculate_total_time(items: &[ListItem]) -> String {
+fn calculate_total_time<'a>(times: impl IntoIterator<Item = &'a str>) -> String {
     let mut total_seconds = 0u32;
-    for item in items {
+    for time in times {
         // "M:SS" or "MM:SS" or "H:MM:SS" format
-        let parts: Vec<&str> = item.time.split(':').collect();
+        let parts: Vec<&str> = time.split(':').collect();
         if parts.len() == 2 {
             // M:SS or MM:SS
             if let (Ok(m), Ok(s)) = (parts[0].parse::<u32>(), parts[1].parse::<u32>()) {
@@ -1310,6 +1598,37 @@ fn truncate(s: &str, max_width: usize) -> String {
     result
 }
 
+/// pad_left(truncate(..)) と同じ幅のセルを、positions の文字だけ match_style にして返す
+fn highlighted_cell(text: &str, positions: &[usize], width: usize, style: Style, match_style: Style) -> Vec<Span<'static>> {
+    let shown = truncate(text, width.saturating_sub(1));
+    if positions.is_empty() {
+        return vec![Span::styled(pad_left(&shown, width), style)];
+    }
+
+    // 強調の有無が変わるところで区切る（省略記号 … は元の文字ではないので強調しない）
+    let kept = if shown != text { shown.chars().count() - 1 } else { usize::MAX };
+    let match_style = style.patch(match_style);
+    let mut spans: Vec<Span> = Vec::new();
+    let mut run = String::new();
+    let mut run_matched = false;
+    for (i, c) in shown.chars().enumerate() {
+        let matched = i < kept && positions.binary_search(&i).is_ok();
+        if matched != run_matched && !run.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut run), if run_matched { match_style } else { style }));
+        }
+        run_matched = matched;
+        run.push(c);
+    }
+    if !run.is_empty() {
+        spans.push(Span::styled(run, if run_matched { match_style } else { style }));
+    }
+    let padding = width.saturating_sub(shown.width());
+    if padding > 0 {
+        spans.push(Span::styled(" ".repeat(padding), style));
+    }
+    spans
+}
+
 /// 文字列を指定幅にパディング（全角文字対応、左寄せ）
 fn pad_left(s: &str, width: usize) -> String {
     let current_width = s.width();